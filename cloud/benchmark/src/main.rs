@@ -3,8 +3,10 @@ use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 use std::time::Instant;
 
+use hyperopic::node;
+use hyperopic::position::Position;
 use hyperopic::search::end::EmptyEndSignal;
-use hyperopic::search::{SearchParameters, ConcurrentTT};
+use hyperopic::search::{SearchOutcome, SearchParameters, ConcurrentTT};
 use itertools::Itertools;
 use lambda_payloads::benchmark::*;
 use lambda_runtime::{Context, Error, LambdaEvent, service_fn};
@@ -50,24 +52,20 @@ async fn handler(event: LambdaEvent<BenchStartEvent>) -> Result<BenchOutput, Err
                 cum_hash
             );
         }
-        let search_result = hyperopic::search::search(
-            position.into(),
-            SearchParameters {
-                end_signal: EmptyEndSignal,
-                table: Arc::new(ConcurrentTT::new(e.table_size)),
-                max_depth: Some(e.depth as u8),
-            },
-        )?;
-        search_result.best_move.hash(&mut hasher);
+        let search_result = search(position, e.depth, e.table_size)?;
+        hash_outcome(&search_result, &mut hasher);
         moves.push(search_result);
     }
 
+    let mate_flags = moves.iter().map(is_mate).collect::<Vec<_>>();
     let execution_times =
         moves.iter().map(|o| o.time.as_millis() as u64).sorted().collect::<Vec<_>>();
 
     let output = BenchOutput {
         depth_searched: e.depth,
         positions_searched: n,
+        mates_found: mate_flags.iter().filter(|&&mate| mate).count(),
+        mate_flags,
         memory_allocated_mb: event.context.env_config.memory as usize,
         min_search_time_millis: execution_times[0],
         max_search_time_millis: execution_times[n - 1],
@@ -79,3 +77,63 @@ async fn handler(event: LambdaEvent<BenchStartEvent>) -> Result<BenchOutput, Err
     log::info!("{}", serde_json::to_string(&output)?);
     Ok(output)
 }
+
+fn search(position: Position, depth: usize, table_size: usize) -> Result<SearchOutcome, Error> {
+    Ok(hyperopic::search::search(
+        position.into(),
+        SearchParameters {
+            max_depth: Some(depth as u8),
+            ..SearchParameters::new(EmptyEndSignal, Arc::new(ConcurrentTT::new(table_size)))
+        },
+    )?)
+}
+
+/// Whether the search found a forced mate for either side, the search stops deepening
+/// once it does so the depth of the outcome may be less than the requested depth.
+fn is_mate(outcome: &SearchOutcome) -> bool {
+    outcome.relative_eval.abs() == node::WIN_VALUE
+}
+
+/// Hash everything about the outcome which should be reproducible between runs so
+/// that search changes which don't alter the chosen move are still detected.
+fn hash_outcome(outcome: &SearchOutcome, hasher: &mut DefaultHasher) {
+    outcome.best_move.hash(hasher);
+    outcome.depth.hash(hasher);
+    outcome.nodes.hash(hasher);
+    outcome.relative_eval.hash(hasher);
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::Hasher;
+
+    use crate::{hash_outcome, is_mate, positions, search};
+
+    const MATE_IN_TWO: &str = "8/7B/5Q2/6p1/6k1/8/5K2/8 w - - 0 1";
+
+    fn combined_hash(n: usize, depth: usize) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        for position in positions::get(n) {
+            hash_outcome(&search(position, depth, 10_000).unwrap(), &mut hasher);
+        }
+        hasher.finish()
+    }
+
+    #[test]
+    fn hash_is_reproducible() {
+        assert_eq!(combined_hash(3, 3), combined_hash(3, 3));
+    }
+
+    #[test]
+    fn mate_flagged_and_ends_early() {
+        let depth = 5;
+        let mating = search(MATE_IN_TWO.parse().unwrap(), depth, 10_000).unwrap();
+        let standard = search(positions::get(1).remove(0), depth, 10_000).unwrap();
+        assert!(is_mate(&mating));
+        assert!(!is_mate(&standard));
+        assert!(mating.depth < depth as u8);
+        assert_eq!(depth as u8, standard.depth);
+        assert!(mating.nodes < standard.nodes);
+    }
+}
@@ -114,6 +114,7 @@ impl MoveChooser for MoveLambdaClient {
             moves_played: moves_played.to_owned(),
             features: vec![],
             table_size: None,
+            max_nodes: None,
             clock_millis: ChooseMoveEventClock {
                 increment: increment.as_millis() as u64,
                 remaining: remaining.as_millis() as u64,
@@ -10,7 +10,7 @@ use log::info;
 use hyperopic::openings::OpeningService;
 use hyperopic::position::Position;
 use hyperopic::timing::TimeAllocator;
-use hyperopic::{ComputeMoveInput, Engine, LookupMoveService};
+use hyperopic::{ComputeMoveInput, ComputeMoveInputBuilder, Engine, LookupMoveService};
 use lambda_payloads::chessmove::*;
 use lichess_api::LichessEndgameClient;
 use openings::{DynamoOpeningClient, OpeningTable};
@@ -29,15 +29,9 @@ async fn main() -> Result<(), Error> {
 async fn move_handler(event: LambdaEvent<ChooseMoveEvent>) -> Result<ChooseMoveOutput, Error> {
     let setup_start = Instant::now();
     let choose_move = &event.payload;
-    let position = choose_move.moves_played.parse::<Position>()?;
     let table_size = choose_move.table_size.unwrap_or(DEFAULT_TABLE_SIZE);
     let engine = Engine::new(table_size, load_lookup_services(&choose_move.features));
-    let input = ComputeMoveInput::new(
-        position,
-        Duration::from_millis(choose_move.clock_millis.remaining),
-        Duration::from_millis(choose_move.clock_millis.increment),
-        TimeAllocator::with_latency(Duration::from_millis(LATENCY_MILLIS)),
-    );
+    let input = create_input(choose_move)?;
     let setup_duration = setup_start.elapsed();
     info!("Setup time: {}ms", setup_duration.as_millis());
     let output = engine.compute_move(input)?;
@@ -47,10 +41,24 @@ async fn move_handler(event: LambdaEvent<ChooseMoveEvent>) -> Result<ChooseMoveO
             depth_searched: details.depth as usize,
             search_duration_millis: details.time.as_millis() as u64,
             eval: details.relative_eval,
+            nodes: details.nodes,
+            nps: details.nps(),
         }),
     })
 }
 
+fn create_input(choose_move: &ChooseMoveEvent) -> Result<ComputeMoveInput<Instant>, Error> {
+    let position = choose_move.moves_played.parse::<Position>()?;
+    Ok(ComputeMoveInputBuilder::clock(
+        position,
+        Duration::from_millis(choose_move.clock_millis.remaining),
+        Duration::from_millis(choose_move.clock_millis.increment),
+        TimeAllocator::with_latency(Duration::from_millis(LATENCY_MILLIS)),
+    )
+    .max_nodes(choose_move.max_nodes)
+    .build())
+}
+
 fn load_lookup_services(
     features: &Vec<ChooseMoveFeature>,
 ) -> Vec<Arc<dyn LookupMoveService + Send + Sync>> {
@@ -69,3 +77,23 @@ fn load_lookup_services(
     }
     services
 }
+
+#[cfg(test)]
+mod test {
+    use super::create_input;
+    use hyperopic::Engine;
+    use lambda_payloads::chessmove::ChooseMoveEvent;
+
+    #[test]
+    fn max_nodes_caps_search() {
+        let event = serde_json::from_str::<ChooseMoveEvent>(
+            r#"{"movesPlayed":"e2e4 e7e5","clockMillis":{"increment":0,"remaining":600000},"maxNodes":5000}"#,
+        )
+        .unwrap();
+        assert_eq!(Some(5000), event.max_nodes);
+        let output =
+            Engine::new(10_000, vec![]).compute_move(create_input(&event).unwrap()).unwrap();
+        let nodes = output.search_details.unwrap().nodes;
+        assert!(0 < nodes && nodes <= 5000, "Searched {} nodes", nodes);
+    }
+}
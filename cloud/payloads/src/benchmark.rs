@@ -11,6 +11,11 @@ pub struct BenchStartEvent {
 pub struct BenchOutput {
     pub positions_searched: usize,
     pub depth_searched: usize,
+    /// How many of the positions searched resolved to a forced mate
+    pub mates_found: usize,
+    /// For each position searched whether the search found a forced mate, in which
+    /// case it ends early without reaching the full depth
+    pub mate_flags: Vec<bool>,
     pub min_search_time_millis: u64,
     pub average_search_time_millis: u64,
     pub max_search_time_millis: u64,
@@ -10,6 +10,9 @@ pub struct ChooseMoveEvent {
     pub features: Vec<ChooseMoveFeature>,
     #[serde(rename = "tableSize", default)]
     pub table_size: Option<usize>,
+    /// Caps the nodes searched whatever the time allocation, bounding the invocation cost
+    #[serde(rename = "maxNodes", default)]
+    pub max_nodes: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
@@ -43,4 +46,54 @@ pub struct SearchDetails {
     #[serde(rename = "searchDurationMillis")]
     pub search_duration_millis: u64,
     pub eval: i32,
+    /// Total number of tree nodes visited in the search
+    #[serde(default)]
+    pub nodes: u64,
+    /// Nodes visited per second of search time
+    #[serde(default)]
+    pub nps: u64,
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ChooseMoveEvent, ChooseMoveOutput, SearchDetails};
+
+    #[test]
+    fn deserialize_with_telemetry() {
+        let output = serde_json::from_str::<ChooseMoveOutput>(
+            r#"{"bestMove":"e2e4","searchDetails":{"depthSearched":8,"searchDurationMillis":2000,"eval":35,"nodes":3000000,"nps":1500000}}"#,
+        )
+        .unwrap();
+        let details = output.search_details.unwrap();
+        assert_eq!(3_000_000, details.nodes);
+        assert_eq!(1_500_000, details.nps);
+        assert_eq!(details.nodes * 1000 / details.search_duration_millis, details.nps);
+    }
+
+    #[test]
+    fn deserialize_without_telemetry() {
+        let output = serde_json::from_str::<ChooseMoveOutput>(
+            r#"{"bestMove":"e2e4","searchDetails":{"depthSearched":8,"searchDurationMillis":2000,"eval":35}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            Some(SearchDetails {
+                depth_searched: 8,
+                search_duration_millis: 2000,
+                eval: 35,
+                nodes: 0,
+                nps: 0
+            }),
+            output.search_details
+        );
+    }
+
+    #[test]
+    fn deserialize_event_without_max_nodes() {
+        let event = serde_json::from_str::<ChooseMoveEvent>(
+            r#"{"movesPlayed":"e2e4","clockMillis":{"increment":0,"remaining":60000}}"#,
+        )
+        .unwrap();
+        assert_eq!(None, event.max_nodes);
+    }
 }
@@ -93,13 +93,19 @@ impl Transpositions for DebugTranspositions {
         if !pos.moves(&Moves::All).contains(m) {
             panic!("Bad node {} <-> {:?}", pos.to_string(), node_type)
         }
-        let entry = TableEntry { key: pos.key, root_index, depth, eval, node_type };
+        let entry = TableEntry { key: pos.key, root_index, generation: 0, depth, eval, node_type };
         *self.store[index].lock().unwrap() = Some((to_table_id(&pos), entry));
     }
 
     fn reset(&self) {
         todo!()
     }
+
+    fn hashfull(&self) -> u16 {
+        let sample = self.store.len().min(1000);
+        let occupied = self.store[..sample].iter().filter(|e| e.lock().unwrap().is_some()).count();
+        (occupied * 1000 / sample.max(1)) as u16
+    }
 }
 
 fn to_table_id(pos: &Position) -> String {
@@ -114,9 +120,11 @@ fn run_search(mut state: TreeNode, depth: usize, table_size: usize) {
         let outcome = hyperopic::search::search(
             state,
             SearchParameters {
-                end_signal: EmptyEndSignal,
-                table: Arc::new(DebugTranspositions::new(table_size)),
                 max_depth: Some(depth as u8),
+                ..SearchParameters::new(
+                    EmptyEndSignal,
+                    Arc::new(DebugTranspositions::new(table_size)),
+                )
             },
         );
         println!("{}", serde_json::to_string_pretty(&outcome.unwrap()).unwrap());
@@ -1,11 +1,16 @@
 use anyhow::anyhow;
+use hyperopic::constants::side;
 use hyperopic::position::Position;
+use hyperopic::timing::TimeAllocator;
 use lazy_static::lazy_static;
 use regex::{Captures, Regex};
 use std::fmt::{Display, Formatter};
 use std::str::FromStr;
 use std::time::Duration;
 
+/// Stands in for an unbounded search time
+pub const ONE_YEAR_IN_SECS: u64 = 60 * 60 * 24 * 365;
+
 // See https://gist.github.com/DOBRO/2592c6dad754ba67e6dcaec8c90165bf for a description of
 // the UCI interface.
 #[derive(Debug, Clone, PartialEq)]
@@ -19,6 +24,8 @@ pub enum Command {
     Debug(bool),
     Position(Position),
     Search(SearchParams),
+    Perft(u8),
+    SetOption { name: String, value: Option<String> },
 }
 
 impl Display for Command {
@@ -38,7 +45,66 @@ pub struct SearchParams {
     pub b_time: Option<Duration>,
     pub b_inc: Option<Duration>,
     pub move_time: Option<Duration>,
+    pub depth: Option<u8>,
+    pub nodes: Option<u64>,
+    /// The number of our moves until the next time control if there is one
+    pub moves_to_go: Option<u32>,
     pub ponder: bool,
+    /// Search until stopped, although an explicit depth, node or time limit takes precedence
+    pub infinite: bool,
+}
+
+impl SearchParams {
+    /// Whether a limit was given which does not depend on the game clock
+    pub fn has_explicit_limit(&self) -> bool {
+        self.depth.is_some() || self.nodes.is_some() || self.move_time.is_some()
+    }
+
+    /// Whether the search duration is allocated from our clock rather than given as a
+    /// fixed move time or left unbounded
+    pub fn allocates_from_clock(&self, position: &Position) -> bool {
+        let remaining = if position.active == side::W { self.w_time } else { self.b_time };
+        self.move_time.is_none()
+            && !self.infinite
+            && (remaining.is_some() || !self.has_explicit_limit())
+    }
+
+    /// How long to search the given position for. A fixed move time is used as given
+    /// less the move overhead, otherwise time is allocated from our clock.
+    pub fn search_duration(&self, position: &Position, timing: &TimeAllocator) -> Duration {
+        let is_white = position.active == side::W;
+        let remaining = if is_white { self.w_time } else { self.b_time };
+        if let Some(move_time) = self.move_time {
+            return move_time.saturating_sub(timing.latency());
+        }
+        if !self.allocates_from_clock(position) {
+            // The search is bounded by depth or nodes alone, or only ends on a stop command
+            return Duration::from_secs(ONE_YEAR_IN_SECS);
+        }
+        timing.allocate_against_opponent(
+            position,
+            remaining.unwrap_or(Duration::from_millis(5000)),
+            if is_white { self.w_inc } else { self.b_inc }.unwrap_or(Duration::ZERO),
+            self.moves_to_go,
+            if is_white { self.b_time } else { self.w_time },
+        )
+    }
+
+    /// The time left to search after a ponderhit, the clocks are those sent with the ponder
+    /// search so the opponent has since used the time we spent pondering. The search has
+    /// already been running for that long so only the rest of the allocation is topped up.
+    pub fn ponderhit_duration(
+        &self,
+        position: &Position,
+        timing: &TimeAllocator,
+        pondered: Duration,
+    ) -> Duration {
+        let mut current = self.clone();
+        let opponent_time =
+            if position.active == side::W { &mut current.b_time } else { &mut current.w_time };
+        *opponent_time = opponent_time.map(|t| t.saturating_sub(pondered));
+        current.search_duration(position, timing).saturating_sub(pondered)
+    }
 }
 
 lazy_static! {
@@ -48,14 +114,21 @@ lazy_static! {
     static ref NEW_GAME: Regex = r"^\s*ucinewgame\s*$".parse().unwrap();
     static ref STOP: Regex = r"^\s*stop\s*$".parse().unwrap();
     static ref QUIT: Regex = r"^\s*quit\s*$".parse().unwrap();
+    static ref PERFT: Regex = r"^\s*go\s+perft\s+(?<depth>\d+)\s*$".parse().unwrap();
     static ref SEARCH: Regex = r"\s*go\s+(?<params>.+)".parse().unwrap();
     static ref WTIME: Regex = r"wtime\s+(?<val>\d+)".parse().unwrap();
     static ref BTIME: Regex = r"btime\s+(?<val>\d+)".parse().unwrap();
     static ref WINC: Regex = r"winc\s+(?<val>\d+)".parse().unwrap();
     static ref BINC: Regex = r"binc\s+(?<val>\d+)".parse().unwrap();
     static ref PONDER: Regex = r"ponder".parse().unwrap();
+    static ref INFINITE: Regex = r"\binfinite\b".parse().unwrap();
     static ref PONDERHIT: Regex = r"\s*ponderhit\s*".parse().unwrap();
     static ref MOVETIME: Regex = r"movetime\s+(?<val>\d+)".parse().unwrap();
+    static ref DEPTH: Regex = r"depth\s+(?<val>\d+)".parse().unwrap();
+    static ref NODES: Regex = r"nodes\s+(?<val>\d+)".parse().unwrap();
+    static ref MOVESTOGO: Regex = r"movestogo\s+(?<val>\d+)".parse().unwrap();
+    static ref SET_OPTION: Regex =
+        r"^\s*setoption\s+name\s+(?<name>.+?)(\s+value\s+(?<value>.+?))?\s*$".parse().unwrap();
     static ref POSITION: Regex =
         r"^\s*position\s+((fen\s+(?<fen>[^m]+))|(startpos))\s*(moves\s+(?<moves>.+))?$"
             .parse()
@@ -80,6 +153,11 @@ impl FromStr for Command {
             Ok(Command::Quit)
         } else if let Some(_) = PONDERHIT.captures(s) {
             Ok(Command::PonderHit)
+        } else if let Some(caps) = SET_OPTION.captures(s) {
+            Ok(Command::SetOption {
+                name: caps["name"].to_string(),
+                value: caps.name("value").map(|m| m.as_str().to_string()),
+            })
         } else if let Some(caps) = POSITION.captures(s) {
             let mut pos = if let Some(fen) = caps.name("fen") {
                 fen.as_str().parse::<Position>()?
@@ -90,6 +168,9 @@ impl FromStr for Command {
                 pos.play(moves.as_str())?;
             }
             Ok(Command::Position(pos))
+        } else if let Some(caps) = PERFT.captures(s) {
+            let depth = &caps["depth"];
+            Ok(Command::Perft(depth.parse().map_err(|_| anyhow!("Invalid perft depth {}", depth))?))
         } else if let Some(caps) = SEARCH.captures(s) {
             let params = caps.name("params").unwrap().as_str();
             Ok(Command::Search(SearchParams {
@@ -98,7 +179,11 @@ impl FromStr for Command {
                 b_time: BTIME.captures(params).extract_duration("val"),
                 b_inc: BINC.captures(params).extract_duration("val"),
                 move_time: MOVETIME.captures(params).extract_duration("val"),
+                depth: DEPTH.captures(params).extract_number("val")?,
+                nodes: NODES.captures(params).extract_number("val")?,
+                moves_to_go: MOVESTOGO.captures(params).extract_number("val")?,
                 ponder: PONDER.captures(params).is_some(),
+                infinite: INFINITE.captures(params).is_some(),
             }))
         } else {
             Err(anyhow!("Unrecognized command"))
@@ -108,18 +193,31 @@ impl FromStr for Command {
 
 trait UciCaptures {
     fn extract_duration(&self, name: &str) -> Option<Duration>;
+    fn extract_number<N: FromStr>(&self, name: &str) -> anyhow::Result<Option<N>>;
 }
 
 impl UciCaptures for Captures<'_> {
     fn extract_duration(&self, name: &str) -> Option<Duration> {
         self.name(name).map(|m| Duration::from_millis(m.as_str().parse::<u64>().unwrap()))
     }
+
+    fn extract_number<N: FromStr>(&self, name: &str) -> anyhow::Result<Option<N>> {
+        self.name(name)
+            .map(|m| {
+                m.as_str().parse::<N>().map_err(|_| anyhow!("Invalid {} {}", name, m.as_str()))
+            })
+            .transpose()
+    }
 }
 
 impl UciCaptures for Option<Captures<'_>> {
     fn extract_duration(&self, name: &str) -> Option<Duration> {
         self.as_ref().and_then(|caps| caps.extract_duration(name))
     }
+
+    fn extract_number<N: FromStr>(&self, name: &str) -> anyhow::Result<Option<N>> {
+        self.as_ref().map_or(Ok(None), |caps| caps.extract_number(name))
+    }
 }
 
 #[cfg(test)]
@@ -190,7 +288,11 @@ mod test {
                 b_time: Some(Duration::from_millis(2212)),
                 b_inc: Some(Duration::from_millis(890)),
                 move_time: None,
+                depth: None,
+                nodes: None,
+                moves_to_go: None,
                 ponder: false,
+                infinite: false,
             }),
             " go\t btime  2212 wtime 2319 winc 32  binc 890 \t".parse().unwrap()
         );
@@ -205,7 +307,11 @@ mod test {
                 b_time: None,
                 b_inc: Some(Duration::from_millis(890)),
                 move_time: None,
+                depth: None,
+                nodes: None,
+                moves_to_go: None,
                 ponder: false,
+                infinite: false,
             }),
             " go\t wtime 2319 winc 32  binc 890 \t".parse().unwrap()
         );
@@ -220,14 +326,218 @@ mod test {
                 b_time: None,
                 b_inc: Some(Duration::from_millis(890)),
                 move_time: None,
+                depth: None,
+                nodes: None,
+                moves_to_go: None,
                 ponder: true,
+                infinite: false,
             }),
             " go\t wtime 2319 winc 32  ponder binc 890 \t".parse().unwrap()
         );
     }
 
+    #[test]
+    fn search_depth() {
+        assert_eq!(
+            Command::Search(SearchParams {
+                w_time: None,
+                w_inc: None,
+                b_time: None,
+                b_inc: None,
+                move_time: None,
+                depth: Some(8),
+                nodes: None,
+                moves_to_go: None,
+                ponder: false,
+                infinite: false,
+            }),
+            " go\t depth 8 \t".parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn search_nodes() {
+        assert_eq!(
+            Command::Search(SearchParams {
+                w_time: Some(Duration::from_millis(2319)),
+                w_inc: None,
+                b_time: Some(Duration::from_millis(2212)),
+                b_inc: None,
+                move_time: None,
+                depth: None,
+                nodes: Some(500000),
+                moves_to_go: None,
+                ponder: false,
+                infinite: false,
+            }),
+            "go wtime 2319 btime 2212 nodes  500000".parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn search_moves_to_go() {
+        assert_eq!(
+            Command::Search(SearchParams {
+                w_time: Some(Duration::from_millis(60000)),
+                w_inc: None,
+                b_time: Some(Duration::from_millis(55000)),
+                b_inc: None,
+                move_time: None,
+                depth: None,
+                nodes: None,
+                moves_to_go: Some(12),
+                ponder: false,
+                infinite: false,
+            }),
+            "go wtime 60000 btime 55000 movestogo 12".parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn search_moves_to_go_not_confused_with_move_time() {
+        let command = "go movetime 1500 movestogo 1 winc 100".parse::<Command>().unwrap();
+        if let Command::Search(params) = command {
+            assert_eq!(Some(1), params.moves_to_go);
+            assert_eq!(Some(Duration::from_millis(1500)), params.move_time);
+            assert_eq!(Some(Duration::from_millis(100)), params.w_inc);
+        } else {
+            panic!("Expected search command but was {}", command)
+        }
+    }
+
+    fn search_duration(input: &str, timing: &TimeAllocator) -> Duration {
+        match input.parse::<Command>().unwrap() {
+            Command::Search(params) => params.search_duration(&Position::default(), timing),
+            command => panic!("Expected search command but was {}", command),
+        }
+    }
+
+    #[test]
+    fn move_time_used_regardless_of_clock() {
+        let timing = TimeAllocator::with_latency(Duration::from_millis(50));
+        let expected = Duration::from_millis(2950);
+        assert_eq!(expected, search_duration("go movetime 3000", &timing));
+        assert_eq!(expected, search_duration("go wtime 100 btime 100 movetime 3000", &timing));
+        assert_eq!(
+            expected,
+            search_duration("go wtime 600000 btime 600000 winc 5000 movetime 3000", &timing)
+        );
+    }
+
+    #[test]
+    fn ponderhit_tops_up_remaining_allocation() {
+        let timing = TimeAllocator::with_latency(Duration::ZERO);
+        let ponder = |input: &str| match input.parse::<Command>().unwrap() {
+            Command::Search(params) => params,
+            command => panic!("Expected search command but was {}", command),
+        };
+        let position = Position::default();
+        let params = ponder("go ponder wtime 60000 btime 60000");
+        let full = params.search_duration(&position, &timing);
+        let pondered = full / 4;
+        // The opponent used the time we pondered for, which shrinks their clock
+        let expected = ponder(&format!("go wtime 60000 btime {}", 60000 - pondered.as_millis()))
+            .search_duration(&position, &timing)
+            - pondered;
+        assert_eq!(expected, params.ponderhit_duration(&position, &timing, pondered));
+        assert!(expected < full);
+        // Pondering past the allocation leaves nothing to top up
+        assert_eq!(Duration::ZERO, params.ponderhit_duration(&position, &timing, 2 * full));
+        let params = ponder("go ponder movetime 3000");
+        assert_eq!(
+            Duration::from_millis(2000),
+            params.ponderhit_duration(&position, &timing, Duration::from_millis(1000))
+        );
+    }
+
+    #[test]
+    fn search_depth_out_of_range() {
+        assert!("go depth 1000".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn perft() {
+        assert_eq!(Command::Perft(5), " go  perft\t5 ".parse().unwrap());
+    }
+
+    #[test]
+    fn perft_out_of_range() {
+        assert!("go perft 300".parse::<Command>().is_err());
+    }
+
+    #[test]
+    fn search_infinite() {
+        assert_eq!(
+            Command::Search(SearchParams {
+                w_time: None,
+                w_inc: None,
+                b_time: None,
+                b_inc: None,
+                move_time: None,
+                depth: None,
+                nodes: None,
+                moves_to_go: None,
+                ponder: false,
+                infinite: true,
+            }),
+            " go  infinite\t".parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn search_infinite_with_depth() {
+        let command = "go infinite depth 12".parse::<Command>().unwrap();
+        if let Command::Search(params) = command {
+            assert!(params.infinite);
+            assert_eq!(Some(12), params.depth);
+            assert!(params.has_explicit_limit());
+        } else {
+            panic!("Expected search command but was {}", command)
+        }
+    }
+
+    #[test]
+    fn only_clock_searches_allocate_from_clock() {
+        let allocates = |input: &str| match input.parse::<Command>().unwrap() {
+            Command::Search(params) => params.allocates_from_clock(&Position::default()),
+            command => panic!("Expected search command but was {}", command),
+        };
+        assert!(allocates("go wtime 60000 btime 60000"));
+        assert!(allocates("go wtime 60000 btime 60000 depth 10"));
+        assert!(!allocates("go depth 10"));
+        assert!(!allocates("go wtime 60000 btime 60000 movetime 1000"));
+        assert!(!allocates("go infinite"));
+    }
+
     #[test]
     fn ponderhit() {
         assert_eq!(Command::PonderHit, " ponderhit\t".parse().unwrap());
     }
+
+    #[test]
+    fn set_option_with_value() {
+        assert_eq!(
+            Command::SetOption { name: "UCI_Chess960".to_string(), value: Some("true".to_string()) },
+            " setoption name UCI_Chess960 value true ".parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn set_option_without_value() {
+        assert_eq!(
+            Command::SetOption { name: "Clear Hash".to_string(), value: None },
+            "setoption name Clear Hash".parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn set_option_with_spaced_name() {
+        assert_eq!(
+            Command::SetOption {
+                name: "Move Overhead".to_string(),
+                value: Some("100".to_string())
+            },
+            "setoption name Move Overhead value 100".parse().unwrap()
+        );
+    }
 }
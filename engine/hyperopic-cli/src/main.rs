@@ -2,16 +2,19 @@ mod command;
 mod latch;
 mod openings;
 
-use crate::command::{Command, SearchParams};
+use crate::command::{Command, ONE_YEAR_IN_SECS, SearchParams};
 use crate::openings::OpeningsDatabase;
 use crate::state::{IDLE, SEARCHING, STOPPING};
 use anyhow::Result;
 use anyhow::anyhow;
 use clap::Parser;
-use hyperopic::constants::side;
-use hyperopic::openings::OpeningService;
+use hyperopic::moves::Move;
+use hyperopic::node::TreeNode;
+use hyperopic::openings::{DepthUnit, OpeningService, SelectionStrategy};
 use hyperopic::position::Position;
-use hyperopic::search::end::SearchEndSignal;
+use hyperopic::search::end::{CompositeEndSignal, SearchEndSignal};
+use hyperopic::search::{ConcurrentTT, InfoCallback, SearchInfo, divide};
+use hyperopic::symmetry::check_eval_symmetry;
 use hyperopic::timing::TimeAllocator;
 use hyperopic::{ComputeMoveInput, ComputeMoveOutput, Engine, LookupMoveService};
 use latch::CountDownLatch;
@@ -21,22 +24,29 @@ use log4rs::append::console::{ConsoleAppender, Target};
 use log4rs::config::{Appender, Root};
 use log4rs::encode::pattern::PatternEncoder;
 use state::PONDERING;
-use std::cmp::max;
 use std::sync::Arc;
 use std::sync::atomic::Ordering::SeqCst;
 use std::sync::atomic::{AtomicU8, Ordering};
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, Instant, SystemTime};
 
 const DEFAULT_TABLE_SIZE: usize = 1_000_000;
-const ONE_YEAR_IN_SECS: u64 = 60 * 60 * 24 * 365;
+const MIN_HASH_MEGABYTES: usize = 1;
+const MAX_HASH_MEGABYTES: usize = 65536;
+const MAX_MOVE_OVERHEAD_MILLIS: u64 = 5000;
+const EVAL_SYMMETRY_POSITIONS: usize = 500;
+const EVAL_SYMMETRY_SEED: u64 = 92746;
 
 #[derive(Parser, Debug, Clone)]
 struct Args {
     /// Path to the openings database file to use
     #[clap(long, default_value = None)]
     openings_db: Option<String>,
+    /// Openings database is not consulted for positions deeper than this into the game
     #[clap(long, default_value = "10")]
     max_openings_depth: usize,
+    /// Unit of the max openings depth, either "plies" or "moves"
+    #[clap(long, default_value = "plies")]
+    openings_depth_unit: DepthUnit,
     /// Table row capacity for the transposition table
     #[clap(long, default_value = None)]
     table_size: Option<usize>,
@@ -44,6 +54,9 @@ struct Args {
     log_config: Option<String>,
     #[clap(long, default_value = None)]
     log_level: Option<LevelFilter>,
+    /// Check the evaluation is symmetric between the sides on random positions before starting
+    #[clap(long)]
+    check_eval_symmetry: bool,
 }
 
 fn main() -> Result<()> {
@@ -54,6 +67,10 @@ fn main() -> Result<()> {
         log4rs::init_config(create_default_logging(args.log_level.unwrap_or(LevelFilter::Info)))?;
     }
     info!("Starting hyperopic CLI");
+    if args.check_eval_symmetry {
+        check_eval_symmetry(EVAL_SYMMETRY_POSITIONS, EVAL_SYMMETRY_SEED)?;
+        info!("Evaluation symmetric on {} random positions", EVAL_SYMMETRY_POSITIONS);
+    }
     Hyperopic::new(args).run()
 }
 
@@ -81,7 +98,11 @@ struct Hyperopic {
     search_control: Option<Arc<SearchControl>>,
     state: Arc<AtomicU8>,
     position: Position,
-    ponderhit_search_duration: Option<Duration>,
+    /// The parameters, position and start time of the current ponder search
+    ponder_start: Option<(SearchParams, Position, Instant)>,
+    chess960: bool,
+    table_size: usize,
+    timing: TimeAllocator,
 }
 
 impl Hyperopic {
@@ -97,16 +118,23 @@ impl Hyperopic {
                     lookups.push(Arc::new(OpeningService {
                         fetcher: db,
                         max_depth: args.max_openings_depth,
+                        depth_unit: args.openings_depth_unit,
+                        selection: SelectionStrategy::default(),
+                        diversity: None,
                     }))
                 }
             }
         }
+        let table_size = args.table_size.unwrap_or(DEFAULT_TABLE_SIZE);
         Hyperopic {
             search_control: None,
-            engine: Engine::new(args.table_size.unwrap_or(DEFAULT_TABLE_SIZE), lookups),
+            engine: Engine::new(table_size, lookups),
             state: Arc::new(AtomicU8::new(IDLE)),
             position: Position::default(),
-            ponderhit_search_duration: None,
+            ponder_start: None,
+            chess960: false,
+            table_size,
+            timing: TimeAllocator::default(),
         }
     }
 
@@ -132,9 +160,28 @@ impl Hyperopic {
                                 Command::Uci => {
                                     println!("id name Hyperopic");
                                     println!("id author th0masb");
+                                    println!("option name UCI_Chess960 type check default false");
+                                    println!(
+                                        "option name Hash type spin default {} min {} max {}",
+                                        hash_megabytes(self.table_size),
+                                        MIN_HASH_MEGABYTES,
+                                        MAX_HASH_MEGABYTES
+                                    );
+                                    println!(
+                                        "option name Move Overhead type spin default {} min 0 max {}",
+                                        self.timing.latency().as_millis(),
+                                        MAX_MOVE_OVERHEAD_MILLIS
+                                    );
                                     println!("uciok");
                                 }
                                 Command::IsReady => println!("readyok"),
+                                Command::SetOption { name, value } => {
+                                    if curr_state == IDLE {
+                                        self.set_option(name.as_str(), value.as_deref())
+                                    } else {
+                                        error!("Cannot set option {} during a search", name)
+                                    }
+                                }
                                 Command::Debug(_) => {}
                                 Command::Quit => {
                                     match curr_state {
@@ -155,19 +202,18 @@ impl Hyperopic {
                                 Command::PonderHit => {
                                     if curr_state == PONDERING {
                                         debug!("Received ponderhit command while pondering");
-                                        let search_duration =
-                                            self.ponderhit_search_duration.unwrap();
-                                        let control = self.search_control.as_ref().unwrap().clone();
-                                        std::thread::spawn(move || {
-                                            debug!(
-                                                "PonderHit wait started for {:?}",
-                                                search_duration
-                                            );
-                                            std::thread::sleep(search_duration);
-                                            debug!("Stopping search after PonderHit");
-                                            control.stop_search.count_down()
-                                        });
-                                        self.ponderhit_search_duration = None;
+                                        let (params, position, started) =
+                                            self.ponder_start.take().unwrap();
+                                        let search_duration = params.ponderhit_duration(
+                                            &position,
+                                            &self.timing,
+                                            started.elapsed(),
+                                        );
+                                        debug!(
+                                            "Searching for {:?} after PonderHit",
+                                            search_duration
+                                        );
+                                        self.engine.ponder_hit(search_duration);
                                         self.state.store(SEARCHING, SeqCst);
                                     }
                                 }
@@ -176,16 +222,24 @@ impl Hyperopic {
                                 Command::Stop => {
                                     if curr_state == SEARCHING || curr_state == PONDERING {
                                         self.state.store(STOPPING, SeqCst);
-                                        self.ponderhit_search_duration = None;
+                                        self.ponder_start = None;
                                         if let Some(control) = self.search_control.as_ref() {
                                             debug!("Stopping search after Stop");
                                             control.stop_search.count_down();
                                         }
                                     }
                                 }
+                                Command::Perft(depth) => {
+                                    if curr_state == IDLE {
+                                        self.run_perft(depth)
+                                    } else {
+                                        error!("Cannot run perft during a search")
+                                    }
+                                }
                                 Command::Search(params) => {
                                     if curr_state == IDLE {
                                         let state_holder = self.state.clone();
+                                        let chess960 = self.chess960;
                                         state_holder.store(
                                             if params.ponder { PONDERING } else { SEARCHING },
                                             SeqCst,
@@ -200,34 +254,57 @@ impl Hyperopic {
                                             search_duration.as_millis()
                                         );
                                         if params.ponder {
-                                            self.ponderhit_search_duration = Some(search_duration);
+                                            self.ponder_start = Some((
+                                                params.clone(),
+                                                self.position.clone(),
+                                                Instant::now(),
+                                            ));
                                             search_duration = Duration::from_secs(ONE_YEAR_IN_SECS)
                                         }
+                                        let soft_deadline = (!params.ponder
+                                            && params.allocates_from_clock(&self.position))
+                                        .then(|| {
+                                            let waited =
+                                                command_received_time.elapsed().unwrap_or_default();
+                                            Instant::now()
+                                                + self
+                                                    .timing
+                                                    .soft_limit(search_duration)
+                                                    .saturating_sub(waited)
+                                        });
                                         let stop_time = command_received_time + search_duration;
                                         debug!("Stopping search at {}", format_millis(stop_time));
-                                        self.engine.compute_move_async(
-                                            ComputeMoveInput {
-                                                position: self.position.clone(),
-                                                max_depth: None,
-                                                wait_for_end: params.ponder,
-                                                search_end: GoSearchEnd {
-                                                    stop_time,
-                                                    stop_latch: next_search_control
-                                                        .stop_search
-                                                        .clone(),
-                                                },
-                                            },
-                                            move |result| {
+                                        let input = ComputeMoveInput::builder(
+                                            self.position.clone(),
+                                            CompositeEndSignal::new(vec![
+                                                Arc::new(stop_time),
+                                                next_search_control.stop_search.clone(),
+                                            ]),
+                                        )
+                                        .max_depth(params.depth)
+                                        .max_nodes(params.nodes)
+                                        .soft_deadline(soft_deadline)
+                                        .wait_for_end(
+                                            params.infinite && !params.has_explicit_limit(),
+                                        )
+                                        .on_info(InfoCallback::new(move |info| {
+                                            format_info(info, chess960)
+                                        }))
+                                        .build();
+                                        let on_complete =
+                                            move |result: Result<ComputeMoveOutput>| {
                                                 state_holder.store(IDLE, SeqCst);
                                                 next_search_control.wait_search.count_down();
                                                 match result {
-                                                    Err(e) => {
-                                                        error!("Error computing move: {}", e)
-                                                    }
-                                                    Ok(output) => format_output(output),
+                                                    Err(e) => error!("Error computing move: {}", e),
+                                                    Ok(output) => format_output(output, chess960),
                                                 }
-                                            },
-                                        );
+                                            };
+                                        if params.ponder {
+                                            self.engine.start_ponder(input, on_complete);
+                                        } else {
+                                            self.engine.compute_move_async(input, on_complete);
+                                        }
                                     }
                                 }
                             }
@@ -239,14 +316,46 @@ impl Hyperopic {
         Ok(())
     }
 
+    fn set_option(&mut self, name: &str, value: Option<&str>) {
+        match (name.to_lowercase().as_str(), value) {
+            ("uci_chess960", Some(value)) => self.chess960 = value.eq_ignore_ascii_case("true"),
+            ("hash", Some(value)) => match value.parse::<usize>() {
+                Ok(megabytes) if (MIN_HASH_MEGABYTES..=MAX_HASH_MEGABYTES).contains(&megabytes) => {
+                    let rows = ConcurrentTT::rows_for_megabytes(megabytes);
+                    if self.engine.resize_table(rows) {
+                        self.table_size = rows;
+                    } else {
+                        error!("Engine busy, could not resize table to {}MB", megabytes)
+                    }
+                }
+                _ => error!("Invalid hash size {}", value),
+            },
+            ("move overhead", Some(value)) => match value.parse::<u64>() {
+                Ok(millis) if millis <= MAX_MOVE_OVERHEAD_MILLIS => {
+                    self.timing = TimeAllocator::with_latency(Duration::from_millis(millis))
+                }
+                _ => error!("Invalid move overhead {}", value),
+            },
+            _ => error!("Unsupported option {} with value {:?}", name, value),
+        }
+    }
+
+    fn run_perft(&self, depth: u8) {
+        let mut node: TreeNode = self.position.clone().into();
+        match divide(&mut node, depth) {
+            Err(e) => error!("Error running perft: {}", e),
+            Ok(counts) => {
+                for (m, count) in counts.iter() {
+                    println!("{}: {}", m.to_uci(self.chess960), count);
+                }
+                println!();
+                println!("Nodes searched: {}", counts.iter().map(|(_, n)| n).sum::<u64>());
+            }
+        }
+    }
+
     fn compute_search_duration(&self, params: &SearchParams) -> Duration {
-        let is_white = self.position.active == side::W;
-        TimeAllocator::default().allocate(
-            self.position.history.len(),
-            if is_white { params.w_time } else { params.b_time }
-                .unwrap_or(Duration::from_millis(5000)),
-            if is_white { params.w_inc } else { params.b_inc }.unwrap_or(Duration::ZERO),
-        )
+        params.search_duration(&self.position, &self.timing)
     }
 }
 
@@ -256,49 +365,87 @@ fn format_millis(time: SystemTime) -> String {
         .unwrap_or("TIME_ERR".to_string())
 }
 
-fn format_output(output: ComputeMoveOutput) {
+/// The size of a table with the given rows as advertised in the Hash option, a table
+/// smaller than the minimum is rounded up so the default is always in range
+fn hash_megabytes(table_size: usize) -> usize {
+    ConcurrentTT::megabytes_for_rows(table_size).clamp(MIN_HASH_MEGABYTES, MAX_HASH_MEGABYTES)
+}
+
+/// Convert an eval into the centipawn score reported over uci
+fn to_centipawns(relative_eval: i32) -> i32 {
+    (relative_eval as f64 / 2.3).round() as i32
+}
+
+fn format_info(info: SearchInfo, chess960: bool) {
+    if info.stalled {
+        println!(
+            "info string depth {} visited far more nodes than predicted after {} nodes",
+            info.depth, info.nodes
+        );
+        return;
+    }
+    let score_cp = to_centipawns(info.relative_eval);
+    let search_info = format!(
+        "info depth {} time {} nodes {} nps {} hashfull {} score cp {} pv {}",
+        info.depth,
+        info.time.as_millis(),
+        info.nodes,
+        info.nps(),
+        info.hashfull,
+        score_cp,
+        info.optimal_path.iter().map(|m| m.to_uci(chess960)).collect::<Vec<_>>().join(" ")
+    );
+    debug!("{}", search_info);
+    println!("{}", search_info);
+}
+
+fn format_output(output: ComputeMoveOutput, chess960: bool) {
     if let Some(details) = output.search_details.as_ref() {
         // TODO Handle score output better
-        let score_cp = (details.relative_eval as f64 / 2.3).round() as i32;
+        let score_cp = to_centipawns(details.relative_eval);
         let search_info = format!(
-            "info depth {} time {} score cp {}",
+            "info depth {} time {} nodes {} nps {} score cp {}",
             details.depth,
             details.time.as_millis(),
+            details.nodes,
+            details.nps(),
             score_cp
         );
         debug!("{}", search_info);
         println!("{}", search_info);
     }
     debug!("Writing bestmove at {}", format_millis(SystemTime::now()));
-    let output = format!(
+    let output = format_bestmove(&output, chess960);
+    debug!("{}", output);
+    println!("{}", output);
+}
+
+/// The bestmove line for the given output, UCI writes the null move returned for a
+/// position with no legal moves as 0000
+fn format_bestmove(output: &ComputeMoveOutput, chess960: bool) -> String {
+    let best_move = match output.best_move {
+        Move::Null => "0000".to_string(),
+        ref m => m.to_uci(chess960),
+    };
+    format!(
         "bestmove {}{}",
-        output.best_move,
+        best_move,
         output
             .search_details
             .as_ref()
             .and_then(|details| details.optimal_path.get(1).cloned())
-            .map(|m| format!(" ponder {}", m))
+            .map(|m| format!(" ponder {}", m.to_uci(chess960)))
             .unwrap_or("".to_string())
-    );
-    debug!("{}", output);
-    println!("{}", output);
-}
-
-#[derive(Clone)]
-struct GoSearchEnd {
-    stop_time: SystemTime,
-    stop_latch: Arc<CountDownLatch>,
+    )
 }
 
-impl SearchEndSignal for GoSearchEnd {
+impl SearchEndSignal for CountDownLatch {
     fn should_end_now(&self) -> bool {
-        self.stop_time.should_end_now() || self.stop_latch.get_current_count(Ordering::Relaxed) == 0
+        self.get_current_count(Ordering::Relaxed) == 0
     }
 
-    fn join(&self) -> () {
-        let wait = self.stop_time.duration_since(SystemTime::now()).unwrap_or(Duration::ZERO);
-        let duration_until_stop = max(Duration::ZERO, wait);
-        self.stop_latch.register_join().recv_timeout(duration_until_stop).ok();
+    fn join(&self) {
+        self.register_join().recv().ok();
     }
 }
 
@@ -317,3 +464,38 @@ impl Default for SearchControl {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{MAX_HASH_MEGABYTES, format_bestmove, hash_megabytes, to_centipawns};
+    use hyperopic::ComputeMoveOutput;
+    use hyperopic::moves::Move;
+    use hyperopic::position::Position;
+
+    #[test]
+    fn null_best_move_written_as_zeros() {
+        let output = ComputeMoveOutput { best_move: Move::Null, search_details: None };
+        assert_eq!("bestmove 0000", format_bestmove(&output, false));
+    }
+
+    #[test]
+    fn best_move_written_in_uci() {
+        let best_move = Position::default().play("e2e4").unwrap().remove(0);
+        let output = ComputeMoveOutput { best_move, search_details: None };
+        assert_eq!("bestmove e2e4", format_bestmove(&output, false));
+    }
+
+    #[test]
+    fn eval_scaled_to_centipawns() {
+        assert_eq!(0, to_centipawns(0));
+        assert_eq!(100, to_centipawns(230));
+        assert_eq!(-10, to_centipawns(-23));
+    }
+
+    #[test]
+    fn hash_default_within_advertised_range() {
+        assert_eq!(1, hash_megabytes(0));
+        assert_eq!(1, hash_megabytes(10));
+        assert_eq!(MAX_HASH_MEGABYTES, hash_megabytes(usize::MAX));
+    }
+}
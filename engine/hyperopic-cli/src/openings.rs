@@ -1,5 +1,5 @@
 use anyhow::{Result, anyhow};
-use hyperopic::openings::{OpeningMoveFetcher, OpeningMoveRecord};
+use hyperopic::openings::{OpeningMoveFetcher, OpeningMoveRecord, book_key};
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
@@ -24,7 +24,8 @@ impl OpeningsDatabase {
                 .split(";")
                 .map(|s| OpeningMoveRecord::from_str(s))
                 .collect::<Result<Vec<_>>>()?;
-            contents.insert(key.to_string(), records);
+            // Normalised so books written with full FENs index by the same key as lookups
+            contents.insert(book_key(key), records);
         }
         Ok(OpeningsDatabase { contents })
     }
@@ -32,6 +33,6 @@ impl OpeningsDatabase {
 
 impl OpeningMoveFetcher for OpeningsDatabase {
     fn lookup(&self, position_key: &str) -> Result<Vec<OpeningMoveRecord>> {
-        Ok(self.contents.get(position_key).cloned().unwrap_or(vec![]))
+        Ok(self.contents.get(&book_key(position_key)).cloned().unwrap_or(vec![]))
     }
 }
@@ -145,7 +145,7 @@ fn benchmark() {
             print_progress(case_count, err_count, search_duration.clone());
         }
         let board_fen = test_case.eval.position().to_string();
-        let params = SearchParameters {end_signal: EmptyEndSignal, table: Arc::new(ConcurrentTT::new(table_size)), max_depth: Some(depth as u8) };
+        let params = SearchParameters {max_depth: Some(depth as u8), ..SearchParameters::new(EmptyEndSignal, Arc::new(ConcurrentTT::new(table_size))) };
         match search(test_case.eval, params) {
             Err(message) => panic!("{}", message),
             Ok(outcome) => {
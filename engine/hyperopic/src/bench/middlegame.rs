@@ -50,6 +50,24 @@ use std::time::Instant;
 /// ------------------------------------------------------------------------------------------------
 /// 19/07/23 | 4(*)(1) | 500   | 0      |  3,367             | 100,000 table entries
 /// ------------------------------------------------------------------------------------------------
+/// 16/10/26 | 5(*)(1) | 500   | 0      |  6,295             | Control run to test static futility
+///          |         |       |        |                    | pruning, 3.82M nodes over the first 150
+///          |         |       |        |                    | positions at depth 6.
+/// ------------------------------------------------------------------------------------------------
+/// 16/10/26 | 5(*)(1) | 500   | 0      |  5,376             | With futility pruning at the frontier
+///          |         |       |        |                    | and reverse futility up to depth 3,
+///          |         |       |        |                    | 2.65M nodes at depth 6 and the best
+///          |         |       |        |                    | move suite unchanged.
+/// ------------------------------------------------------------------------------------------------
+/// 17/10/26 | 5(*)(1) | 500   | 0      | 12,222             | Control run to test razoring, 2.47M
+///          |         |       |        |                    | nodes over the first 150 positions at
+///          |         |       |        |                    | depth 6.
+/// ------------------------------------------------------------------------------------------------
+/// 17/10/26 | 5(*)(1) | 500   | 0      |  9,992             | Razoring at depth 1 and 2 when no
+///          |         |       |        |                    | checks or promotions are available,
+///          |         |       |        |                    | 2.21M nodes at depth 6 and the best
+///          |         |       |        |                    | move suite unchanged.
+/// ------------------------------------------------------------------------------------------------
 #[test]
 #[ignore]
 fn benchmark() -> Result<(), Box<dyn Error>> {
@@ -80,9 +98,8 @@ fn benchmark() -> Result<(), Box<dyn Error>> {
             println!("[Position {}, Duration {}ms]", i, start.elapsed().as_millis());
         }
         best_moves.push(crate::search::search(position.into(), SearchParameters {
-            end_signal: EmptyEndSignal,
-            table: Arc::new(ConcurrentTT::new(table_size)),
             max_depth: Some(depth as u8),
+            ..SearchParameters::new(EmptyEndSignal, Arc::new(ConcurrentTT::new(table_size)))
         })?)
     }
     println!("Successfully computed {} moves at depth {} in {}ms", best_moves.len(), depth, start.elapsed().as_millis());
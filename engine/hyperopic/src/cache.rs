@@ -0,0 +1,85 @@
+use crate::search::SearchOutcome;
+use std::collections::VecDeque;
+
+/// Small least recently used cache of whole search outcomes keyed by the position
+/// key and the depth searched to. Unlike the transposition table, which stores the
+/// evaluation of sub-trees, a hit here skips the search entirely.
+#[derive(Debug, Clone)]
+pub struct OutcomeCache {
+    capacity: usize,
+    /// Most recently used at the front
+    entries: VecDeque<(u64, u8, SearchOutcome)>,
+}
+
+impl OutcomeCache {
+    pub fn new(capacity: usize) -> OutcomeCache {
+        OutcomeCache { capacity, entries: VecDeque::with_capacity(capacity) }
+    }
+
+    pub fn get(&mut self, key: u64, depth: u8) -> Option<SearchOutcome> {
+        let index = self.entries.iter().position(|(k, d, _)| *k == key && *d == depth)?;
+        let entry = self.entries.remove(index)?;
+        let outcome = entry.2.clone();
+        self.entries.push_front(entry);
+        Some(outcome)
+    }
+
+    pub fn insert(&mut self, key: u64, depth: u8, outcome: SearchOutcome) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.entries.retain(|(k, d, _)| *k != key || *d != depth);
+        self.entries.truncate(self.capacity - 1);
+        self.entries.push_front((key, depth, outcome));
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::OutcomeCache;
+    use crate::position::Position;
+    use crate::search::SearchOutcome;
+    use std::time::Duration;
+
+    fn outcome(depth: u8) -> SearchOutcome {
+        let best_move = Position::default().play("e2e4").unwrap().remove(0);
+        SearchOutcome {
+            best_move: best_move.clone(),
+            relative_eval: 0,
+            depth,
+            time: Duration::from_millis(1),
+            nodes: 1,
+            optimal_path: vec![best_move],
+            alternative: None,
+            stats: None,
+        }
+    }
+
+    #[test]
+    fn keyed_on_depth() {
+        let mut cache = OutcomeCache::new(2);
+        cache.insert(1, 3, outcome(3));
+        assert_eq!(Some(outcome(3)), cache.get(1, 3));
+        assert_eq!(None, cache.get(1, 4));
+        assert_eq!(None, cache.get(2, 3));
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut cache = OutcomeCache::new(2);
+        cache.insert(1, 3, outcome(3));
+        cache.insert(2, 3, outcome(3));
+        // Touching the first entry means the second is evicted next
+        assert!(cache.get(1, 3).is_some());
+        cache.insert(3, 3, outcome(3));
+        assert!(cache.get(1, 3).is_some());
+        assert!(cache.get(2, 3).is_none());
+        assert!(cache.get(3, 3).is_some());
+        cache.clear();
+        assert!(cache.get(1, 3).is_none());
+    }
+}
@@ -47,6 +47,7 @@ pub mod boards {
     pub const EMPTY: Board = 0u64;
     pub const ALL: Board = !0u64;
     pub const RIM: Board = board!(A1 => A8, H1; H8 => A8, H1);
+    pub const LIGHT_SQUARES: Board = 0xAA55AA55AA55AA55u64;
 
     pub const RANKS: [Board; 8] = [
         board!(A1 => H1),
@@ -186,3 +187,21 @@ pub const fn intersects(left: Board, right: Board) -> bool {
 pub const fn first_square(board: Board) -> Square {
     board.trailing_zeros() as Square
 }
+
+#[cfg(test)]
+mod test {
+    use super::boards::LIGHT_SQUARES;
+    use super::square::*;
+    use super::in_board;
+
+    #[test]
+    fn light_squares() {
+        for light in [H1, A8, B1, A2, D1, E8] {
+            assert!(in_board(LIGHT_SQUARES, light));
+        }
+        for dark in [A1, H8, G1, E1, D8] {
+            assert!(!in_board(LIGHT_SQUARES, dark));
+        }
+        assert_eq!(32, LIGHT_SQUARES.count_ones());
+    }
+}
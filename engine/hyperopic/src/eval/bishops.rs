@@ -0,0 +1,154 @@
+use crate::constants::{class, create_piece, piece_class, piece_side, side};
+use crate::moves::Move;
+use crate::node::{EvalFacet, Evaluation};
+use crate::position::Position;
+use crate::{Piece, SideMap};
+
+const DEFAULT_BISHOP_PAIR_VALUE: i32 = 70;
+
+/// Awards a bonus to each side which holds at least two bishops, the bishop counts
+/// are tracked incrementally as moves are made and unmade.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct BishopPairFacet {
+    bishop_pair_value: i32,
+    bishop_counts: SideMap<i32>,
+}
+
+impl Default for BishopPairFacet {
+    fn default() -> Self {
+        BishopPairFacet { bishop_pair_value: DEFAULT_BISHOP_PAIR_VALUE, bishop_counts: [2, 2] }
+    }
+}
+
+impl From<&Position> for BishopPairFacet {
+    fn from(value: &Position) -> Self {
+        let count = |side| value.piece_boards[create_piece(side, class::B)].count_ones() as i32;
+        BishopPairFacet { bishop_counts: [count(side::W), count(side::B)], ..Default::default() }
+    }
+}
+
+type UpdateFn = fn(&mut BishopPairFacet, Piece) -> ();
+
+impl BishopPairFacet {
+    fn bonus(&self, side: usize) -> i32 {
+        if self.bishop_counts[side] >= 2 { self.bishop_pair_value } else { 0 }
+    }
+
+    fn add(&mut self, piece: Piece) {
+        if piece_class(piece) == class::B {
+            self.bishop_counts[piece_side(piece)] += 1;
+        }
+    }
+
+    fn remove(&mut self, piece: Piece) {
+        if piece_class(piece) == class::B {
+            self.bishop_counts[piece_side(piece)] -= 1;
+        }
+    }
+
+    fn make_impl(&mut self, mv: &Move, add: UpdateFn, remove: UpdateFn) {
+        match mv {
+            Move::Castle { .. } | Move::Null | Move::Enpassant { .. } => {}
+            Move::Normal { capture, .. } => {
+                if let Some(piece) = capture {
+                    remove(self, *piece);
+                }
+            }
+            Move::Promote { promoted, capture, .. } => {
+                add(self, *promoted);
+                if let Some(piece) = capture {
+                    remove(self, *piece)
+                }
+            }
+        }
+    }
+}
+
+impl EvalFacet for BishopPairFacet {
+    fn static_eval(&self, _: &Position) -> Evaluation {
+        Evaluation::Single(self.bonus(side::W) - self.bonus(side::B))
+    }
+
+    fn make(&mut self, mv: &Move, _: &Position) {
+        self.make_impl(mv, BishopPairFacet::add, BishopPairFacet::remove)
+    }
+
+    fn unmake(&mut self, mv: &Move) {
+        self.make_impl(mv, BishopPairFacet::remove, BishopPairFacet::add)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::eval::BishopPairFacet;
+    use crate::node::{EvalFacet, Evaluation};
+    use crate::parse::parse_uci_move;
+    use crate::position::Position;
+    use crate::test::facets::test_facet_evolution;
+
+    const PAIR_VALUE: i32 = 50;
+
+    fn facet(counts: [i32; 2]) -> BishopPairFacet {
+        BishopPairFacet { bishop_pair_value: PAIR_VALUE, bishop_counts: counts }
+    }
+
+    fn from_position(position: &Position) -> BishopPairFacet {
+        BishopPairFacet { bishop_pair_value: PAIR_VALUE, ..BishopPairFacet::from(position) }
+    }
+
+    #[test]
+    fn pair_present() {
+        let position: Position = "4k3/3b4/8/8/8/8/3BB3/4K3 w - - 0 1".parse().unwrap();
+        let under_test = from_position(&position);
+        assert_eq!(facet([2, 1]), under_test);
+        assert_eq!(Evaluation::Single(PAIR_VALUE), under_test.static_eval(&position));
+    }
+
+    #[test]
+    fn pair_broken_by_capture_and_restored_by_unmake() {
+        let position: Position = "4k3/3b4/8/8/8/5n2/3BB3/4K3 b - - 0 1".parse().unwrap();
+        let mut under_test = from_position(&position);
+        let capture = parse_uci_move(&position, "f3d2").unwrap();
+        under_test.make(&capture, &position);
+        assert_eq!(facet([1, 1]), under_test);
+        assert_eq!(Evaluation::Single(0), under_test.static_eval(&position));
+        under_test.unmake(&capture);
+        assert_eq!(facet([2, 1]), under_test);
+        assert_eq!(Evaluation::Single(PAIR_VALUE), under_test.static_eval(&position));
+    }
+
+    #[test]
+    fn pair_gained_by_promotion() {
+        let position: Position = "4k3/1P6/8/8/8/8/4B3/4K3 w - - 0 1".parse().unwrap();
+        let mut under_test = from_position(&position);
+        let promotion = parse_uci_move(&position, "b7b8b").unwrap();
+        under_test.make(&promotion, &position);
+        assert_eq!(facet([2, 0]), under_test);
+        under_test.unmake(&promotion);
+        assert_eq!(facet([1, 0]), under_test);
+    }
+
+    #[test]
+    fn evolution() {
+        test_facet_evolution(
+            "1. e4 e5 2. Bc4 Nf6 3. Bxf7+ Kxf7 4. d4 Bb4+ 5. c3 Bxc3+ 6. Nxc3 d6",
+            vec![
+                [2, 2],
+                [2, 2],
+                [2, 2],
+                [2, 2],
+                [2, 2],
+                [1, 2],
+                [1, 2],
+                [1, 2],
+                [1, 2],
+                [1, 2],
+                [1, 1],
+                [1, 1],
+            ]
+            .into_iter()
+            .map(|counts| BishopPairFacet { bishop_counts: counts, ..Default::default() })
+            .collect(),
+        )
+    }
+}
@@ -0,0 +1,108 @@
+use crate::constants::boards::FILES;
+use crate::constants::{class, create_piece, piece, side, square_file};
+use crate::moves::Move;
+use crate::node::{EvalFacet, Evaluation};
+use crate::position::Position;
+use crate::{Side, board};
+
+const DEFAULT_OPEN_FILE_VALUE: i32 = 25;
+const DEFAULT_HALF_OPEN_FILE_VALUE: i32 = 12;
+// Queens are less dependent on open files than rooks so receive this fraction
+// of the rook bonus
+const QUEEN_DIVISOR: i32 = 2;
+
+/// Rewards rooks, and to a lesser extent queens, placed on files with no pawns
+/// at all (open) or with only enemy pawns (half-open). This facet is not part of
+/// the default evaluation and must be added to a node with
+/// [crate::node::TreeNode::push_facet].
+#[derive(Debug, Clone)]
+pub struct RookFileFacet {
+    open_file_value: i32,
+    half_open_file_value: i32,
+}
+
+impl Default for RookFileFacet {
+    fn default() -> Self {
+        Self {
+            open_file_value: DEFAULT_OPEN_FILE_VALUE,
+            half_open_file_value: DEFAULT_HALF_OPEN_FILE_VALUE,
+        }
+    }
+}
+
+impl RookFileFacet {
+    fn file_value(&self, position: &Position, side: Side, class: usize) -> i32 {
+        let friendly_pawns = position.piece_boards[create_piece(side, class::P)];
+        let all_pawns = position.piece_boards[piece::WP] | position.piece_boards[piece::BP];
+        board::iter(position.piece_boards[create_piece(side, class)])
+            .map(|loc| FILES[square_file(loc)])
+            .map(|file| {
+                if file & all_pawns == 0 {
+                    self.open_file_value
+                } else if file & friendly_pawns == 0 {
+                    self.half_open_file_value
+                } else {
+                    0
+                }
+            })
+            .sum()
+    }
+
+    fn side_value(&self, position: &Position, side: Side) -> i32 {
+        self.file_value(position, side, class::R)
+            + self.file_value(position, side, class::Q) / QUEEN_DIVISOR
+    }
+}
+
+impl EvalFacet for RookFileFacet {
+    fn static_eval(&self, board: &Position) -> Evaluation {
+        Evaluation::Single(self.side_value(board, side::W) - self.side_value(board, side::B))
+    }
+
+    fn make(&mut self, _: &Move, _: &Position) {}
+
+    fn unmake(&mut self, _: &Move) {}
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Symmetric;
+    use crate::eval::RookFileFacet;
+    use crate::node::{EvalFacet, Evaluation};
+    use crate::position::Position;
+
+    const OPEN: i32 = 20;
+    const HALF_OPEN: i32 = 10;
+
+    fn execute_test(fen: &str, expected: i32) {
+        let facet = RookFileFacet { open_file_value: OPEN, half_open_file_value: HALF_OPEN };
+        let position: Position = fen.parse().unwrap();
+        assert_eq!(Evaluation::Single(expected), facet.static_eval(&position));
+        assert_eq!(Evaluation::Single(-expected), facet.static_eval(&position.reflect()));
+    }
+
+    #[test]
+    fn rook_on_open_file() {
+        execute_test("4k3/pp3ppp/8/8/8/8/PP3PPP/3RK3 w - - 0 1", OPEN);
+    }
+
+    #[test]
+    fn rook_on_blocked_file() {
+        execute_test("4k3/pp1p1ppp/8/8/8/8/PP1P1PPP/3RK3 w - - 0 1", 0);
+    }
+
+    #[test]
+    fn rook_on_half_open_file() {
+        execute_test("4k3/pp1p1ppp/8/8/8/8/PP3PPP/3RK3 w - - 0 1", HALF_OPEN);
+    }
+
+    #[test]
+    fn queen_scored_at_reduced_weight() {
+        execute_test("4k3/pp3ppp/8/8/8/8/PP3PPP/3QK3 w - - 0 1", OPEN / 2);
+    }
+
+    #[test]
+    fn sides_cancel() {
+        execute_test("3rk3/pp3ppp/8/8/8/8/PP3PPP/3RK3 w - - 0 1", 0);
+    }
+}
@@ -0,0 +1,99 @@
+use crate::constants::{piece_class, side_parity};
+use crate::eval::material::{MaterialFacet, PieceValues};
+use crate::moves::Move;
+use crate::node::{EvalFacet, Evaluation};
+use crate::position::Position;
+
+const DEFAULT_HANGING_PERCENT: i32 = 10;
+
+/// Penalises the side to move for each of its pieces which it could lose to an enemy
+/// capture, see [Position::hanging_pieces], by a percentage of the value of the piece.
+/// Only the side to move is penalised as the hanging pieces of the other side can be
+/// taken straight away, which the search already accounts for. This facet is not part
+/// of the default evaluation and must be added to a node with
+/// [crate::node::TreeNode::push_facet].
+#[derive(Debug, Clone)]
+pub struct HangingPieceFacet {
+    hanging_percent: i32,
+    values: PieceValues,
+}
+
+impl Default for HangingPieceFacet {
+    fn default() -> Self {
+        Self {
+            hanging_percent: DEFAULT_HANGING_PERCENT,
+            values: *MaterialFacet::default().mid_values(),
+        }
+    }
+}
+
+impl EvalFacet for HangingPieceFacet {
+    fn static_eval(&self, board: &Position) -> Evaluation {
+        let hanging_value = board
+            .hanging_pieces(board.active)
+            .into_iter()
+            .map(|(piece, _)| self.values[piece_class(piece)])
+            .sum::<i32>();
+        Evaluation::Single(-side_parity(board.active) * self.hanging_percent * hanging_value / 100)
+    }
+
+    fn make(&mut self, _: &Move, _: &Position) {}
+
+    fn unmake(&mut self, _: &Move) {}
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Symmetric;
+    use crate::board;
+    use crate::constants::piece::*;
+    use crate::constants::side;
+    use crate::constants::square::*;
+    use crate::eval::HangingPieceFacet;
+    use crate::node::{EvalFacet, Evaluation};
+    use crate::position::Position;
+
+    const HANGING_ROOK: &str = "4k3/8/2n5/3r4/4P3/2N5/5B2/4K3 b - - 0 1";
+
+    fn execute_test(fen: &str, expected: i32) {
+        let facet = HangingPieceFacet::default();
+        let position: Position = fen.parse().unwrap();
+        assert_eq!(Evaluation::Single(expected), facet.static_eval(&position));
+        assert_eq!(Evaluation::Single(-expected), facet.static_eval(&position.reflect()));
+    }
+
+    #[test]
+    fn hanging_rook_penalised() {
+        // The black rook on d5 is attacked by a pawn and a knight with no defender
+        execute_test(HANGING_ROOK, 128);
+    }
+
+    #[test]
+    fn hanging_pieces_of_side_not_to_move_ignored() {
+        execute_test("4k3/8/2n5/3r4/4P3/2N5/5B2/4K3 w - - 0 1", 0);
+    }
+
+    #[test]
+    fn defended_piece_attacked_by_cheaper_piece_penalised() {
+        execute_test("4k3/8/2n1p3/3r4/4P3/2N5/5B2/4K3 b - - 0 1", 128);
+    }
+
+    #[test]
+    fn penalty_scales_with_piece_value() {
+        execute_test("4k3/8/8/3q4/4P3/8/8/4K3 b - - 0 1", 252);
+        execute_test("4k3/8/8/3p4/8/4N3/8/4K3 b - - 0 1", 23);
+    }
+
+    #[test]
+    fn adequately_defended_piece_not_penalised() {
+        execute_test("4k3/8/4p3/3p4/8/4N3/8/4K3 b - - 0 1", 0);
+    }
+
+    #[test]
+    fn least_valuable_attacker() {
+        let position: Position = HANGING_ROOK.parse().unwrap();
+        assert_eq!(board!(E4, C3), position.attackers_to(D5, side::W));
+        assert_eq!(Some((WP, E4)), position.least_valuable_attacker(D5, side::W));
+        assert_eq!(None, position.least_valuable_attacker(D5, side::B));
+    }
+}
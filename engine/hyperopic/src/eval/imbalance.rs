@@ -0,0 +1,90 @@
+use crate::Board;
+use crate::constants::{class, create_piece, side};
+use crate::moves::Move;
+use crate::node::{EvalFacet, Evaluation};
+use crate::position::Position;
+
+/// Number of locked pawn pairs at which knights and bishops are considered equal
+const DEFAULT_NEUTRAL_LOCKED_COUNT: i32 = 3;
+const DEFAULT_LOCKED_PAWN_VALUE: i32 = 4;
+
+/// Knights are stronger in closed positions and bishops in open ones. This facet
+/// shifts the relative value of the minor pieces according to how many pawns are
+/// locked head to head with an enemy pawn. This facet is not part of the default
+/// evaluation and must be added to a node with [crate::node::TreeNode::push_facet].
+#[derive(Debug, Clone)]
+pub struct KnightBishopImbalanceFacet {
+    neutral_locked_count: i32,
+    locked_pawn_value: i32,
+}
+
+impl Default for KnightBishopImbalanceFacet {
+    fn default() -> Self {
+        Self {
+            neutral_locked_count: DEFAULT_NEUTRAL_LOCKED_COUNT,
+            locked_pawn_value: DEFAULT_LOCKED_PAWN_VALUE,
+        }
+    }
+}
+
+/// Count the white pawns which are directly blocked by a black pawn
+fn count_locked_pawns(whites: Board, blacks: Board) -> i32 {
+    ((whites << 8) & blacks).count_ones() as i32
+}
+
+impl EvalFacet for KnightBishopImbalanceFacet {
+    fn static_eval(&self, board: &Position) -> Evaluation {
+        let count = |side, class| board.piece_boards[create_piece(side, class)].count_ones() as i32;
+        let knight_diff = count(side::W, class::N) - count(side::B, class::N);
+        let bishop_diff = count(side::W, class::B) - count(side::B, class::B);
+        let locked = count_locked_pawns(
+            board.piece_boards[create_piece(side::W, class::P)],
+            board.piece_boards[create_piece(side::B, class::P)],
+        );
+        let knight_bonus = self.locked_pawn_value * (locked - self.neutral_locked_count);
+        Evaluation::Single((knight_diff - bishop_diff) * knight_bonus)
+    }
+
+    fn make(&mut self, _: &Move, _: &Position) {}
+
+    fn unmake(&mut self, _: &Move) {}
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Symmetric;
+    use crate::board;
+    use crate::constants::square::*;
+    use crate::eval::KnightBishopImbalanceFacet;
+    use crate::node::{EvalFacet, Evaluation};
+    use crate::position::Position;
+
+    fn execute_test(fen: &str, expected: i32) {
+        let facet = KnightBishopImbalanceFacet::default();
+        let position: Position = fen.parse().unwrap();
+        assert_eq!(Evaluation::Single(expected), facet.static_eval(&position));
+        assert_eq!(Evaluation::Single(-expected), facet.static_eval(&position.reflect()));
+    }
+
+    #[test]
+    fn locked_pawn_count() {
+        assert_eq!(2, super::count_locked_pawns(board!(C4, D5, H2), board!(C5, D6, H4)));
+    }
+
+    #[test]
+    fn closed_position_favours_knight() {
+        // Seven locked pawn pairs, white has the knight against the bishop
+        execute_test("4k3/8/1p1b4/pPp1p1p1/P1PpPpP1/3P1P2/4N3/4K3 w - - 0 1", 32);
+    }
+
+    #[test]
+    fn open_position_favours_bishop() {
+        // No locked pawns, white has the knight against the bishop
+        execute_test("4k3/pp3ppp/3b4/8/8/4N3/PP3PPP/4K3 w - - 0 1", -24);
+    }
+
+    #[test]
+    fn balanced_minor_pieces() {
+        execute_test("4k3/1p1bn3/8/pPp1p1p1/P1PpPpP1/3P1P2/3BN3/4K3 w - - 0 1", 0);
+    }
+}
@@ -0,0 +1,98 @@
+use crate::constants::{class, create_piece, reflect_side, side};
+use crate::moves::Move;
+use crate::node::{EvalFacet, Evaluation};
+use crate::position::Position;
+use crate::{ClassMap, Side, board};
+
+const DEFAULT_MOBILITY_WEIGHTS: ClassMap<i32> = [0, 4, 3, 2, 1, 0];
+const DEFAULT_TRAPPED_PENALTY: i32 = 40;
+/// Pieces with at most this many moves are considered trapped
+const TRAPPED_MOBILITY: u32 = 1;
+
+/// Rewards each minor and major piece for the number of pseudo-legal moves it has,
+/// weighted by the class of the piece, and penalises pieces which barely move at all.
+/// Unlike [crate::eval::SpaceFacet] squares controlled by the enemy are not excluded.
+/// The two overlap heavily so this facet is not part of the default evaluation and
+/// must be added to a node with [crate::node::TreeNode::push_facet].
+#[derive(Debug, Clone)]
+pub struct MobilityFacet {
+    mobility_weights: ClassMap<i32>,
+    trapped_penalty: i32,
+}
+
+impl Default for MobilityFacet {
+    fn default() -> Self {
+        Self {
+            mobility_weights: DEFAULT_MOBILITY_WEIGHTS,
+            trapped_penalty: DEFAULT_TRAPPED_PENALTY,
+        }
+    }
+}
+
+impl MobilityFacet {
+    fn compute_mobility(&self, position: &Position, side: Side) -> i32 {
+        let friendly = position.side_boards[side];
+        let enemies = position.side_boards[reflect_side(side)];
+        [class::N, class::B, class::R, class::Q]
+            .iter()
+            .flat_map(|&class| {
+                let piece = create_piece(side, class);
+                board::iter(position.piece_boards[piece]).map(move |loc| {
+                    let count = board::board_moves(piece, loc, friendly, enemies).count_ones();
+                    let penalty = if count <= TRAPPED_MOBILITY { self.trapped_penalty } else { 0 };
+                    self.mobility_weights[class] * count as i32 - penalty
+                })
+            })
+            .sum()
+    }
+}
+
+impl EvalFacet for MobilityFacet {
+    fn static_eval(&self, board: &Position) -> Evaluation {
+        Evaluation::Single(
+            self.compute_mobility(board, side::W) - self.compute_mobility(board, side::B),
+        )
+    }
+
+    fn make(&mut self, _: &Move, _: &Position) {}
+
+    fn unmake(&mut self, _: &Move) {}
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Symmetric;
+    use crate::eval::MobilityFacet;
+    use crate::node::{EvalFacet, Evaluation};
+    use crate::position::Position;
+
+    const TRAPPED_BISHOP: &str = "4k3/8/8/8/8/8/1P1P4/2B1K3 w - - 0 1";
+    const ACTIVE_BISHOP: &str = "4k3/8/8/8/5B2/8/1P1P4/4K3 w - - 0 1";
+
+    fn facet() -> MobilityFacet {
+        MobilityFacet { mobility_weights: [0, 4, 3, 2, 1, 0], trapped_penalty: 40 }
+    }
+
+    fn execute_test(fen: &str, expected: i32) {
+        let position: Position = fen.parse().unwrap();
+        assert_eq!(Evaluation::Single(expected), facet().static_eval(&position));
+        assert_eq!(Evaluation::Single(-expected), facet().static_eval(&position.reflect()));
+    }
+
+    #[test]
+    fn trapped_bishop_penalised() {
+        execute_test(TRAPPED_BISHOP, -40);
+    }
+
+    #[test]
+    fn active_bishop_rewarded() {
+        // The bishop on f4 reaches 9 squares
+        execute_test(ACTIVE_BISHOP, 27);
+    }
+
+    #[test]
+    fn weighted_by_class() {
+        // The knight on d4 reaches 8 squares, the rook on a1 reaches 10
+        execute_test("4k3/8/8/8/3N4/8/8/R3K3 w - - 0 1", 8 * 4 + 10 * 2);
+    }
+}
@@ -1,16 +1,30 @@
+mod bishops;
 mod castling;
+mod files;
+mod hanging;
+mod imbalance;
 pub mod material;
+mod mobility;
+mod outposts;
 mod pawns;
+mod pins;
 mod safety;
+mod scaling;
 mod space;
 pub mod tables;
+mod tempo;
 
+pub use bishops::BishopPairFacet;
 pub use castling::CastlingFacet;
+pub use files::RookFileFacet;
+pub use hanging::HangingPieceFacet;
+pub use imbalance::KnightBishopImbalanceFacet;
+pub use mobility::MobilityFacet;
+pub use outposts::KnightOutpostFacet;
 pub use pawns::PawnStructureFacet;
+pub use pins::PinFacet;
 pub use safety::SafetyFacet;
+pub use scaling::{SCALE_NORMAL, draw_scale};
 pub use space::SpaceFacet;
 pub use tables::PieceSquareTablesFacet;
-
-// Add facets for:
-// - Pins/xrays
-// - Knight outposts
+pub use tempo::TempoFacet;
@@ -0,0 +1,117 @@
+use crate::board::{pawn_control, union_boards};
+use crate::constants::boards::{ADJACENT_FILES, RANKS};
+use crate::constants::{
+    class, create_piece, in_board, reflect_side, side, square_file, square_rank,
+};
+use crate::moves::Move;
+use crate::node::{EvalFacet, Evaluation};
+use crate::position::Position;
+use crate::{Board, Side, Square, board};
+
+const DEFAULT_OUTPOST_VALUE: i32 = 15;
+/// Extra bonus indexed by the rank of the outpost relative to the owning side
+const RANK_BONUS: [i32; 8] = [0, 0, 0, 0, 5, 15, 5, 0];
+/// Extra bonus indexed by file for outposts closer to the centre
+const FILE_BONUS: [i32; 8] = [0, 0, 5, 10, 10, 5, 0, 0];
+
+/// Rewards knights standing in the enemy half on a square which is defended by a
+/// friendly pawn and which can never be attacked by an enemy pawn. This facet is not
+/// part of the default evaluation and must be added to a node with
+/// [crate::node::TreeNode::push_facet].
+#[derive(Debug, Clone)]
+pub struct KnightOutpostFacet {
+    outpost_value: i32,
+}
+
+impl Default for KnightOutpostFacet {
+    fn default() -> Self {
+        Self { outpost_value: DEFAULT_OUTPOST_VALUE }
+    }
+}
+
+fn relative_rank(side: Side, square: Square) -> usize {
+    if side == side::W { square_rank(square) } else { 7 - square_rank(square) }
+}
+
+/// The squares strictly in front of the given square from the perspective of the given side
+fn squares_ahead(side: Side, square: Square) -> Board {
+    let rank = square_rank(square);
+    let ranks = if side == side::W { &RANKS[rank + 1..] } else { &RANKS[..rank] };
+    union_boards(ranks)
+}
+
+impl KnightOutpostFacet {
+    fn outpost_score(&self, position: &Position, side: Side) -> i32 {
+        let friendly_pawns = position.piece_boards[create_piece(side, class::P)];
+        let enemy_pawns = position.piece_boards[create_piece(reflect_side(side), class::P)];
+        let defended = pawn_control(side, friendly_pawns);
+        board::iter(position.piece_boards[create_piece(side, class::N)])
+            .filter(|&sq| relative_rank(side, sq) >= 4 && in_board(defended, sq))
+            .filter(|&sq| {
+                ADJACENT_FILES[square_file(sq)] & squares_ahead(side, sq) & enemy_pawns == 0
+            })
+            .map(|sq| {
+                self.outpost_value
+                    + RANK_BONUS[relative_rank(side, sq)]
+                    + FILE_BONUS[square_file(sq)]
+            })
+            .sum()
+    }
+}
+
+impl EvalFacet for KnightOutpostFacet {
+    fn static_eval(&self, board: &Position) -> Evaluation {
+        Evaluation::Single(self.outpost_score(board, side::W) - self.outpost_score(board, side::B))
+    }
+
+    fn make(&mut self, _: &Move, _: &Position) {}
+
+    fn unmake(&mut self, _: &Move) {}
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Symmetric;
+    use crate::eval::KnightOutpostFacet;
+    use crate::node::{EvalFacet, Evaluation};
+    use crate::position::Position;
+
+    const OUTPOST: i32 = 10;
+
+    fn execute_test(fen: &str, expected: i32) {
+        let facet = KnightOutpostFacet { outpost_value: OUTPOST };
+        let position: Position = fen.parse().unwrap();
+        assert_eq!(Evaluation::Single(expected), facet.static_eval(&position));
+        assert_eq!(Evaluation::Single(-expected), facet.static_eval(&position.reflect()));
+    }
+
+    #[test]
+    fn d5_outpost() {
+        execute_test("4k3/pp3ppp/3p4/3N4/2P1P3/8/PP3PPP/4K3 w - - 0 1", OUTPOST + 5 + 10);
+    }
+
+    #[test]
+    fn e5_outpost() {
+        execute_test("4k3/ppp3pp/4p3/4N3/3P4/8/PPP2PPP/4K3 w - - 0 1", OUTPOST + 5 + 10);
+    }
+
+    #[test]
+    fn advanced_outpost() {
+        execute_test("4k3/pp3ppp/3N4/2P5/8/8/PP3PPP/4K3 w - - 0 1", OUTPOST + 15 + 10);
+    }
+
+    #[test]
+    fn attackable_by_enemy_pawn() {
+        execute_test("4k3/ppp2ppp/3p4/3N4/2P1P3/8/PP3PPP/4K3 w - - 0 1", 0);
+    }
+
+    #[test]
+    fn undefended_knight() {
+        execute_test("4k3/pp3ppp/3p4/3N4/8/8/PP3PPP/4K3 w - - 0 1", 0);
+    }
+
+    #[test]
+    fn own_half() {
+        execute_test("4k3/pp3ppp/3p4/8/3N4/2P5/PP3PPP/4K3 w - - 0 1", 0);
+    }
+}
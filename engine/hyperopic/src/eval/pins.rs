@@ -0,0 +1,103 @@
+use crate::constants::{class, create_piece, first_square, lift, piece_class, side};
+use crate::moves::Move;
+use crate::node::{EvalFacet, Evaluation};
+use crate::position::Position;
+use crate::{Board, ClassMap, Side, Square, board};
+
+const DEFAULT_ABSOLUTE_PIN_PERCENT: i32 = 10;
+const DEFAULT_RELATIVE_PIN_PERCENT: i32 = 5;
+/// Approximate piece values used to weight pins, a king can never be pinned
+const PIN_VALUES: ClassMap<i32> = [100, 300, 300, 500, 900, 0];
+
+/// Penalises pieces which are pinned by an enemy slider, either against their own
+/// king (absolute) or against a more valuable piece (relative). The penalty is a
+/// percentage of the value of the pinned piece. This facet is not part of the default
+/// evaluation and must be added to a node with [crate::node::TreeNode::push_facet].
+#[derive(Debug, Clone)]
+pub struct PinFacet {
+    absolute_pin_percent: i32,
+    relative_pin_percent: i32,
+}
+
+impl Default for PinFacet {
+    fn default() -> Self {
+        Self {
+            absolute_pin_percent: DEFAULT_ABSOLUTE_PIN_PERCENT,
+            relative_pin_percent: DEFAULT_RELATIVE_PIN_PERCENT,
+        }
+    }
+}
+
+fn value_at(position: &Position, square: Square) -> i32 {
+    position.piece_locs[square].map(|p| PIN_VALUES[piece_class(p)]).unwrap_or(0)
+}
+
+fn pinned_on(position: &Position, target: Square) -> Board {
+    position.compute_pinned_on(target).map(|pinned| pinned.0).unwrap_or(0)
+}
+
+impl PinFacet {
+    fn pin_penalty(&self, position: &Position, side: Side) -> i32 {
+        let king = position.piece_boards[create_piece(side, class::K)];
+        let absolute = if king == 0 { 0 } else { pinned_on(position, first_square(king)) };
+        let relative = [class::R, class::Q]
+            .iter()
+            .flat_map(|&class| board::iter(position.piece_boards[create_piece(side, class)]))
+            .flat_map(|target| {
+                let target_value = value_at(position, target);
+                board::iter(pinned_on(position, target))
+                    .filter(move |&sq| value_at(position, sq) < target_value)
+            })
+            .fold(0u64, |a, sq| a | lift(sq))
+            & !absolute;
+        let total =
+            |pinned: Board| board::iter(pinned).map(|sq| value_at(position, sq)).sum::<i32>();
+        (self.absolute_pin_percent * total(absolute) + self.relative_pin_percent * total(relative))
+            / 100
+    }
+}
+
+impl EvalFacet for PinFacet {
+    fn static_eval(&self, board: &Position) -> Evaluation {
+        Evaluation::Single(self.pin_penalty(board, side::B) - self.pin_penalty(board, side::W))
+    }
+
+    fn make(&mut self, _: &Move, _: &Position) {}
+
+    fn unmake(&mut self, _: &Move) {}
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Symmetric;
+    use crate::eval::PinFacet;
+    use crate::node::{EvalFacet, Evaluation};
+    use crate::position::Position;
+
+    fn execute_test(fen: &str, expected: i32) {
+        let facet = PinFacet { absolute_pin_percent: 20, relative_pin_percent: 10 };
+        let position: Position = fen.parse().unwrap();
+        assert_eq!(Evaluation::Single(expected), facet.static_eval(&position));
+        assert_eq!(Evaluation::Single(-expected), facet.static_eval(&position.reflect()));
+    }
+
+    #[test]
+    fn knight_pinned_to_king_by_bishop() {
+        execute_test("4k3/8/2n5/1B6/8/8/8/4K3 w - - 0 1", 60);
+    }
+
+    #[test]
+    fn bishop_pinned_to_queen_by_rook() {
+        execute_test("3qk3/8/8/3b4/8/8/8/3RK3 w - - 0 1", 30);
+    }
+
+    #[test]
+    fn no_pin_against_less_valuable_piece() {
+        execute_test("3nk3/8/8/3q4/8/8/8/3RK3 w - - 0 1", 0);
+    }
+
+    #[test]
+    fn two_pieces_in_between_not_pinned() {
+        execute_test("4k3/3p4/2n5/1B6/8/8/8/4K3 w - - 0 1", 0);
+    }
+}
@@ -0,0 +1,199 @@
+use crate::board::control;
+use crate::constants::boards::{FILES, LIGHT_SQUARES};
+use crate::constants::square::{H1, H8};
+use crate::constants::{
+    class, create_piece, in_board, intersects, is_superset, lift, reflect_side, side,
+};
+use crate::position::Position;
+use crate::{Class, Side};
+
+/// The scale factor which leaves an evaluation unchanged
+pub const SCALE_NORMAL: i32 = 64;
+/// Each side has a single bishop on opposite colours alongside other pieces
+const SCALE_OPPOSITE_BISHOPS: i32 = 48;
+/// Each side has a single rook and a single bishop on opposite colours and the
+/// pawn counts are close, these are very often fortress draws
+const SCALE_OPPOSITE_BISHOPS_ROOKS: i32 = 24;
+/// Each side has only a bishop on opposite colours plus pawns
+const SCALE_OPPOSITE_BISHOPS_ONLY: i32 = 16;
+/// A bishop and rook pawns which cannot drive the defending king out of the corner
+/// as the bishop does not control the promotion square
+const SCALE_WRONG_BISHOP: i32 = 0;
+/// The largest pawn advantage for which the rook fortress scaling applies
+const MAX_FORTRESS_PAWN_DIFFERENCE: u32 = 2;
+/// The half move clock at which the fifty move rule draws the game
+const CLOCK_LIMIT: usize = 100;
+/// The half move clock beyond which the evaluation shrinks linearly toward a draw
+const CLOCK_SCALE_START: usize = 60;
+
+/// Compute the factor, out of [SCALE_NORMAL], by which the evaluation of the given
+/// position should be scaled to reflect endgames where a material advantage is
+/// unlikely to be enough to win, or too little time remains before the fifty move
+/// rule to convert it.
+pub fn draw_scale(position: &Position) -> i32 {
+    material_scale(position) * clock_scale(position.clock) / SCALE_NORMAL
+}
+
+fn clock_scale(clock: usize) -> i32 {
+    if clock <= CLOCK_SCALE_START {
+        SCALE_NORMAL
+    } else {
+        let remaining = CLOCK_LIMIT.saturating_sub(clock) as i32;
+        SCALE_NORMAL * remaining / (CLOCK_LIMIT - CLOCK_SCALE_START) as i32
+    }
+}
+
+fn material_scale(position: &Position) -> i32 {
+    let signature = position.material_signature();
+    let count = |side: Side, class: Class| signature.count(create_piece(side, class));
+    if is_wrong_bishop_draw(position, side::W) || is_wrong_bishop_draw(position, side::B) {
+        return SCALE_WRONG_BISHOP;
+    }
+    let opposite_bishops = count(side::W, class::B) == 1
+        && count(side::B, class::B) == 1
+        && intersects(LIGHT_SQUARES, position.piece_boards[create_piece(side::W, class::B)])
+            != intersects(LIGHT_SQUARES, position.piece_boards[create_piece(side::B, class::B)]);
+    if !opposite_bishops {
+        return SCALE_NORMAL;
+    }
+    let no_other = |classes: &[Class]| {
+        classes.iter().all(|&class| count(side::W, class) == 0 && count(side::B, class) == 0)
+    };
+    if no_other(&[class::N, class::R, class::Q]) {
+        SCALE_OPPOSITE_BISHOPS_ONLY
+    } else if no_other(&[class::N, class::Q])
+        && count(side::W, class::R) == 1
+        && count(side::B, class::R) == 1
+        && count(side::W, class::P).abs_diff(count(side::B, class::P))
+            <= MAX_FORTRESS_PAWN_DIFFERENCE
+    {
+        SCALE_OPPOSITE_BISHOPS_ROOKS
+    } else {
+        SCALE_OPPOSITE_BISHOPS
+    }
+}
+
+/// Check if the given side has only a bishop and pawns on a single rook file against
+/// a bare king which has reached the promotion square the bishop cannot control
+fn is_wrong_bishop_draw(position: &Position, side: Side) -> bool {
+    let signature = position.material_signature();
+    let count = |side: Side, class: Class| signature.count(create_piece(side, class));
+    let enemy = reflect_side(side);
+    if count(side, class::B) != 1
+        || count(side, class::P) == 0
+        || [class::N, class::R, class::Q].iter().any(|&class| count(side, class) > 0)
+        || [class::P, class::N, class::B, class::R, class::Q]
+            .iter()
+            .any(|&class| count(enemy, class) > 0)
+    {
+        return false;
+    }
+    let pawns = position.piece_boards[create_piece(side, class::P)];
+    let Some(file) = [0, 7].into_iter().find(|&file| is_superset(FILES[file], pawns)) else {
+        return false;
+    };
+    let promotion = if side == side::W { H8 + file } else { H1 + file };
+    let bishop = position.piece_boards[create_piece(side, class::B)];
+    let enemy_king = create_piece(enemy, class::K);
+    intersects(LIGHT_SQUARES, bishop) != in_board(LIGHT_SQUARES, promotion)
+        && intersects(
+            position.piece_boards[enemy_king],
+            lift(promotion) | control(enemy_king, promotion, 0),
+        )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Symmetric;
+    use crate::node::TreeNode;
+
+    fn execute_test(fen: &str, expected: i32) {
+        let position: Position = fen.parse().unwrap();
+        assert_eq!(expected, draw_scale(&position));
+        assert_eq!(expected, draw_scale(&position.reflect()));
+    }
+
+    #[test]
+    fn same_coloured_bishops() {
+        execute_test("4k3/5p2/2b5/8/8/3B4/5PP1/4K3 w - - 0 1", SCALE_NORMAL);
+    }
+
+    #[test]
+    fn opposite_bishops_with_knights() {
+        execute_test("4k3/5p2/2b2n2/8/8/4B3/3N1PP1/4K3 w - - 0 1", SCALE_OPPOSITE_BISHOPS);
+    }
+
+    #[test]
+    fn opposite_bishops_only() {
+        execute_test("4k3/5p2/2b5/8/8/4B3/5PP1/4K3 w - - 0 1", SCALE_OPPOSITE_BISHOPS_ONLY);
+    }
+
+    #[test]
+    fn opposite_bishops_and_rooks() {
+        execute_test("2r3k1/5pp1/4b2p/8/8/2B3PP/5PK1/3R4 w - - 0 1", SCALE_OPPOSITE_BISHOPS_ROOKS);
+    }
+
+    #[test]
+    fn opposite_bishops_and_rooks_large_pawn_advantage() {
+        execute_test("2r3k1/8/4b3/8/8/2B3PP/5PK1/3R4 w - - 0 1", SCALE_OPPOSITE_BISHOPS);
+    }
+
+    #[test]
+    fn wrong_bishop_rook_pawn() {
+        execute_test("7k/8/8/7P/8/5K2/8/3B4 w - - 0 1", SCALE_WRONG_BISHOP);
+        execute_test("8/6k1/8/7P/7P/5K2/8/3B4 b - - 0 1", SCALE_WRONG_BISHOP);
+    }
+
+    #[test]
+    fn right_bishop_rook_pawn() {
+        execute_test("7k/8/8/7P/8/5K2/8/2B5 w - - 0 1", SCALE_NORMAL);
+    }
+
+    #[test]
+    fn wrong_bishop_defending_king_too_far() {
+        execute_test("k7/8/8/7P/8/5K2/8/3B4 w - - 0 1", SCALE_NORMAL);
+    }
+
+    #[test]
+    fn scale_shrinks_as_clock_nears_limit() {
+        execute_test("4k3/5p2/2b5/8/8/3B4/5PP1/4K3 w - - 60 80", SCALE_NORMAL);
+        execute_test("4k3/5p2/2b5/8/8/3B4/5PP1/4K3 w - - 80 80", SCALE_NORMAL / 2);
+        execute_test("4k3/5p2/2b5/8/8/4B3/5PP1/4K3 w - - 80 80", SCALE_OPPOSITE_BISHOPS_ONLY / 2);
+        execute_test("4k3/5p2/2b5/8/8/3B4/5PP1/4K3 w - - 99 80", SCALE_NORMAL / 40);
+    }
+
+    #[test]
+    fn material_edge_worth_less_near_fifty_moves() {
+        let eval = |clock: usize| {
+            let fen = format!("4k3/8/8/8/8/8/2R2PP1/4K3 w - - {} 80", clock);
+            TreeNode::from(fen.parse::<Position>().unwrap()).relative_eval()
+        };
+        assert!(eval(90) < eval(10), "{} {}", eval(90), eval(10));
+        assert!(0 < eval(90));
+    }
+
+    #[test]
+    fn wrong_bishop_evaluated_as_draw() {
+        let drawn = TreeNode::from("7k/8/8/7P/8/5K2/8/3B4 w - - 0 1".parse::<Position>().unwrap());
+        let winning =
+            TreeNode::from("7k/8/8/7P/8/5K2/8/2B5 w - - 0 1".parse::<Position>().unwrap());
+        assert_eq!(0, drawn.relative_eval());
+        assert!(winning.relative_eval() > 300, "{}", winning.relative_eval());
+    }
+
+    #[test]
+    fn drawn_rook_fortress_evaluated_close_to_draw() {
+        // White is a pawn up but cannot break the blockade on the light squares
+        let drawn = TreeNode::from(
+            "8/5k2/4bp2/r6p/7P/4BPK1/5P2/1R6 w - - 0 1".parse::<Position>().unwrap(),
+        );
+        // The same material with bishops of the same colour
+        let winning = TreeNode::from(
+            "8/5k2/4bp2/r6p/7P/3B1PK1/5P2/1R6 w - - 0 1".parse::<Position>().unwrap(),
+        );
+        // Less than half the value of a pawn
+        assert!(drawn.relative_eval().abs() < 115, "{}", drawn.relative_eval());
+        assert!(2 * drawn.relative_eval() < winning.relative_eval());
+    }
+}
@@ -4,16 +4,19 @@ use crate::node::{EvalFacet, Evaluation};
 use crate::position::Position;
 use crate::{Side, board};
 
-const DEFAULT_SPACE_VALUE: i32 = 5;
+const DEFAULT_MID_SPACE_VALUE: i32 = 5;
+/// Space is worth less once there are few pieces left to make use of it
+const DEFAULT_END_SPACE_VALUE: i32 = 2;
 
 #[derive(Debug, Clone)]
 pub struct SpaceFacet {
-    space_value: i32,
+    mid_space_value: i32,
+    end_space_value: i32,
 }
 
 impl Default for SpaceFacet {
     fn default() -> Self {
-        Self { space_value: DEFAULT_SPACE_VALUE }
+        Self { mid_space_value: DEFAULT_MID_SPACE_VALUE, end_space_value: DEFAULT_END_SPACE_VALUE }
     }
 }
 
@@ -40,11 +43,33 @@ fn compute_space_count(position: &Position, side: Side) -> i32 {
 impl EvalFacet for SpaceFacet {
     fn static_eval(&self, board: &Position) -> Evaluation {
         let space_diff = compute_space_count(board, side::W) - compute_space_count(board, side::B);
-        let eval = self.space_value * space_diff;
-        Evaluation::Single(eval)
+        Evaluation::Phased {
+            mid: self.mid_space_value * space_diff,
+            end: self.end_space_value * space_diff,
+        }
     }
 
     fn make(&mut self, _mv: &Move, _board: &Position) {}
 
     fn unmake(&mut self, _mv: &Move) {}
 }
+
+#[cfg(test)]
+mod test {
+    use crate::Symmetric;
+    use crate::eval::SpaceFacet;
+    use crate::node::{EvalFacet, Evaluation};
+    use crate::position::Position;
+
+    #[test]
+    fn tapered_by_phase() {
+        // The knight on d4 reaches 8 squares, the black king controls none of them
+        let position: Position = "4k3/8/8/8/3N4/8/8/4K3 w - - 0 1".parse().unwrap();
+        let facet = SpaceFacet { mid_space_value: 5, end_space_value: 2 };
+        assert_eq!(Evaluation::Phased { mid: 40, end: 16 }, facet.static_eval(&position));
+        assert_eq!(
+            Evaluation::Phased { mid: -40, end: -16 },
+            facet.static_eval(&position.reflect())
+        );
+    }
+}
@@ -2,11 +2,19 @@ use crate::constants::{
     class, corner_side, create_piece, piece_class, piece_side, reflect_piece, reflect_square,
     square_file, square_rank,
 };
-use crate::{ClassMap, Piece, SideMap, Square, SquareMap, Symmetric};
+use crate::{Class, ClassMap, Piece, SideMap, Square, SquareMap, Symmetric};
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::str::FromStr;
 
+use crate::constants::side;
 use crate::moves::Move;
 use crate::node::{EvalFacet, Evaluation};
-use crate::position::{CASTLING_DETAILS, Position};
+use crate::position::{CastlingDetails, Position};
+use anyhow::{Error, Result, anyhow};
+
+/// Piece letters used to label each table in a table file, indexed by class
+const TABLE_LABELS: ClassMap<&str> = ["P", "N", "B", "R", "Q", "K"];
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct PieceSquareTablesFacet {
@@ -23,16 +31,30 @@ impl Default for PieceSquareTablesFacet {
 
 impl<'a> From<&'a Position> for PieceSquareTablesFacet {
     fn from(value: &Position) -> Self {
-        let mut facet = PieceSquareTablesFacet::default();
-        facet.mid_eval = facet.compute_midgame_eval(value);
-        facet.end_eval = facet.compute_endgame_eval(value);
-        facet
+        PieceSquareTablesFacet::default().at_position(value)
     }
 }
 
 type UpdateFn = fn(&mut PieceSquareTablesFacet, Piece, Square) -> ();
 
 impl PieceSquareTablesFacet {
+    /// Load the white tables from a file in the format described on [PositionTables],
+    /// the black tables are the reflections. The facet is set up for the start position,
+    /// use [PieceSquareTablesFacet::at_position] for any other.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Cannot read tables from {}: {}", path.display(), e))?;
+        Ok(PieceSquareTablesFacet { tables: content.parse()?, mid_eval: 0, end_eval: 0 })
+    }
+
+    /// Recompute the incremental evals from scratch for the given position
+    pub fn at_position(mut self, position: &Position) -> Self {
+        self.mid_eval = self.compute_midgame_eval(position);
+        self.end_eval = self.compute_endgame_eval(position);
+        self
+    }
+
     pub fn compute_midgame_eval(&self, board: &Position) -> i32 {
         (0..64)
             .flat_map(|square| board.piece_locs[square].map(|p| (p, square)))
@@ -60,8 +82,8 @@ impl PieceSquareTablesFacet {
     fn make_impl(&mut self, mv: &Move, add: UpdateFn, remove: UpdateFn) {
         match mv {
             Move::Null => {}
-            &Move::Castle { corner } => {
-                let details = &CASTLING_DETAILS[corner];
+            &Move::Castle { corner, king, rook } => {
+                let details = CastlingDetails::new(corner, king, rook);
                 let side = corner_side(corner);
                 let rook = create_piece(side, class::R);
                 let king = create_piece(side, class::K);
@@ -108,6 +130,10 @@ impl EvalFacet for PieceSquareTablesFacet {
     }
 }
 
+/// The midgame and endgame tables for each piece. In text form each white table starts
+/// with a header line of the piece letter and phase, e.g. `N mid`, followed by its 64
+/// values laid out like a board diagram, from a8 to h8 down to a1 to h1. Lines starting
+/// with `#` are ignored and every table must appear exactly once.
 #[derive(Debug, Clone, PartialOrd, PartialEq, Eq)]
 pub struct PositionTables {
     tables: SideMap<ClassMap<SquareTable>>,
@@ -126,34 +152,113 @@ impl PositionTables {
     }
 }
 
+impl PositionTables {
+    fn from_white(white: ClassMap<SquareTable>) -> PositionTables {
+        let black = std::array::from_fn(|class| white[class].reflect());
+        PositionTables { tables: [white, black] }
+    }
+}
+
+/// In a table file the values run from a8 to h1 which is the reverse of square order
+fn file_index(square: Square) -> usize {
+    63 - square
+}
+
+impl FromStr for PositionTables {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut values: ClassMap<[Option<Vec<i32>>; 2]> = Default::default();
+        let mut current: Option<(Class, usize)> = None;
+        for line in s.lines().map(|l| l.trim()).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+            if let Some((class, phase)) = parse_header(line) {
+                if values[class][phase].replace(vec![]).is_some() {
+                    return Err(anyhow!("Duplicate table {}", line));
+                }
+                current = Some((class, phase));
+                continue;
+            }
+            let (class, phase) =
+                current.ok_or_else(|| anyhow!("Values given before any table header"))?;
+            let table = values[class][phase].as_mut().unwrap();
+            for token in line.split_whitespace() {
+                table.push(token.parse().map_err(|_| anyhow!("Cannot parse value {}", token))?);
+            }
+        }
+        let mut white: ClassMap<SquareTable> = Default::default();
+        for class in 0..6 {
+            let mut phases = [[0; 64]; 2];
+            for (phase, name) in ["mid", "end"].into_iter().enumerate() {
+                let label = TABLE_LABELS[class];
+                let table = values[class][phase]
+                    .as_ref()
+                    .ok_or_else(|| anyhow!("Missing table {} {}", label, name))?;
+                phases[phase] = table.as_slice().try_into().map_err(|_| {
+                    anyhow!("Table {} {} has {} values, expected 64", label, name, table.len())
+                })?;
+            }
+            white[class] = SquareTable(std::array::from_fn(|sq| {
+                (phases[0][file_index(sq)], phases[1][file_index(sq)])
+            }));
+        }
+        Ok(PositionTables::from_white(white))
+    }
+}
+
+fn parse_header(line: &str) -> Option<(Class, usize)> {
+    let mut tokens = line.split_whitespace();
+    let label = tokens.next()?;
+    let class = TABLE_LABELS.iter().position(|&l| l == label)?;
+    let phase = match tokens.next()? {
+        "mid" => 0,
+        "end" => 1,
+        _ => return None,
+    };
+    tokens.next().is_none().then_some((class, phase))
+}
+
+impl Display for PositionTables {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for (class, table) in self.tables[side::W].iter().enumerate() {
+            for (phase, name) in ["mid", "end"].into_iter().enumerate() {
+                writeln!(f, "{} {}", TABLE_LABELS[class], name)?;
+                for row in 0..8 {
+                    let line = (0..8)
+                        .map(|column| {
+                            let (mid, end) = table.0[file_index(8 * row + column)];
+                            format!("{:>5}", if phase == 0 { mid } else { end })
+                        })
+                        .collect::<String>();
+                    writeln!(f, "{}", line.trim_end())?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
 impl Default for PositionTables {
     fn default() -> Self {
-        PositionTables {
-            tables: [
-                [
-                    parse_full(PAWN),
-                    parse_symmetric(KNIGHT),
-                    parse_symmetric(BISHOP),
-                    parse_symmetric(ROOK),
-                    parse_symmetric(QUEEN),
-                    parse_symmetric(KING),
-                ],
-                [
-                    parse_full(PAWN).reflect(),
-                    parse_symmetric(KNIGHT).reflect(),
-                    parse_symmetric(BISHOP).reflect(),
-                    parse_symmetric(ROOK).reflect(),
-                    parse_symmetric(QUEEN).reflect(),
-                    parse_symmetric(KING).reflect(),
-                ],
-            ],
-        }
+        PositionTables::from_white([
+            parse_full(PAWN),
+            parse_symmetric(KNIGHT),
+            parse_symmetric(BISHOP),
+            parse_symmetric(ROOK),
+            parse_symmetric(QUEEN),
+            parse_symmetric(KING),
+        ])
     }
 }
 
 #[derive(Debug, Clone, PartialOrd, PartialEq, Eq)]
 struct SquareTable(SquareMap<(i32, i32)>);
 
+impl Default for SquareTable {
+    fn default() -> Self {
+        SquareTable([(0, 0); 64])
+    }
+}
+
 impl Symmetric for SquareTable {
     fn reflect(&self) -> Self {
         SquareTable(std::array::from_fn(|sq| {
@@ -273,8 +378,15 @@ mod test {
 
     use super::{PieceSquareTablesFacet, PositionTables};
     use crate::node::EvalFacet;
+    use crate::phase::{game_phase, interpolate};
     use crate::position::Position;
 
+    fn tapered_eval(fen: &str) -> i32 {
+        let position: Position = fen.parse().unwrap();
+        let pst = PieceSquareTablesFacet::from(&position);
+        interpolate(game_phase(&position), pst.mid_eval, pst.end_eval)
+    }
+
     #[test]
     fn zero_eval_for_start() {
         let pst = PieceSquareTablesFacet::from(&Position::default());
@@ -300,6 +412,62 @@ mod test {
         assert_eq!(-194, tables.endgame(create_piece(side::B, class::K), D4));
     }
 
+    #[test]
+    fn king_centralised_in_pawn_endgame() {
+        let centre = tapered_eval("8/4k3/8/8/4K3/8/4P3/8 w - - 0 1");
+        let home = tapered_eval("8/4k3/8/8/8/8/4P3/4K3 w - - 0 1");
+        assert!(centre > home, "{} vs {}", centre, home);
+    }
+
+    #[test]
+    fn king_stays_home_in_opening() {
+        let home = tapered_eval("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        let centre = tapered_eval("rnbqkbnr/pppppppp/8/8/4K3/8/PPPPPPPP/RNBQ1BNR w kq - 0 1");
+        assert!(home > centre, "{} vs {}", home, centre);
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let tables = PositionTables::default();
+        assert_eq!(tables, tables.to_string().parse::<PositionTables>().unwrap());
+    }
+
+    #[test]
+    fn load_tables_from_file() {
+        let knight = create_piece(side::W, class::N);
+        let default = PositionTables::default();
+        let value = default.midgame(knight, F3);
+        assert_ne!(0, value);
+        // The white knight table is the third group of tables, f3 is the sixth row down
+        let mut lines = default.to_string().lines().map(|l| l.to_string()).collect::<Vec<_>>();
+        let mut row = lines[2 * 9 + 1 + 5].split_whitespace().collect::<Vec<_>>();
+        let inverted = (-value).to_string();
+        row[5] = inverted.as_str();
+        lines[2 * 9 + 1 + 5] = row.join(" ");
+        let path = std::env::temp_dir().join(format!("hyperopic-pst-{}.txt", std::process::id()));
+        std::fs::write(&path, lines.join("\n")).unwrap();
+        let loaded = PieceSquareTablesFacet::from_file(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let position: Position = "4k3/8/8/8/8/5N2/8/4K3 w - - 0 1".parse().unwrap();
+        let custom = loaded.unwrap().at_position(&position);
+        let standard = PieceSquareTablesFacet::from(&position);
+        assert_eq!(standard.mid_eval - 2 * value, custom.mid_eval);
+        assert_eq!(standard.end_eval, custom.end_eval);
+        assert_eq!(-value, custom.tables.midgame(knight, F3));
+        assert_eq!(value, custom.tables.midgame(create_piece(side::B, class::N), F6));
+    }
+
+    #[test]
+    fn rejects_wrong_value_count() {
+        let text = PositionTables::default().to_string();
+        let truncated = text.replacen("N end\n", "N end\n0 ", 1);
+        let error = truncated.parse::<PositionTables>().unwrap_err();
+        assert_eq!("Table N end has 65 values, expected 64", error.to_string());
+        let missing = text.replacen("K mid", "# K mid", 1);
+        assert!(missing.parse::<PositionTables>().is_err());
+    }
+
     #[test]
     fn test_evolution() {
         let pgn = "1. e4 c5 2. Nc3 Nc6 3. Nf3 e6 4. Bc4 d6 5. d4 cxd4 6. Nxd4 Nxd4 \
@@ -0,0 +1,59 @@
+use crate::constants::side_parity;
+use crate::moves::Move;
+use crate::node::{EvalFacet, Evaluation};
+use crate::position::Position;
+
+const DEFAULT_TEMPO_VALUE: i32 = 10;
+
+/// Awards a small bonus to the side to move, having the move is usually worth
+/// something and it evens out the evals of odd and even depth searches. This is not
+/// part of the default evaluation and must be added with [crate::node::TreeNode::push_facet].
+#[derive(Debug, Clone)]
+pub struct TempoFacet {
+    tempo_value: i32,
+}
+
+impl Default for TempoFacet {
+    fn default() -> Self {
+        Self { tempo_value: DEFAULT_TEMPO_VALUE }
+    }
+}
+
+impl EvalFacet for TempoFacet {
+    fn static_eval(&self, board: &Position) -> Evaluation {
+        Evaluation::Single(side_parity(board.active) * self.tempo_value)
+    }
+
+    fn make(&mut self, _: &Move, _: &Position) {}
+
+    fn unmake(&mut self, _: &Move) {}
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Symmetric;
+    use crate::eval::TempoFacet;
+    use crate::node::{EvalFacet, Evaluation};
+    use crate::position::Position;
+
+    const WHITE_TO_MOVE: &str = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+    const BLACK_TO_MOVE: &str = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 2 3";
+
+    fn eval(fen: &str) -> Evaluation {
+        TempoFacet { tempo_value: 10 }.static_eval(&fen.parse::<Position>().unwrap())
+    }
+
+    #[test]
+    fn favours_side_to_move() {
+        assert_eq!(Evaluation::Single(10), eval(WHITE_TO_MOVE));
+        assert_eq!(Evaluation::Single(-10), eval(BLACK_TO_MOVE));
+    }
+
+    #[test]
+    fn flips_under_reflection() {
+        let facet = TempoFacet::default();
+        let position: Position = WHITE_TO_MOVE.parse().unwrap();
+        let Evaluation::Single(eval) = facet.static_eval(&position) else { panic!() };
+        assert_eq!(Evaluation::Single(-eval), facet.static_eval(&position.reflect()));
+    }
+}
@@ -1,7 +1,8 @@
-use crate::constants::{piece_class, side};
-use crate::moves::Move;
+use crate::constants::{class, corner_side, piece_class, side, square_file, square_rank};
+use crate::moves::{Move, Moves};
 use crate::parse::StringIndexMap;
-use crate::position::{CASTLING_DETAILS, Position};
+use crate::position::{CastlingDetails, Position, STANDARD_CASTLING_SQUARES, TerminalState};
+use crate::{Piece, Square};
 
 use lazy_static::lazy_static;
 use std::fmt::{Display, Formatter};
@@ -33,30 +34,198 @@ impl Display for Position {
 
 impl Display for Move {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_uci(false))
+    }
+}
+
+impl Move {
+    /// Format this move in UCI notation. In Chess960 mode castling is encoded as the
+    /// king moving to the square of the rook it castles with, otherwise it is encoded
+    /// as the king moving two squares.
+    pub fn to_uci(&self, chess960: bool) -> String {
         lazy_static! {
             static ref SQUARES: StringIndexMap = StringIndexMap::squares();
             static ref PIECES: StringIndexMap = StringIndexMap::uci_pieces();
         }
-        write!(
-            f,
-            "{}",
-            match self {
-                Move::Null => "null".to_owned(),
-                &Move::Normal { from, dest, .. } =>
-                    format!("{}{}", SQUARES.format(from), SQUARES.format(dest)),
-                &Move::Enpassant { from, dest, .. } =>
-                    format!("{}{}", SQUARES.format(from), SQUARES.format(dest)),
-                &Move::Castle { corner, .. } => {
-                    let details = &CASTLING_DETAILS[corner];
-                    let (from, dest) = details.king_line;
-                    format!("{}{}", SQUARES.format(from), SQUARES.format(dest))
-                }
-                &Move::Promote { from, dest, promoted, .. } => {
-                    let promote_class = PIECES.format(piece_class(promoted));
-                    format!("{}{}{}", SQUARES.format(from), SQUARES.format(dest), promote_class)
-                }
+        match self {
+            Move::Null => "null".to_owned(),
+            &Move::Normal { from, dest, .. } => {
+                format!("{}{}", SQUARES.format(from), SQUARES.format(dest))
             }
-        )
+            &Move::Enpassant { from, dest, .. } => {
+                format!("{}{}", SQUARES.format(from), SQUARES.format(dest))
+            }
+            &Move::Castle { corner, king, rook } => {
+                let (from, king_dest) = CastlingDetails::new(corner, king, rook).king_line;
+                let dest = if chess960 { rook } else { king_dest };
+                format!("{}{}", SQUARES.format(from), SQUARES.format(dest))
+            }
+            &Move::Promote { from, dest, promoted, .. } => {
+                let promote_class = PIECES.format(piece_class(promoted));
+                format!("{}{}{}", SQUARES.format(from), SQUARES.format(dest), promote_class)
+            }
+        }
+    }
+
+    /// Format this move in standard algebraic notation. The position must be the one
+    /// the move is played from, it is needed to disambiguate between pieces of the same
+    /// type moving to the same square and to decide the check or mate suffix.
+    pub fn to_san(&self, position: &Position) -> String {
+        lazy_static! {
+            static ref SQUARES: StringIndexMap = StringIndexMap::squares();
+        }
+        let file = |sq: Square| FILES[square_file(sq)];
+        let capture = |captures: bool| if captures { "x" } else { "" };
+        let san = match self {
+            Move::Null => return "--".to_owned(),
+            &Move::Castle { corner, .. } => {
+                if corner % 2 == 0 { "O-O" } else { "O-O-O" }.to_owned()
+            }
+            &Move::Enpassant { from, dest, .. } => {
+                format!("{}x{}", file(from), SQUARES.format(dest))
+            }
+            &Move::Promote { from, dest, promoted, capture: taken } => format!(
+                "{}{}{}={}",
+                if taken.is_some() { file(from) } else { "" },
+                capture(taken.is_some()),
+                SQUARES.format(dest),
+                SAN_PIECES[piece_class(promoted)]
+            ),
+            &Move::Normal { moving, from, dest, capture: taken } => {
+                let prefix = if piece_class(moving) != class::P {
+                    let disambiguation = san_disambiguation(position, moving, from, dest);
+                    format!("{}{}", SAN_PIECES[piece_class(moving)], disambiguation)
+                } else if taken.is_some() {
+                    file(from).to_owned()
+                } else {
+                    String::new()
+                };
+                format!("{}{}{}", prefix, capture(taken.is_some()), SQUARES.format(dest))
+            }
+        };
+        let mut next = position.clone();
+        let suffix = match next.make(self.clone()) {
+            Ok(_) if next.compute_terminal_state() == Some(TerminalState::Loss) => "#",
+            Ok(_) if next.in_check() => "+",
+            _ => "",
+        };
+        format!("{}{}", san, suffix)
+    }
+}
+
+/// The tags every PGN game should carry in the order they are written, with the value
+/// used when no other is given.
+const SEVEN_TAG_ROSTER: [(&str, &str); 7] = [
+    ("Event", "?"),
+    ("Site", "?"),
+    ("Date", "????.??.??"),
+    ("Round", "?"),
+    ("White", "?"),
+    ("Black", "?"),
+    ("Result", "*"),
+];
+
+/// Movetext lines are wrapped before reaching this many characters
+const PGN_LINE_LIMIT: usize = 80;
+
+impl Position {
+    /// Render the game leading to this position as PGN. The seven tag roster comes first
+    /// using the given header values where present, the remaining given headers follow in
+    /// order. If the game is over the result is derived from the final position rather
+    /// than taken from the headers. Games not played from the standard start position
+    /// carry their initial position in SetUp and FEN headers.
+    pub fn to_pgn(&self, headers: &[(String, String)]) -> String {
+        let mut start = self.clone();
+        while start.unmake().is_ok() {}
+        let result = match self.compute_terminal_state() {
+            Some(TerminalState::Draw) => "1/2-1/2".to_owned(),
+            Some(TerminalState::Loss) if self.active == side::W => "0-1".to_owned(),
+            Some(TerminalState::Loss) => "1-0".to_owned(),
+            None => headers
+                .iter()
+                .find(|(name, _)| name == "Result")
+                .map_or("*".to_owned(), |(_, value)| value.clone()),
+        };
+
+        let mut tags = SEVEN_TAG_ROSTER
+            .iter()
+            .map(|&(name, default)| {
+                let given = headers.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str());
+                let value =
+                    if name == "Result" { result.as_str() } else { given.unwrap_or(default) };
+                (name.to_owned(), value.to_owned())
+            })
+            .collect::<Vec<_>>();
+        if start.to_string() != Position::default().to_string() {
+            tags.push(("SetUp".to_owned(), "1".to_owned()));
+            tags.push(("FEN".to_owned(), start.to_string()));
+        }
+        let reserved = |name: &str| {
+            SEVEN_TAG_ROSTER.iter().any(|(n, _)| *n == name) || name == "SetUp" || name == "FEN"
+        };
+        tags.extend(headers.iter().filter(|(name, _)| !reserved(name)).cloned());
+
+        let mut tokens = vec![];
+        let black_first = if start.active == side::B { 1 } else { 0 };
+        for (i, (_, m)) in self.history.iter().enumerate() {
+            let move_number = (i + black_first) / 2 + 1;
+            if start.active == side::W {
+                tokens.push(format!("{}.", move_number));
+            } else if i == 0 {
+                tokens.push(format!("{}...", move_number));
+            }
+            tokens.push(m.to_san(&start));
+            start.make(m.clone()).unwrap();
+        }
+        tokens.push(result);
+
+        let mut pgn = tags
+            .iter()
+            .map(|(name, value)| format!("[{} \"{}\"]\n", name, value))
+            .collect::<String>();
+        pgn.push('\n');
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() >= PGN_LINE_LIMIT {
+                pgn.push_str(&line);
+                pgn.push('\n');
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        pgn.push_str(&line);
+        pgn.push('\n');
+        pgn
+    }
+}
+
+/// The smallest prefix of the source square which separates a move from the other
+/// legal moves of the same piece type to the same square, preferring the file.
+fn san_disambiguation(position: &Position, moving: Piece, from: Square, dest: Square) -> String {
+    lazy_static! {
+        static ref SQUARES: StringIndexMap = StringIndexMap::squares();
+    }
+    let others = position
+        .moves(&Moves::All)
+        .into_iter()
+        .filter_map(|m| match m {
+            Move::Normal { moving: p, from: f, dest: d, .. } if p == moving && d == dest => Some(f),
+            _ => None,
+        })
+        .filter(|&f| f != from)
+        .collect::<Vec<_>>();
+    let source = SQUARES.format(from);
+    if others.is_empty() {
+        String::new()
+    } else if others.iter().all(|&f| square_file(f) != square_file(from)) {
+        source[..1].to_owned()
+    } else if others.iter().all(|&f| square_rank(f) != square_rank(from)) {
+        source[1..].to_owned()
+    } else {
+        source.to_owned()
     }
 }
 
@@ -113,8 +282,15 @@ fn to_fen_side(board: &Position) -> String {
 }
 
 fn to_fen_castling_rights(board: &Position) -> String {
-    let rights =
-        (0..4).filter(|c| board.castling_rights[*c]).map(|c| CORNERS[c]).collect::<String>();
+    // Chess960 rights are written in Shredder-FEN notation as the file of the rook
+    let rights = (0..4)
+        .filter(|c| board.castling_rights[*c])
+        .map(|c| match board.castling_squares[c] {
+            squares if squares == STANDARD_CASTLING_SQUARES[c] => CORNERS[c].to_owned(),
+            (_, rook) if corner_side(c) == side::W => FILES[square_file(rook)].to_uppercase(),
+            (_, rook) => FILES[square_file(rook)].to_owned(),
+        })
+        .collect::<String>();
     if rights.is_empty() { format!("-") } else { rights }
 }
 
@@ -133,12 +309,17 @@ fn to_fen_move_count(board: &Position) -> String {
 }
 
 const CORNERS: [&'static str; 4] = ["K", "Q", "k", "q"];
+const FILES: [&str; 8] = ["h", "g", "f", "e", "d", "c", "b", "a"];
+const SAN_PIECES: [&str; 6] = ["", "N", "B", "R", "Q", "K"];
 const PIECES: [&'static str; 12] = ["P", "N", "B", "R", "Q", "K", "p", "n", "b", "r", "q", "k"];
 
 #[cfg(test)]
 mod test {
     use super::to_fen_impl;
+    use crate::constants::{corner, piece, square};
     use crate::format::FenPart;
+    use crate::moves::Move;
+    use crate::parse::{parse_uci_move, verify_pgn};
     use crate::position::Position;
     use std::iter::once;
 
@@ -279,4 +460,153 @@ mod test {
         let expected = "rnbq1br1/pppkppp1/5n1p/3pP3/8/5N2/PPPPKPPP/RNBQ1B1R b - - 3 6";
         assert_eq!(expected, position_2().to_string());
     }
+
+    #[test]
+    fn chess960_castling_rights_round_trip() {
+        for fen in [
+            "bqnbrkrn/pppppppp/8/8/8/8/PPPPPPPP/BQNBRKRN w GEge - 0 1",
+            "rkrnnqbb/pppppppp/8/8/8/8/PPPPPPPP/RKRNNQBB b Ca - 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        ] {
+            assert_eq!(fen, fen.parse::<Position>().unwrap().to_string());
+        }
+    }
+
+    #[test]
+    fn chess960_castling_rights_from_x_fen() {
+        let position: Position =
+            "bqnbrkrn/pppppppp/8/8/8/8/PPPPPPPP/BQNBRKRN w KQkq - 0 1".parse().unwrap();
+        assert_eq!("GEge", to_fen_impl(&position, once(FenPart::CastlingRights)));
+        let standard: Position = START_FEN.replace("KQkq", "HAha").parse().unwrap();
+        assert_eq!(START_FEN, standard.to_string());
+    }
+
+    #[test]
+    fn display_normal_move() {
+        let m = Move::Normal { moving: piece::WN, from: square::G1, dest: square::F3, capture: None };
+        assert_eq!("g1f3", m.to_string());
+        assert_eq!("g1f3", m.to_uci(true));
+    }
+
+    #[test]
+    fn display_promotion() {
+        let m = Move::Promote {
+            from: square::B7,
+            dest: square::A8,
+            promoted: piece::WQ,
+            capture: Some(piece::BR),
+        };
+        assert_eq!("b7a8q", m.to_string());
+    }
+
+    #[test]
+    fn display_standard_castling() {
+        let castle = |corner, king, rook| Move::Castle { corner, king, rook };
+        assert_eq!("e1g1", castle(corner::WK, square::E1, square::H1).to_string());
+        assert_eq!("e1c1", castle(corner::WQ, square::E1, square::A1).to_string());
+        assert_eq!("e8g8", castle(corner::BK, square::E8, square::H8).to_uci(false));
+        assert_eq!("e8c8", castle(corner::BQ, square::E8, square::A8).to_uci(false));
+    }
+
+    #[test]
+    fn display_chess960_castling() {
+        let castle = |corner, king, rook| Move::Castle { corner, king, rook };
+        assert_eq!("e1h1", castle(corner::WK, square::E1, square::H1).to_uci(true));
+        assert_eq!("e1a1", castle(corner::WQ, square::E1, square::A1).to_uci(true));
+        assert_eq!("e8h8", castle(corner::BK, square::E8, square::H8).to_uci(true));
+        assert_eq!("e8a8", castle(corner::BQ, square::E8, square::A8).to_uci(true));
+    }
+
+    #[test]
+    fn display_chess960_castling_from_non_standard_squares() {
+        let kingside = Move::Castle { corner: corner::WK, king: square::F1, rook: square::G1 };
+        assert_eq!("f1g1", kingside.to_uci(true));
+        assert_eq!("f1g1", kingside.to_uci(false));
+        let queenside = Move::Castle { corner: corner::BQ, king: square::B8, rook: square::A8 };
+        assert_eq!("b8a8", queenside.to_uci(true));
+        assert_eq!("b8c8", queenside.to_uci(false));
+    }
+
+    fn san(fen: &str, uci: &str) -> String {
+        let position: Position = fen.parse().unwrap();
+        parse_uci_move(&position, uci).unwrap().to_san(&position)
+    }
+
+    #[test]
+    fn san_piece_moves_and_captures() {
+        let position = "1. e4 d5 2. Nf3 Nc6 3. Bb5 Qd6".parse::<Position>().unwrap();
+        assert_eq!("exd5", san(&position.to_string(), "e4d5"));
+        assert_eq!("Bxc6+", san(&position.to_string(), "b5c6"));
+        assert_eq!("Nf3", san(START_FEN, "g1f3"));
+        assert_eq!("e4", san(START_FEN, "e2e4"));
+    }
+
+    #[test]
+    fn san_disambiguates_by_file() {
+        assert_eq!("Rad1", san("4k3/8/8/8/8/8/8/R4RK1 w - - 0 1", "a1d1"));
+        assert_eq!("Rfd1", san("4k3/8/8/8/8/8/8/R4RK1 w - - 0 1", "f1d1"));
+    }
+
+    #[test]
+    fn san_disambiguates_by_rank() {
+        assert_eq!("R1a3", san("4k3/8/8/R7/8/8/8/R3K3 w - - 0 1", "a1a3"));
+        assert_eq!("R5a3", san("4k3/8/8/R7/8/8/8/R3K3 w - - 0 1", "a5a3"));
+    }
+
+    #[test]
+    fn san_disambiguates_by_square() {
+        let fen = "8/8/8/7k/8/Q1Q5/8/Q6K w - - 0 1";
+        assert_eq!("Q1b2", san(fen, "a1b2"));
+        assert_eq!("Qcb2", san(fen, "c3b2"));
+        assert_eq!("Qa3b2", san(fen, "a3b2"));
+    }
+
+    #[test]
+    fn san_promotions() {
+        assert_eq!("e8=Q+", san("k7/4P3/8/8/8/8/8/4K3 w - - 0 1", "e7e8q"));
+        assert_eq!("dxe8=N", san("k3r3/3P4/8/8/8/8/8/4K3 w - - 0 1", "d7e8n"));
+    }
+
+    #[test]
+    fn san_castling() {
+        let fen = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        assert_eq!("O-O", san(fen, "e1g1"));
+        assert_eq!("O-O-O", san(fen, "e1c1"));
+    }
+
+    #[test]
+    fn san_checkmate() {
+        assert_eq!("Ra8#", san("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", "a1a8"));
+    }
+
+    #[test]
+    fn pgn_round_trip() {
+        let mut position = Position::default();
+        position.play_san("1. e4 e5 2. Bc4 Nc6 3. Qh5 Nf6 4. Qxf7#").unwrap();
+        let headers = vec![
+            ("White".to_owned(), "Alice".to_owned()),
+            ("Result".to_owned(), "*".to_owned()),
+            ("ECO".to_owned(), "C23".to_owned()),
+        ];
+        let pgn = position.to_pgn(&headers);
+        assert_eq!(
+            concat!(
+                "[Event \"?\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n[Round \"?\"]\n",
+                "[White \"Alice\"]\n[Black \"?\"]\n[Result \"1-0\"]\n[ECO \"C23\"]\n\n",
+                "1. e4 e5 2. Bc4 Nc6 3. Qh5 Nf6 4. Qxf7# 1-0\n"
+            ),
+            pgn
+        );
+        assert_eq!(position.to_string(), verify_pgn(&pgn).unwrap().to_string());
+    }
+
+    #[test]
+    fn pgn_from_non_standard_start() {
+        let fen = "4k3/8/8/8/8/8/8/R3K3 b Q - 0 1";
+        let mut position: Position = fen.parse().unwrap();
+        position.play_san("Kd7 O-O-O+ Kc7").unwrap();
+        let pgn = position.to_pgn(&[]);
+        assert!(pgn.contains("[SetUp \"1\"]\n[FEN \"4k3/8/8/8/8/8/8/R3K3 b Q - 0 1\"]\n"));
+        assert!(pgn.ends_with("\n\n1... Kd7 2. O-O-O+ Kc7 *\n"));
+    }
 }
@@ -27,7 +27,7 @@ pub fn corner(corner: Corner) -> u64 {
 }
 
 fn compute_features() -> Features {
-    let mut prng = PRNG { s: 1070372 };
+    let mut prng = PRNG::new(1070372);
     Features {
         black_move: prng.rand64(),
         enpassant: array::from_fn(|_| prng.rand64()),
@@ -44,12 +44,16 @@ struct Features {
 }
 
 // https://github.com/official-stockfish/Stockfish/blob/master/src/misc.h#L122
-struct PRNG {
+pub(crate) struct PRNG {
     s: u64,
 }
 
 impl PRNG {
-    fn rand64(&mut self) -> u64 {
+    pub(crate) fn new(seed: u64) -> PRNG {
+        PRNG { s: seed }
+    }
+
+    pub(crate) fn rand64(&mut self) -> u64 {
         self.s ^= self.s.wrapping_shr(12);
         self.s ^= self.s.wrapping_shl(25);
         self.s ^= self.s.wrapping_shr(27);
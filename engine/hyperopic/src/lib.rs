@@ -1,18 +1,31 @@
-use crate::moves::Move;
+use crate::cache::OutcomeCache;
+use crate::moves::{Move, Moves};
 use crate::node::TreeNode;
+use crate::openings::OpeningMoveRecord;
+use crate::phase::MAX_PHASE;
 use crate::position::Position;
-use crate::search::end::SearchEndSignal;
-use crate::search::{SearchOutcome, SearchParameters, Transpositions, ConcurrentTT};
+use crate::search::end::{CompositeEndSignal, EmptyEndSignal, PonderEndSignal, SearchEndSignal};
+use crate::search::{
+    ConcurrentTT, Contempt, InfoCallback, SearchOutcome, SearchParameters, Transpositions,
+};
 use crate::timing::TimeAllocator;
 use Ordering::SeqCst;
 use anyhow::{Result, anyhow};
 pub use board::union_boards;
-use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
+pub use eval::{
+    HangingPieceFacet, KnightBishopImbalanceFacet, KnightOutpostFacet, MobilityFacet,
+    PieceSquareTablesFacet, PinFacet, RookFileFacet, TempoFacet,
+};
+pub use parse::verify_pgn;
+pub use phase::game_phase;
+pub use see::see;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 use threadpool::ThreadPool;
 
 mod board;
+mod cache;
 mod eval;
 mod format;
 mod hash;
@@ -24,6 +37,7 @@ mod phase;
 pub mod position;
 pub mod search;
 mod see;
+pub mod symmetry;
 #[cfg(test)]
 mod test;
 pub mod timing;
@@ -32,6 +46,12 @@ pub mod constants;
 #[cfg(test)]
 mod bench;
 
+/// Null move cutoffs are verified in positions at least this far into the endgame
+/// where zugzwang is a real possibility
+const NULL_MOVE_VERIFICATION_PHASE: i32 = 3 * MAX_PHASE / 4;
+/// The minimum remaining depth for null move cutoffs to be verified
+const NULL_MOVE_VERIFICATION_DEPTH: u8 = 6;
+
 pub type Side = usize;
 // H1 -> .. -> A1 -> H2 ... -> A8
 pub type Square = usize;
@@ -100,9 +120,14 @@ pub trait Symmetric {
 
 pub trait LookupMoveService {
     fn lookup(&self, position: Position) -> Result<Option<Move>>;
+
+    /// Every move this service knows for the position, only opening books have any
+    fn candidates(&self, _position: &Position) -> Result<Vec<OpeningMoveRecord>> {
+        Ok(vec![])
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ComputeMoveInput<E: SearchEndSignal + Clone> {
     /// The root position we want to search
     pub position: Position,
@@ -110,10 +135,17 @@ pub struct ComputeMoveInput<E: SearchEndSignal + Clone> {
     pub search_end: E,
     /// The max depth on the search
     pub max_depth: Option<u8>,
+    /// The max number of nodes visited by the search
+    pub max_nodes: Option<u64>,
+    /// No iteration of the search is started after this, except one to confirm a best
+    /// move which has just changed, see [SearchParameters::soft_end]
+    pub soft_deadline: Option<Instant>,
     /// Flag which when set disables early return, i.e. in the case
     /// of a forced checkmate we wait for the end signal instead of
     /// returning the result immediately
     pub wait_for_end: bool,
+    /// Called with the details of each iteration of the search as it completes
+    pub on_info: Option<InfoCallback>,
 }
 
 impl ComputeMoveInput<Instant> {
@@ -123,14 +155,93 @@ impl ComputeMoveInput<Instant> {
         inc: Duration,
         timing: TimeAllocator,
     ) -> Self {
-        let position_count = position.history.len();
-        ComputeMoveInput {
-            position,
-            search_end: Instant::now() + timing.allocate(position_count, remaining, inc),
-            max_depth: None,
-            wait_for_end: false,
+        ComputeMoveInputBuilder::clock(position, remaining, inc, timing).build()
+    }
+}
+
+impl<E: SearchEndSignal + Clone> ComputeMoveInput<E> {
+    pub fn builder(position: Position, search_end: E) -> ComputeMoveInputBuilder<E> {
+        ComputeMoveInputBuilder::new(position, search_end)
+    }
+}
+
+/// Builds a [ComputeMoveInput] with no depth or node limit which returns as soon
+/// as the search completes unless otherwise specified.
+#[derive(Debug, Clone)]
+pub struct ComputeMoveInputBuilder<E: SearchEndSignal + Clone> {
+    input: ComputeMoveInput<E>,
+}
+
+impl<E: SearchEndSignal + Clone> ComputeMoveInputBuilder<E> {
+    pub fn new(position: Position, search_end: E) -> Self {
+        ComputeMoveInputBuilder {
+            input: ComputeMoveInput {
+                position,
+                search_end,
+                max_depth: None,
+                max_nodes: None,
+                soft_deadline: None,
+                wait_for_end: false,
+                on_info: None,
+            },
         }
     }
+
+    pub fn max_depth(mut self, max_depth: impl Into<Option<u8>>) -> Self {
+        self.input.max_depth = max_depth.into();
+        self
+    }
+
+    pub fn max_nodes(mut self, max_nodes: impl Into<Option<u64>>) -> Self {
+        self.input.max_nodes = max_nodes.into();
+        self
+    }
+
+    pub fn soft_deadline(mut self, soft_deadline: impl Into<Option<Instant>>) -> Self {
+        self.input.soft_deadline = soft_deadline.into();
+        self
+    }
+
+    pub fn wait_for_end(mut self, wait_for_end: bool) -> Self {
+        self.input.wait_for_end = wait_for_end;
+        self
+    }
+
+    pub fn on_info(mut self, on_info: impl Into<Option<InfoCallback>>) -> Self {
+        self.input.on_info = on_info.into();
+        self
+    }
+
+    pub fn build(self) -> ComputeMoveInput<E> {
+        self.input
+    }
+}
+
+impl ComputeMoveInputBuilder<EmptyEndSignal> {
+    /// Search to exactly the given depth however long it takes
+    pub fn fixed_depth(position: Position, depth: u8) -> Self {
+        ComputeMoveInputBuilder::new(position, EmptyEndSignal).max_depth(depth)
+    }
+}
+
+impl ComputeMoveInputBuilder<Instant> {
+    /// Search for the given duration from now
+    pub fn move_time(position: Position, move_time: Duration) -> Self {
+        ComputeMoveInputBuilder::new(position, Instant::now() + move_time)
+    }
+
+    /// Search for a duration allocated from the remaining time on our clock
+    pub fn clock(
+        position: Position,
+        remaining: Duration,
+        inc: Duration,
+        timing: TimeAllocator,
+    ) -> Self {
+        let search_time = timing.allocate_for_position(&position, remaining, inc, None);
+        let start = Instant::now();
+        ComputeMoveInputBuilder::new(position, start + search_time)
+            .soft_deadline(start + timing.soft_limit(search_time))
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -139,30 +250,123 @@ pub struct ComputeMoveOutput {
     pub search_details: Option<SearchOutcome>,
 }
 
+/// How the engine chooses a move when none of its lookup services returns one. The
+/// cheaper modes suit bots which are only meant to play from an opening book.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FallbackMode {
+    /// Search within the limits given by the input
+    Search,
+    /// Play a random legal move without searching
+    RandomMove,
+    /// Search to at most the given depth regardless of the limits given by the input
+    MaxDepth(u8),
+}
+
 pub struct Engine {
-    transpositions: Arc<ConcurrentTT>,
+    transpositions: Mutex<Arc<ConcurrentTT>>,
+    /// The current game position, kept up to date by advancing it move by move
+    position: Mutex<Position>,
     lookups: Vec<Arc<dyn LookupMoveService + Send + Sync>>,
+    fallback: FallbackMode,
+    /// The number of threads used by each search, see [SearchParameters::threads]
+    search_threads: usize,
+    /// Applied to every search, see [SearchParameters::contempt]
+    contempt: Contempt,
+    /// Outcomes of earlier fixed depth searches, only present if requested
+    outcomes: Option<Arc<Mutex<OutcomeCache>>>,
+    /// The number of searches completed so far, cache hits are not counted
+    searches: Arc<AtomicU64>,
     threads: ThreadPool,
     /// Flag ensuring at most one operation runs at any time
     available: Arc<AtomicBool>,
+    /// Signal of the running ponder search if it has not yet been converted
+    ponder: Arc<Mutex<Option<PonderEndSignal>>>,
 }
 
 impl Engine {
     pub fn new(
         table_size: usize,
         lookups: Vec<Arc<dyn LookupMoveService + Send + Sync>>,
+    ) -> Engine {
+        Engine::with_table(Arc::new(ConcurrentTT::new(table_size)), lookups)
+    }
+
+    /// Create an engine searching the given table which may be shared with other
+    /// engines. Each engine runs one operation at a time so to play several games at
+    /// once use an engine per game, sharing a table keeps the memory used fixed however
+    /// many games are running. The games then compete for table entries, which costs
+    /// some search quality under load, and [Engine::reset] on any engine clears the
+    /// table for all of them.
+    pub fn with_table(
+        table: Arc<ConcurrentTT>,
+        lookups: Vec<Arc<dyn LookupMoveService + Send + Sync>>,
     ) -> Engine {
         Engine {
-            transpositions: Arc::new(ConcurrentTT::new(table_size)),
+            transpositions: Mutex::new(table),
+            position: Mutex::new(Position::default()),
             lookups,
+            fallback: FallbackMode::Search,
+            search_threads: 1,
+            contempt: Contempt::default(),
+            outcomes: None,
+            searches: Arc::new(AtomicU64::new(0)),
             threads: ThreadPool::new(1),
             available: Arc::new(AtomicBool::new(true)),
+            ponder: Arc::new(Mutex::new(None)),
         }
     }
 
+    /// Change how a move is chosen when the lookup services have nothing to offer
+    pub fn with_fallback(mut self, fallback: FallbackMode) -> Engine {
+        self.fallback = fallback;
+        self
+    }
+
+    /// Search with the given number of threads sharing the transposition table
+    pub fn with_search_threads(mut self, search_threads: usize) -> Engine {
+        self.search_threads = search_threads.max(1);
+        self
+    }
+
+    /// Avoid draws with the given contempt
+    pub fn with_contempt(mut self, contempt: Contempt) -> Engine {
+        self.contempt = contempt;
+        self
+    }
+
+    /// Remember the outcomes of up to the given number of fixed depth searches so
+    /// repeating the same query for a position and depth returns without searching.
+    /// The cache is cleared on [Engine::reset].
+    pub fn with_outcome_cache(mut self, capacity: usize) -> Engine {
+        self.outcomes = Some(Arc::new(Mutex::new(OutcomeCache::new(capacity))));
+        self
+    }
+
+    /// The number of searches completed by the engine, moves from the lookup
+    /// services or the outcome cache are not counted
+    pub fn searches(&self) -> u64 {
+        self.searches.load(SeqCst)
+    }
+
     pub fn reset(&self) -> bool {
         if self.available.compare_exchange(true, false, SeqCst, SeqCst).is_ok() {
-            self.transpositions.reset();
+            self.transpositions.lock().unwrap().reset();
+            if let Some(outcomes) = self.outcomes.as_ref() {
+                outcomes.lock().unwrap().clear();
+            }
+            *self.position.lock().unwrap() = Position::default();
+            self.available.store(true, SeqCst);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Replace the transposition table with an empty one with the given number of
+    /// entries, this fails if an operation is currently running.
+    pub fn resize_table(&self, entries: usize) -> bool {
+        if self.available.compare_exchange(true, false, SeqCst, SeqCst).is_ok() {
+            *self.transpositions.lock().unwrap() = Arc::new(ConcurrentTT::new(entries));
             self.available.store(true, SeqCst);
             true
         } else {
@@ -170,6 +374,33 @@ impl Engine {
         }
     }
 
+    /// The table currently searched, see [Engine::with_table] for sharing it
+    pub fn table(&self) -> Arc<ConcurrentTT> {
+        self.transpositions.lock().unwrap().clone()
+    }
+
+    /// The game position currently held by the engine
+    pub fn position(&self) -> Position {
+        self.position.lock().unwrap().clone()
+    }
+
+    /// Replace the game position held by the engine
+    pub fn set_position(&self, position: Position) {
+        *self.position.lock().unwrap() = position;
+    }
+
+    /// Apply a legal move to the game position held by the engine, this avoids
+    /// rebuilding the position from the full move list every turn. The root index
+    /// of subsequent searches follows the game history so the table entries from
+    /// earlier searches remain usable, while being the first to make way for new ones.
+    pub fn advance(&self, mv: Move) -> Result<()> {
+        let mut position = self.position.lock().unwrap();
+        if !position.moves(&Moves::All).contains(&mv) {
+            return Err(anyhow!("{} is not legal in {}", mv, position));
+        }
+        position.make(mv)
+    }
+
     pub fn compute_move<E>(&self, input: ComputeMoveInput<E>) -> Result<ComputeMoveOutput>
     where
         E: SearchEndSignal + Clone + Send + 'static,
@@ -182,48 +413,200 @@ impl Engine {
         }
     }
 
+    /// Search the given position on the calling thread ignoring the lookup services,
+    /// the fallback mode and the outcome cache so the full search details are always
+    /// returned, even for positions a book has a move for.
+    pub fn analyse<E>(&self, input: ComputeMoveInput<E>) -> Result<SearchOutcome>
+    where
+        E: SearchEndSignal + Clone + Send + 'static,
+    {
+        if self.available.compare_exchange(true, false, SeqCst, SeqCst).is_err() {
+            return Err(anyhow!("Engine unavailable, operation already running"));
+        }
+        let node: TreeNode = input.position.into();
+        let verify_null_move = (game_phase(node.position()) >= NULL_MOVE_VERIFICATION_PHASE)
+            .then_some(NULL_MOVE_VERIFICATION_DEPTH);
+        let params = SearchParameters {
+            max_depth: input.max_depth,
+            soft_end: soft_end(input.soft_deadline),
+            confirm_unstable_best_move: true,
+            aspiration_windows: true,
+            max_nodes: input.max_nodes,
+            verify_null_move,
+            on_info: input.on_info,
+            threads: self.search_threads,
+            contempt: self.contempt,
+            ..SearchParameters::new(input.search_end, self.transpositions.lock().unwrap().clone())
+        };
+        let outcome = search::search(node, params);
+        if outcome.is_ok() {
+            self.searches.fetch_add(1, SeqCst);
+        }
+        self.available.store(true, SeqCst);
+        outcome
+    }
+
     pub fn compute_move_async<E, F>(&self, input: ComputeMoveInput<E>, on_complete: F) -> bool
     where
         E: SearchEndSignal + Clone + Send + 'static,
         F: FnOnce(Result<ComputeMoveOutput>) -> () + Send + 'static,
+    {
+        self.compute_move_with_ponder(input, None, on_complete)
+    }
+
+    /// Compute a move as in [Engine::compute_move_async], if this is a ponder search the
+    /// signal is told once the search is only waiting to be ended
+    fn compute_move_with_ponder<E, F>(
+        &self,
+        input: ComputeMoveInput<E>,
+        ponder: Option<PonderEndSignal>,
+        on_complete: F,
+    ) -> bool
+    where
+        E: SearchEndSignal + Clone + Send + 'static,
+        F: FnOnce(Result<ComputeMoveOutput>) + Send + 'static,
     {
         if self.available.compare_exchange(true, false, SeqCst, SeqCst).is_err() {
             return false;
         }
         let lookups = self.lookups.clone();
-        let transpositions = self.transpositions.clone();
+        let fallback = self.fallback;
+        let transpositions = self.transpositions.lock().unwrap().clone();
         let available = self.available.clone();
         let search_end = input.search_end.clone();
-        let max_depth = input.max_depth;
+        let max_depth = match fallback {
+            FallbackMode::MaxDepth(depth) => Some(input.max_depth.map_or(depth, |d| d.min(depth))),
+            _ => input.max_depth,
+        };
+        let max_nodes = input.max_nodes;
+        let soft_end = soft_end(input.soft_deadline);
         let wait_for_end = input.wait_for_end;
+        let on_info = input.on_info;
+        let search_threads = self.search_threads;
+        let contempt = self.contempt;
+        // Only complete searches to a fixed depth are worth caching
+        let outcomes = match (max_depth, max_nodes) {
+            (Some(depth), None) => self.outcomes.clone().map(|cache| (cache, depth)),
+            _ => None,
+        };
+        let searches = self.searches.clone();
+        let ponder_slot = self.ponder.clone();
         self.threads.execute(move || {
             let node: TreeNode = input.position.into();
+            let key = node.position().key;
+            let verify_null_move = (game_phase(node.position()) >= NULL_MOVE_VERIFICATION_PHASE)
+                .then_some(NULL_MOVE_VERIFICATION_DEPTH);
+            let cached =
+                outcomes.as_ref().and_then(|(cache, depth)| cache.lock().unwrap().get(key, *depth));
             let output = match perform_lookups(lookups, node.position().clone()) {
                 Some(mv) => Ok(ComputeMoveOutput { best_move: mv, search_details: None }),
+                None if fallback == FallbackMode::RandomMove => random_move(node.position())
+                    .map(|mv| ComputeMoveOutput { best_move: mv, search_details: None }),
+                None if cached.is_some() => Ok(ComputeMoveOutput {
+                    best_move: cached.as_ref().unwrap().best_move.clone(),
+                    search_details: cached,
+                }),
                 None => search::search(
                     node,
                     SearchParameters {
-                        table: transpositions,
-                        end_signal: search_end.clone(),
                         max_depth,
+                        // Returning early only makes sense to save time on the clock
+                        return_easy_moves: soft_end.is_some(),
+                        soft_end,
+                        confirm_unstable_best_move: true,
+                        aspiration_windows: true,
+                        max_nodes,
+                        verify_null_move,
+                        on_info,
+                        threads: search_threads,
+                        contempt,
+                        ..SearchParameters::new(search_end.clone(), transpositions)
                     },
                 )
+                .inspect(|outcome| {
+                    searches.fetch_add(1, SeqCst);
+                    if let Some((cache, depth)) = outcomes.as_ref()
+                        && outcome.depth == *depth
+                    {
+                        cache.lock().unwrap().insert(key, *depth, outcome.clone());
+                    }
+                })
                 .map(|outcome| ComputeMoveOutput {
                     best_move: outcome.best_move.clone(),
                     search_details: Some(outcome),
                 }),
             };
             if wait_for_end {
+                if let Some(ponder) = ponder.as_ref() {
+                    ponder.finish();
+                }
                 // Wait until the search is meant to end, i.e. in case we have forced ending
                 // and an infinite search has been requested.
                 search_end.join();
             }
+            // Clear the signal before the engine is available so a later hit cannot see
+            // it, no other ponder search can have started in the meantime
+            if ponder.is_some() {
+                ponder_slot.lock().unwrap().take();
+            }
             // Make sure the engine is available again
             available.store(true, SeqCst);
             on_complete(output);
         });
         true
     }
+
+    /// Start searching the position we expect after the predicted reply of our
+    /// opponent. The search runs until the end signal of the input fires or until
+    /// the time passed to [Engine::ponder_hit] is up, the move is only returned then.
+    pub fn start_ponder<E, F>(&self, input: ComputeMoveInput<E>, on_complete: F) -> bool
+    where
+        E: SearchEndSignal + Clone + Send + Sync + 'static,
+        F: FnOnce(Result<ComputeMoveOutput>) + Send + 'static,
+    {
+        let ponder = PonderEndSignal::default();
+        let input = ComputeMoveInput {
+            position: input.position,
+            search_end: CompositeEndSignal::new(vec![
+                Arc::new(input.search_end),
+                Arc::new(ponder.clone()),
+            ]),
+            max_depth: input.max_depth,
+            max_nodes: input.max_nodes,
+            // The time available is only known at a ponder hit
+            soft_deadline: None,
+            wait_for_end: true,
+            on_info: input.on_info,
+        };
+        // Hold the slot while starting so a hit cannot miss the signal of the new search
+        let mut slot = self.ponder.lock().unwrap();
+        let started = self.compute_move_with_ponder(input, Some(ponder.clone()), on_complete);
+        if started {
+            *slot = Some(ponder);
+        }
+        started
+    }
+
+    /// The opponent played the predicted move so convert the running ponder search
+    /// into one which ends after the given search time. This returns false if there
+    /// is no ponder search running.
+    pub fn ponder_hit(&self, search_time: Duration) -> bool {
+        let ponder = self.ponder.lock().unwrap().take();
+        !self.available.load(SeqCst) && ponder.is_some_and(|p| p.hit(search_time))
+    }
+
+    /// The book moves with their frequencies for the given position taken from the first
+    /// lookup service which has any, empty if the position is out of book.
+    pub fn book_candidates(&self, position: &Position) -> Vec<OpeningMoveRecord> {
+        for service in self.lookups.iter() {
+            match service.candidates(position) {
+                Ok(candidates) if !candidates.is_empty() => return candidates,
+                Err(e) => log::warn!("Failed to fetch book candidates for {}: {}", position, e),
+                _ => {}
+            }
+        }
+        vec![]
+    }
 }
 
 fn perform_lookups(
@@ -238,6 +621,334 @@ fn perform_lookups(
     None
 }
 
+fn soft_end(deadline: Option<Instant>) -> Option<Arc<dyn SearchEndSignal + Send + Sync>> {
+    deadline.map(|d| Arc::new(d) as Arc<dyn SearchEndSignal + Send + Sync>)
+}
+
+fn random_move(position: &Position) -> Result<Move> {
+    let moves = position.moves(&Moves::All);
+    if moves.is_empty() {
+        Err(anyhow!("No moves for position {}", position))
+    } else {
+        Ok(moves[rand::random::<u64>() as usize % moves.len()].clone())
+    }
+}
+
+#[cfg(test)]
+mod engine_test {
+    use crate::moves::{Move, Moves};
+    use crate::openings::{OpeningMoveFetcher, OpeningMoveRecord, OpeningService, book_key};
+    use crate::position::Position;
+    use crate::search::ConcurrentTT;
+    use crate::search::end::EmptyEndSignal;
+    use crate::{
+        ComputeMoveInput, ComputeMoveInputBuilder, Engine, FallbackMode, LookupMoveService,
+    };
+    use anyhow::Result;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    const TABLE_SIZE: usize = 100_000;
+    const DEPTH: u8 = 4;
+    const FEN: &str = "r2q1rk1/pp2bppp/2n1pn2/3p4/3P4/2NBPN2/PP3PPP/R2QK2R w KQ - 0 10";
+
+    /// Book which only knows the first move from the starting position
+    struct SingleMoveBook;
+
+    impl LookupMoveService for SingleMoveBook {
+        fn lookup(&self, position: Position) -> Result<Option<Move>> {
+            Ok(if position == Position::default() {
+                Some(position.clone().play("e2e4")?.remove(0))
+            } else {
+                None
+            })
+        }
+    }
+
+    fn search_nodes(engine: &Engine) -> u64 {
+        let input = ComputeMoveInputBuilder::fixed_depth(engine.position(), DEPTH).build();
+        engine.compute_move(input).unwrap().search_details.unwrap().nodes
+    }
+
+    #[test]
+    fn builder_matches_manual_construction() {
+        let search_end = Instant::now() + Duration::from_secs(1);
+        let manual = ComputeMoveInput {
+            position: Position::default(),
+            search_end,
+            max_depth: Some(DEPTH),
+            max_nodes: None,
+            soft_deadline: None,
+            wait_for_end: true,
+            on_info: None,
+        };
+        let built = ComputeMoveInput::builder(Position::default(), search_end)
+            .max_depth(DEPTH)
+            .wait_for_end(true)
+            .build();
+        assert_eq!(manual, built);
+    }
+
+    #[test]
+    fn fixed_depth_builder() {
+        let manual = ComputeMoveInput {
+            position: Position::default(),
+            search_end: EmptyEndSignal,
+            max_depth: Some(DEPTH),
+            max_nodes: None,
+            soft_deadline: None,
+            wait_for_end: false,
+            on_info: None,
+        };
+        assert_eq!(
+            manual,
+            ComputeMoveInputBuilder::fixed_depth(Position::default(), DEPTH).build()
+        );
+    }
+
+    #[test]
+    fn zero_size_table_still_searches() {
+        let engine = Engine::new(0, vec![]);
+        engine.set_position(FEN.parse().unwrap());
+        let input = ComputeMoveInputBuilder::fixed_depth(engine.position(), DEPTH).build();
+        let output = engine.compute_move(input).unwrap();
+        assert!(engine.position().moves(&Moves::All).contains(&output.best_move));
+    }
+
+    #[test]
+    fn book_only_fallbacks() {
+        for fallback in [FallbackMode::RandomMove, FallbackMode::MaxDepth(1)] {
+            let engine =
+                Engine::new(TABLE_SIZE, vec![Arc::new(SingleMoveBook)]).with_fallback(fallback);
+            let in_book = ComputeMoveInputBuilder::fixed_depth(Position::default(), DEPTH).build();
+            let output = engine.compute_move(in_book).unwrap();
+            assert_eq!("e2e4", output.best_move.to_string());
+            assert_eq!(None, output.search_details);
+
+            let position: Position = FEN.parse().unwrap();
+            let out_of_book = ComputeMoveInputBuilder::fixed_depth(position.clone(), DEPTH).build();
+            let output = engine.compute_move(out_of_book).unwrap();
+            assert!(position.moves(&Moves::All).contains(&output.best_move));
+            match fallback {
+                FallbackMode::RandomMove => assert_eq!(None, output.search_details),
+                _ => assert_eq!(1, output.search_details.unwrap().depth),
+            }
+        }
+    }
+
+    #[test]
+    fn resize_table_only_when_available() {
+        let engine = Engine::new(TABLE_SIZE, vec![]);
+        assert!(engine.resize_table(1000));
+        let (tx, rx) = std::sync::mpsc::channel();
+        let input = ComputeMoveInput::builder(
+            Position::default(),
+            Instant::now() + Duration::from_millis(200),
+        )
+        .max_depth(1)
+        .wait_for_end(true)
+        .build();
+        assert!(engine.compute_move_async(input, move |r| tx.send(r).unwrap()));
+        assert!(!engine.resize_table(TABLE_SIZE));
+        assert!(rx.recv().unwrap().is_ok());
+        assert!(engine.resize_table(TABLE_SIZE));
+    }
+
+    #[test]
+    fn repeated_query_served_from_outcome_cache() {
+        let engine = Engine::new(TABLE_SIZE, vec![]).with_outcome_cache(4);
+        let position: Position = FEN.parse().unwrap();
+        let query = |depth| ComputeMoveInputBuilder::fixed_depth(position.clone(), depth).build();
+        let first = engine.compute_move(query(DEPTH)).unwrap();
+        assert_eq!(1, engine.searches());
+        let second = engine.compute_move(query(DEPTH)).unwrap();
+        assert_eq!(1, engine.searches());
+        assert_eq!(first, second);
+        // A different depth is a different query
+        engine.compute_move(query(2)).unwrap();
+        assert_eq!(2, engine.searches());
+        // Reset clears the cache
+        assert!(engine.reset());
+        engine.compute_move(query(DEPTH)).unwrap();
+        assert_eq!(3, engine.searches());
+    }
+
+    #[test]
+    fn null_move_verified_in_endgame() {
+        // See verified_null_move_handles_zugzwang in the best move tests
+        let position: Position = "7b/6p1/1k2K1P1/p3P3/P7/6p1/6P1/7B w - - 0 1".parse().unwrap();
+        let engine = Engine::new(TABLE_SIZE, vec![]);
+        let input = ComputeMoveInputBuilder::fixed_depth(position, 8).build();
+        assert_eq!("e6d6", engine.compute_move(input).unwrap().best_move.to_string());
+    }
+
+    #[test]
+    fn ponder_hit_ends_search_within_budget() {
+        let engine = Engine::new(TABLE_SIZE, vec![]);
+        let position: Position = FEN.parse().unwrap();
+        assert!(!engine.ponder_hit(Duration::from_millis(100)));
+        let (tx, rx) = std::sync::mpsc::channel();
+        let input = ComputeMoveInput::builder(position.clone(), EmptyEndSignal).build();
+        assert!(engine.start_ponder(input, move |r| tx.send(r).unwrap()));
+        // Pondering continues indefinitely until the hit
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(rx.try_recv().is_err());
+        let hit = Instant::now();
+        assert!(engine.ponder_hit(Duration::from_millis(200)));
+        assert!(!engine.ponder_hit(Duration::from_millis(200)));
+        let output = rx.recv_timeout(Duration::from_secs(1)).unwrap().unwrap();
+        assert!(hit.elapsed() < Duration::from_millis(500), "{:?}", hit.elapsed());
+        assert!(position.moves(&Moves::All).contains(&output.best_move));
+        assert!(!engine.ponder_hit(Duration::from_millis(100)));
+    }
+
+    #[test]
+    fn ponder_hit_after_forced_mate_ends_at_once() {
+        let engine = Engine::new(TABLE_SIZE, vec![]);
+        let position: Position = "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1".parse().unwrap();
+        let (tx, rx) = std::sync::mpsc::channel();
+        let input = ComputeMoveInput::builder(position.clone(), EmptyEndSignal).build();
+        assert!(engine.start_ponder(input, move |r| tx.send(r).unwrap()));
+        // The search finds the mate quickly, it then only waits for the hit
+        let ponder = engine.ponder.lock().unwrap().clone().unwrap();
+        let deadline = Instant::now() + Duration::from_secs(30);
+        while !ponder.is_finished() {
+            assert!(Instant::now() < deadline, "Ponder search did not finish");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(rx.try_recv().is_err());
+        assert!(engine.ponder_hit(Duration::from_secs(30)));
+        let output = rx.recv_timeout(Duration::from_secs(1)).unwrap().unwrap();
+        assert_eq!("d1d8", output.best_move.to_string());
+    }
+
+    #[test]
+    fn ponder_signal_cleared_when_search_ends_without_hit() {
+        let engine = Engine::new(TABLE_SIZE, vec![]);
+        let position: Position = FEN.parse().unwrap();
+        let (tx, rx) = std::sync::mpsc::channel();
+        let end = Instant::now() + Duration::from_millis(100);
+        let input = ComputeMoveInput::builder(position.clone(), end).build();
+        assert!(engine.start_ponder(input, move |r| tx.send(r).unwrap()));
+        rx.recv_timeout(Duration::from_secs(5)).unwrap().unwrap();
+        assert!(engine.ponder.lock().unwrap().is_none());
+        assert!(!engine.ponder_hit(Duration::from_millis(100)));
+    }
+
+    struct StartBook;
+
+    impl OpeningMoveFetcher for StartBook {
+        fn lookup(&self, position_key: &str) -> Result<Vec<OpeningMoveRecord>> {
+            if position_key == book_key(&Position::default().to_string()) {
+                Ok(vec!["e2e4:30".parse()?, "d2d4:20".parse()?, "c2c4:5".parse()?])
+            } else {
+                Ok(vec![])
+            }
+        }
+    }
+
+    #[test]
+    fn book_candidates_listed_in_full() {
+        let engine = Engine::new(TABLE_SIZE, vec![Arc::new(OpeningService::new(StartBook))]);
+        let candidates = engine
+            .book_candidates(&Position::default())
+            .into_iter()
+            .map(|r| (r.mv, r.freq))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![("e2e4".to_string(), 30), ("d2d4".to_string(), 20), ("c2c4".to_string(), 5)],
+            candidates
+        );
+        let out_of_book: Position = "1. e4 e5".parse().unwrap();
+        assert!(engine.book_candidates(&out_of_book).is_empty());
+    }
+
+    #[test]
+    fn easy_move_searched_to_requested_depth() {
+        // The black queen is hanging to the knight, a clock search would return early
+        let fen = "r1b1kb1r/pppp1ppp/2n5/4p3/3qP3/2N2N2/PPP2PPP/R1BQKB1R w KQkq - 0 6";
+        let engine = Engine::new(TABLE_SIZE, vec![]);
+        let input = ComputeMoveInputBuilder::fixed_depth(fen.parse().unwrap(), 6).build();
+        let outcome = engine.compute_move(input).unwrap().search_details.unwrap();
+        assert_eq!("f3d4", outcome.best_move.to_string());
+        assert_eq!(6, outcome.depth);
+    }
+
+    #[test]
+    fn analyse_searches_book_positions() {
+        let engine = Engine::new(TABLE_SIZE, vec![Arc::new(SingleMoveBook)]);
+        let input = ComputeMoveInputBuilder::fixed_depth(Position::default(), DEPTH).build();
+        assert_eq!(None, engine.compute_move(input.clone()).unwrap().search_details);
+        let outcome = engine.analyse(input).unwrap();
+        assert_eq!(DEPTH, outcome.depth);
+        assert!(outcome.nodes > 0);
+        assert!(Position::default().moves(&Moves::All).contains(&outcome.best_move));
+        assert_eq!(1, engine.searches());
+    }
+
+    #[test]
+    fn engines_sharing_table_search_concurrently() {
+        let table = Arc::new(ConcurrentTT::new(TABLE_SIZE));
+        let engines =
+            [Engine::with_table(table.clone(), vec![]), Engine::with_table(table.clone(), vec![])];
+        assert!(engines.iter().all(|e| Arc::ptr_eq(&table, &e.table())));
+        let positions = [FEN.parse::<Position>().unwrap(), Position::default()];
+        let (tx, rx) = std::sync::mpsc::channel();
+        for (engine, position) in engines.iter().zip(positions.iter()) {
+            let tx = tx.clone();
+            let input = ComputeMoveInputBuilder::fixed_depth(position.clone(), DEPTH).build();
+            assert!(engine.compute_move_async(input, move |r| tx.send(r).unwrap()));
+        }
+        for _ in 0..2 {
+            let output = rx.recv_timeout(Duration::from_secs(30)).unwrap().unwrap();
+            assert!(output.search_details.is_some());
+            assert!(positions.iter().any(|p| p.moves(&Moves::All).contains(&output.best_move)));
+        }
+    }
+
+    #[test]
+    fn advance_rejects_illegal_move() {
+        let engine = Engine::new(TABLE_SIZE, vec![]);
+        let first = Position::default().moves(&Moves::All)[0].clone();
+        engine.advance(first.clone()).unwrap();
+        // The same move is no longer legal for the other side
+        assert!(engine.advance(first).is_err());
+        assert_eq!(1, engine.position().history.len());
+    }
+
+    #[test]
+    fn advance_reuses_table_entries() {
+        let first_search = |engine: &Engine| {
+            engine.set_position(FEN.parse().unwrap());
+            let input = ComputeMoveInputBuilder::fixed_depth(engine.position(), DEPTH).build();
+            engine.compute_move(input).unwrap().search_details.unwrap().optimal_path
+        };
+        let advanced = Engine::new(TABLE_SIZE, vec![]);
+        let path = first_search(&advanced);
+        for m in path.iter().take(2) {
+            advanced.advance(m.clone()).unwrap();
+        }
+
+        // Rebuilding the position from the move list after the same search
+        let rebuilt = Engine::new(TABLE_SIZE, vec![]);
+        assert_eq!(path, first_search(&rebuilt));
+        let mut reconstructed: Position = FEN.parse().unwrap();
+        for m in path.iter().take(2) {
+            reconstructed.make(m.clone()).unwrap();
+        }
+        rebuilt.set_position(reconstructed.clone());
+
+        let cold = Engine::new(TABLE_SIZE, vec![]);
+        cold.set_position(reconstructed);
+
+        assert_eq!(advanced.position().key, rebuilt.position().key);
+        assert_eq!(advanced.position().history, rebuilt.position().history);
+        let advanced_nodes = search_nodes(&advanced);
+        assert_eq!(advanced_nodes, search_nodes(&rebuilt));
+        assert!(advanced_nodes < search_nodes(&cold));
+    }
+}
+
 #[cfg(test)]
 mod macro_test {
     use crate::constants::lift;
@@ -1,5 +1,7 @@
-use crate::constants::{class, piece_class};
-use crate::{Corner, Piece, Side, Square};
+use crate::constants::{
+    class, piece_class, reflect_corner, reflect_piece, reflect_side, reflect_square,
+};
+use crate::{Corner, Piece, Side, Square, Symmetric};
 use Move::*;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
@@ -7,10 +9,45 @@ pub enum Move {
     Normal { moving: Piece, from: Square, dest: Square, capture: Option<Piece> },
     Enpassant { side: Side, from: Square, dest: Square, capture: Square },
     Promote { from: Square, dest: Square, promoted: Piece, capture: Option<Piece> },
-    Castle { corner: Corner },
+    /// The king and rook start squares are only fixed in standard chess, in Chess960
+    /// they can be on any file with the king between the two rooks.
+    Castle { corner: Corner, king: Square, rook: Square },
     Null,
 }
 
+impl Symmetric for Move {
+    fn reflect(&self) -> Self {
+        use crate::moves::Move::*;
+
+        match self {
+            Null => Null,
+            Castle { corner, king, rook } => Castle {
+                corner: reflect_corner(*corner),
+                king: reflect_square(*king),
+                rook: reflect_square(*rook),
+            },
+            Normal { moving, from, dest, capture } => Normal {
+                moving: reflect_piece(*moving),
+                from: reflect_square(*from),
+                dest: reflect_square(*dest),
+                capture: capture.map(reflect_piece),
+            },
+            Enpassant { side, from, dest, capture } => Enpassant {
+                side: reflect_side(*side),
+                from: reflect_square(*from),
+                dest: reflect_square(*dest),
+                capture: reflect_square(*capture),
+            },
+            Promote { from, dest, promoted, capture } => Promote {
+                from: reflect_square(*from),
+                dest: reflect_square(*dest),
+                promoted: reflect_piece(*promoted),
+                capture: capture.map(reflect_piece),
+            },
+        }
+    }
+}
+
 impl Move {
     pub fn is_repeatable(&self) -> bool {
         match self {
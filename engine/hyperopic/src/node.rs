@@ -3,7 +3,8 @@ use crate::position::{Position, TerminalState};
 
 use crate::eval::material::{MaterialFacet, PieceValues};
 use crate::eval::{
-    CastlingFacet, PawnStructureFacet, PieceSquareTablesFacet, SafetyFacet, SpaceFacet,
+    BishopPairFacet, CastlingFacet, PawnStructureFacet, PieceSquareTablesFacet, SCALE_NORMAL,
+    SafetyFacet, SpaceFacet, draw_scale,
 };
 use crate::moves::Move;
 use crate::phase::Phase;
@@ -70,14 +71,15 @@ impl TreeNode {
         self.facets.push(facet);
     }
 
-    /// Make the given move on the underlying board and update all the internal facets
+    /// Make the given move on the underlying board and update all the internal facets,
+    /// unlike [Position::make] the move is assumed to be pseudo-legal and is not checked
     pub fn make(&mut self, action: Move) -> Result<()> {
         self.material.make(&action, &self.position);
         self.phase.make(&action);
         for cmp in self.facets.iter_mut() {
             cmp.make(&action, &self.position);
         }
-        self.position.make(action)
+        self.position.make_unchecked(action)
     }
 
     /// Unmake the given move on the underlying board and update all the internal facets
@@ -112,7 +114,8 @@ impl TreeNode {
                     .iter()
                     .map(|facet| self.phase.unwrap(facet.static_eval(&self.position)))
                     .sum::<i32>();
-                parity * (material + facets)
+                let scale = draw_scale(&self.position);
+                parity * (material + facets) * scale / SCALE_NORMAL
             }
         }
     }
@@ -157,6 +160,7 @@ impl From<Position> for TreeNode {
                     Box::new(PawnStructureFacet::default()),
                     Box::new(SafetyFacet::default()),
                     Box::new(SpaceFacet::default()),
+                    Box::new(BishopPairFacet::default()),
                 ],
             };
             moves.into_iter().rev().for_each(|m| eval.make(m).unwrap());
@@ -170,6 +174,7 @@ impl From<Position> for TreeNode {
                     Box::new(PawnStructureFacet::default()),
                     Box::new(SafetyFacet::default()),
                     Box::new(SpaceFacet::default()),
+                    Box::new(BishopPairFacet::from(&board)),
                 ],
                 position: board,
             }
@@ -3,44 +3,274 @@ use crate::moves::Move;
 use crate::position::Position;
 use anyhow::{Error, Result, anyhow};
 use itertools::Itertools;
+use std::cmp::{Reverse, max, min};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+mod polyglot;
+
+pub use polyglot::{POLYGLOT_KEY_COUNT, PolyglotBook, PolyglotKeys};
 
 const MOVE_FREQ_SEPARATOR: &'static str = ":";
+/// The number of most recent book lines which later games try to avoid
+const RECENT_LINES: usize = 10;
+/// Scales score rates in [0, 1] to the integer weights moves are chosen by
+const SCORE_WEIGHT_SCALE: f64 = 1000.0;
 
+/// A book move parsed from "move:freq" optionally followed by ":wins:draws:losses"
 #[derive(Debug, Clone, PartialOrd, PartialEq)]
 pub struct OpeningMoveRecord {
-    mv: String,
-    freq: u64,
+    pub mv: String,
+    pub freq: u64,
+    pub results: Option<MoveResults>,
+}
+
+/// The results of the book games in which a move was played, counted for the side
+/// which played it.
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq, Eq)]
+pub struct MoveResults {
+    pub wins: u64,
+    pub draws: u64,
+    pub losses: u64,
+}
+
+impl MoveResults {
+    /// The fraction of the available points scored with a draw worth half a win, a move
+    /// with no games is assumed to score evenly
+    pub fn score_rate(&self) -> f64 {
+        let games = self.wins + self.draws + self.losses;
+        if games == 0 { 0.5 } else { (self.wins as f64 + self.draws as f64 / 2.0) / games as f64 }
+    }
+}
+
+impl OpeningMoveRecord {
+    /// The weight of this move when choosing by score, never zero so a move is only
+    /// ever made unlikely rather than impossible
+    fn score_weight(&self) -> u64 {
+        let rate = self.results.as_ref().map_or(0.5, |r| r.score_rate());
+        ((SCORE_WEIGHT_SCALE * rate).round() as u64).max(1)
+    }
 }
 
 pub trait OpeningMoveFetcher {
     fn lookup(&self, position_key: &str) -> Result<Vec<OpeningMoveRecord>>;
 }
 
+/// Normalise a FEN to the key the book is indexed by, the pieces, active side and
+/// castling rights. This is independent of the move order so transpositions share an
+/// entry, the en passant square is left out as it is set after every double pawn push
+/// whether or not a capture is possible and so would split transposed positions.
+pub fn book_key(fen: &str) -> String {
+    fen.split_whitespace().take(3).join(" ")
+}
+
+/// Cached lookups by book key paired with the keys in the order they were cached
+type FetchCache = (HashMap<String, Vec<OpeningMoveRecord>>, VecDeque<String>);
+
+/// Serves repeated lookups of a position from memory so a remote book is only queried
+/// once for it. Once capacity positions are held the oldest is dropped to make room,
+/// failed lookups are not cached.
+pub struct CachedFetcher<F: OpeningMoveFetcher> {
+    fetcher: F,
+    capacity: usize,
+    cache: Mutex<FetchCache>,
+}
+
+impl<F: OpeningMoveFetcher> CachedFetcher<F> {
+    pub fn new(fetcher: F, capacity: usize) -> Self {
+        CachedFetcher { fetcher, capacity, cache: Mutex::new((HashMap::new(), VecDeque::new())) }
+    }
+
+    /// Drop every cached position, e.g. between games
+    pub fn clear(&self) {
+        let mut cache = self.cache.lock().unwrap();
+        cache.0.clear();
+        cache.1.clear();
+    }
+}
+
+impl<F: OpeningMoveFetcher> OpeningMoveFetcher for CachedFetcher<F> {
+    fn lookup(&self, position_key: &str) -> Result<Vec<OpeningMoveRecord>> {
+        let key = book_key(position_key);
+        if let Some(records) = self.cache.lock().unwrap().0.get(&key) {
+            return Ok(records.clone());
+        }
+        let records = self.fetcher.lookup(position_key)?;
+        let (entries, order) = &mut *self.cache.lock().unwrap();
+        if self.capacity > 0 && !entries.contains_key(&key) {
+            while entries.len() >= self.capacity {
+                order.pop_front().map(|oldest| entries.remove(&oldest));
+            }
+            order.push_back(key.clone());
+            entries.insert(key, records.clone());
+        }
+        Ok(records)
+    }
+}
+
+/// The unit the maximum depth of an opening book lookup is measured in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum DepthUnit {
+    /// Half moves, i.e. a single move by one side
+    #[default]
+    Plies,
+    /// Full moves, i.e. a move by white followed by a move by black
+    Moves,
+}
+
+impl DepthUnit {
+    /// Convert a depth measured in this unit to a number of plies
+    pub fn to_plies(&self, depth: usize) -> usize {
+        match self {
+            DepthUnit::Plies => depth,
+            DepthUnit::Moves => 2 * depth,
+        }
+    }
+}
+
+impl FromStr for DepthUnit {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "plies" => Ok(DepthUnit::Plies),
+            "moves" => Ok(DepthUnit::Moves),
+            _ => Err(anyhow!("Cannot parse depth unit from {}", s)),
+        }
+    }
+}
+
+/// How a move is chosen from the options the book has for a position.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum SelectionStrategy {
+    /// Random with each move chosen in proportion to its frequency
+    #[default]
+    Weighted,
+    /// Always the most frequent move
+    MostFrequent,
+    /// Uniformly random among the given number of most frequent moves
+    TopK(usize),
+    /// Random with each move chosen in proportion to the score it achieved in the book
+    /// games, moves without results are assumed to score evenly
+    WinRate,
+}
+
+/// The exit position of a book line paired with the book positions moved into along it
+type BookLine = (String, HashSet<String>);
+
+/// The book lines played in recent games, shared between the opening services of
+/// successive games so each can prefer lines the others have not played. A line is
+/// identified by the position the game left the book in.
+#[derive(Debug, Clone, Default)]
+pub struct OpeningHistory {
+    lines: Arc<Mutex<VecDeque<BookLine>>>,
+}
+
+impl OpeningHistory {
+    /// Create the hint for a new game which records its line into this history
+    pub fn hint(&self) -> DiversityHint {
+        DiversityHint { history: self.clone(), line: Mutex::new(HashSet::new()) }
+    }
+
+    fn record(&self, exit: String, line: HashSet<String>) {
+        let mut lines = self.lines.lock().unwrap();
+        lines.retain(|(e, _)| e != &exit);
+        lines.push_back((exit, line));
+        while lines.len() > RECENT_LINES {
+            lines.pop_front();
+        }
+    }
+
+    /// Whether moving into the position with the given key would complete a recent line,
+    /// which is when the game has already moved into every other position of that line
+    fn completes_line(&self, key: &str, followed: &HashSet<String>) -> bool {
+        self.lines.lock().unwrap().iter().any(|(_, line)| {
+            line.contains(key) && line.iter().all(|p| p == key || followed.contains(p))
+        })
+    }
+}
+
+/// Steers the book moves of a single game away from recently played lines, a move which
+/// would repeat the whole of such a line is avoided unless the book has nothing else.
+#[derive(Debug)]
+pub struct DiversityHint {
+    history: OpeningHistory,
+    /// The book positions moved into so far in this game
+    line: Mutex<HashSet<String>>,
+}
+
+impl DiversityHint {
+    fn prefer_unplayed(
+        &self,
+        position: &Position,
+        options: Vec<OpeningMoveRecord>,
+    ) -> Vec<OpeningMoveRecord> {
+        let followed = self.line.lock().unwrap().clone();
+        let unplayed = options
+            .iter()
+            .filter(|r| {
+                let mut next = position.clone();
+                next.play(&r.mv).is_ok_and(|_| {
+                    !self.history.completes_line(&book_key(&next.to_string()), &followed)
+                })
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        if unplayed.is_empty() { options } else { unplayed }
+    }
+
+    fn played(&self, position: &Position, m: &Move) {
+        let mut next = position.clone();
+        if next.make(m.clone()).is_ok() {
+            self.line.lock().unwrap().insert(book_key(&next.to_string()));
+        }
+    }
+
+    fn left_book(&self, position: &Position) {
+        let line = std::mem::take(&mut *self.line.lock().unwrap());
+        if !line.is_empty() {
+            self.history.record(book_key(&position.to_string()), line);
+        }
+    }
+}
+
 pub struct OpeningService<F: OpeningMoveFetcher> {
     pub fetcher: F,
+    /// The book is not consulted for positions deeper than this into the game
     pub max_depth: usize,
+    /// The unit max_depth is measured in
+    pub depth_unit: DepthUnit,
+    /// How the move is chosen from the book options
+    pub selection: SelectionStrategy,
+    /// If present book lines played in recent games are avoided
+    pub diversity: Option<DiversityHint>,
 }
 
 impl<F: OpeningMoveFetcher> OpeningService<F> {
     pub fn new(fetcher: F) -> Self {
-        OpeningService { fetcher, max_depth: 10 }
+        OpeningService {
+            fetcher,
+            max_depth: 10,
+            depth_unit: DepthUnit::Plies,
+            selection: SelectionStrategy::Weighted,
+            diversity: None,
+        }
     }
-}
 
-impl<F: OpeningMoveFetcher> LookupMoveService for OpeningService<F> {
-    fn lookup(&self, position: Position) -> Result<Option<Move>> {
+    fn choose(&self, position: &Position) -> Result<Option<Move>> {
         let pos_count = position.history.len();
-        if pos_count > self.max_depth {
+        if pos_count > self.depth_unit.to_plies(self.max_depth) {
             Ok(None)
         } else {
-            // The table index comprises, the pieces, active square, castling rights
-            let key = position.to_string().split_whitespace().take(3).join(" ");
-            let options = self.fetcher.lookup(&key)?;
+            let mut options = self.fetcher.lookup(&book_key(&position.to_string()))?;
             if options.len() == 0 {
                 return Ok(None);
             }
-            let chosen_move = choose_move(&options, rand::random)?;
+            if let Some(hint) = self.diversity.as_ref() {
+                options = hint.prefer_unplayed(position, options);
+            }
+            let chosen_move = select_move(self.selection, &options, rand::random)?;
             let parsed = position.clone().play(chosen_move.mv)?;
             let m = parsed.first().cloned().ok_or(anyhow!(
                 "{:?} not parsed on {}",
@@ -52,18 +282,77 @@ impl<F: OpeningMoveFetcher> LookupMoveService for OpeningService<F> {
     }
 }
 
+impl<F: OpeningMoveFetcher> LookupMoveService for OpeningService<F> {
+    fn lookup(&self, position: Position) -> Result<Option<Move>> {
+        let chosen = self.choose(&position);
+        if let Some(hint) = self.diversity.as_ref() {
+            match chosen.as_ref() {
+                Ok(Some(m)) => hint.played(&position, m),
+                _ => hint.left_book(&position),
+            }
+        }
+        chosen
+    }
+
+    fn candidates(&self, position: &Position) -> Result<Vec<OpeningMoveRecord>> {
+        self.fetcher.lookup(&book_key(&position.to_string()))
+    }
+}
+
 impl FromStr for OpeningMoveRecord {
     type Err = Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let split = s.split(MOVE_FREQ_SEPARATOR).map(|s| s.to_string()).collect::<Vec<_>>();
+        let results = match &split[min(2, split.len())..] {
+            [] => None,
+            [wins, draws, losses] => Some(MoveResults {
+                wins: wins.parse()?,
+                draws: draws.parse()?,
+                losses: losses.parse()?,
+            }),
+            _ => return Err(anyhow!("Cannot parse results from {}", s)),
+        };
         Ok(OpeningMoveRecord {
             mv: split.get(0).ok_or(anyhow!("Cannot parse move from {}", s))?.clone(),
             freq: split.get(1).ok_or(anyhow!("Cannot parse freq from {}", s))?.parse()?,
+            results,
         })
     }
 }
 
+fn select_move(
+    strategy: SelectionStrategy,
+    available: &Vec<OpeningMoveRecord>,
+    f: impl Fn() -> u64,
+) -> Result<OpeningMoveRecord> {
+    let most_frequent_first =
+        available.iter().sorted_by_key(|r| Reverse(r.freq)).collect::<Vec<_>>();
+    match strategy {
+        SelectionStrategy::Weighted => choose_move(available, f),
+        SelectionStrategy::MostFrequent => most_frequent_first
+            .first()
+            .map(|&r| r.clone())
+            .ok_or(anyhow!("No moves to choose from")),
+        SelectionStrategy::TopK(k) => {
+            let top = &most_frequent_first[..min(max(k, 1), most_frequent_first.len())];
+            if top.is_empty() {
+                Err(anyhow!("No moves to choose from"))
+            } else {
+                Ok(top[(f() % top.len() as u64) as usize].clone())
+            }
+        }
+        SelectionStrategy::WinRate => {
+            let by_score = available
+                .iter()
+                .map(|r| OpeningMoveRecord { freq: r.score_weight(), ..r.clone() })
+                .collect::<Vec<_>>();
+            let chosen = choose_move(&by_score, f)?;
+            Ok(available.iter().find(|r| r.mv == chosen.mv).cloned().unwrap_or(chosen))
+        }
+    }
+}
+
 fn choose_move(
     available: &Vec<OpeningMoveRecord>,
     f: impl Fn() -> u64,
@@ -89,7 +378,15 @@ fn choose_move(
 
 #[cfg(test)]
 mod test {
-    use super::{OpeningMoveRecord, choose_move};
+    use super::{
+        CachedFetcher, DepthUnit, MoveResults, OpeningHistory, OpeningMoveFetcher,
+        OpeningMoveRecord, OpeningService, SelectionStrategy, book_key, choose_move, select_move,
+    };
+    use crate::LookupMoveService;
+    use crate::position::Position;
+    use anyhow::Result;
+    use std::cell::Cell;
+    use std::collections::HashMap;
 
     fn mv(input: &str) -> OpeningMoveRecord {
         input.parse().unwrap()
@@ -112,4 +409,219 @@ mod test {
 
         assert_eq!(mv("a2a3:1"), choose_move(&choices, || { 25 }).unwrap());
     }
+
+    #[test]
+    fn test_select_weighted() {
+        let choices = vec![mv("a2a3:1"), mv("b2b4:1"), mv("g8f6:3"), mv("e1g1:20")];
+        let strategy = SelectionStrategy::default();
+        for i in 0..25 {
+            assert_eq!(
+                choose_move(&choices, || { i }).unwrap(),
+                select_move(strategy, &choices, || { i }).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_select_most_frequent() {
+        let choices = vec![mv("a2a3:1"), mv("b2b4:1"), mv("g8f6:3"), mv("e1g1:20")];
+        for i in 0..25 {
+            assert_eq!(
+                mv("e1g1:20"),
+                select_move(SelectionStrategy::MostFrequent, &choices, || { i }).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_select_top_k() {
+        let choices = vec![mv("a2a3:1"), mv("b2b4:1"), mv("g8f6:3"), mv("e1g1:20")];
+        let top_two = SelectionStrategy::TopK(2);
+        assert_eq!(mv("e1g1:20"), select_move(top_two, &choices, || { 0 }).unwrap());
+        assert_eq!(mv("g8f6:3"), select_move(top_two, &choices, || { 1 }).unwrap());
+        assert_eq!(mv("e1g1:20"), select_move(top_two, &choices, || { 2 }).unwrap());
+
+        let top_all = SelectionStrategy::TopK(10);
+        assert_eq!(mv("a2a3:1"), select_move(top_all, &choices, || { 2 }).unwrap());
+        assert_eq!(mv("b2b4:1"), select_move(top_all, &choices, || { 3 }).unwrap());
+
+        let top_none = SelectionStrategy::TopK(0);
+        assert_eq!(mv("e1g1:20"), select_move(top_none, &choices, || { 3 }).unwrap());
+        assert!(select_move(top_two, &vec![], || { 0 }).is_err());
+    }
+
+    #[test]
+    fn test_parse_results() {
+        let record = mv("e2e4:10:5:3:2");
+        assert_eq!(Some(MoveResults { wins: 5, draws: 3, losses: 2 }), record.results);
+        assert_eq!(0.65, record.results.unwrap().score_rate());
+        assert_eq!(None, mv("e2e4:10").results);
+        assert!("e2e4:10:5".parse::<OpeningMoveRecord>().is_err());
+    }
+
+    #[test]
+    fn test_select_win_rate() {
+        // The most played move mostly draws while the rarer one mostly wins
+        let choices = vec![mv("e2e4:1000:100:800:100"), mv("d2d4:10:8:1:1")];
+        let count_chosen = |strategy: SelectionStrategy, m: &str| {
+            (0..1350).filter(|&i| select_move(strategy, &choices, || i).unwrap().mv == m).count()
+        };
+        assert!(count_chosen(SelectionStrategy::Weighted, "e2e4") > 1300);
+        // Weighted by scores of 500 and 850 per mille
+        assert_eq!(500, count_chosen(SelectionStrategy::WinRate, "e2e4"));
+        assert_eq!(850, count_chosen(SelectionStrategy::WinRate, "d2d4"));
+        assert_eq!(
+            mv("d2d4:10:8:1:1"),
+            select_move(SelectionStrategy::WinRate, &choices, || { 1349 }).unwrap()
+        );
+    }
+
+    struct CountingFetcher {
+        calls: Cell<usize>,
+    }
+
+    impl OpeningMoveFetcher for CountingFetcher {
+        fn lookup(&self, _: &str) -> Result<Vec<OpeningMoveRecord>> {
+            self.calls.set(self.calls.get() + 1);
+            Ok(vec![])
+        }
+    }
+
+    fn is_consulted(service: &OpeningService<CountingFetcher>, plies: usize) -> bool {
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+        let moves = (0..plies).map(|i| shuffle[i % shuffle.len()]).collect::<Vec<_>>().join(" ");
+        let mut position = Position::default();
+        position.play(moves).unwrap();
+        let calls_before = service.fetcher.calls.get();
+        service.lookup(position).unwrap();
+        service.fetcher.calls.get() > calls_before
+    }
+
+    #[test]
+    fn test_full_move_depth_limit() {
+        let service = OpeningService {
+            fetcher: CountingFetcher { calls: Cell::new(0) },
+            max_depth: 5,
+            depth_unit: DepthUnit::Moves,
+            selection: SelectionStrategy::Weighted,
+            diversity: None,
+        };
+        assert!(is_consulted(&service, 0));
+        assert!(is_consulted(&service, 10));
+        assert!(!is_consulted(&service, 11));
+    }
+
+    #[test]
+    fn test_ply_depth_limit() {
+        let service = OpeningService {
+            fetcher: CountingFetcher { calls: Cell::new(0) },
+            max_depth: 5,
+            depth_unit: DepthUnit::Plies,
+            selection: SelectionStrategy::Weighted,
+            diversity: None,
+        };
+        assert!(is_consulted(&service, 5));
+        assert!(!is_consulted(&service, 6));
+    }
+
+    struct MapFetcher(HashMap<String, Vec<OpeningMoveRecord>>);
+
+    impl OpeningMoveFetcher for MapFetcher {
+        fn lookup(&self, position_key: &str) -> Result<Vec<OpeningMoveRecord>> {
+            Ok(self.0.get(position_key).cloned().unwrap_or_default())
+        }
+    }
+
+    #[test]
+    fn transpositions_share_book_entry() {
+        let main_line: Position = "1. e4 e5 2. Nf3".parse().unwrap();
+        let transposed: Position = "1. Nf3 e5 2. e4".parse().unwrap();
+        // The en passant square only differs between the two move orders
+        assert_ne!(main_line.to_string(), transposed.to_string());
+        assert_eq!(book_key(&main_line.to_string()), book_key(&transposed.to_string()));
+
+        let entry = (book_key(&main_line.to_string()), vec![mv("b8c6:1")]);
+        let service = OpeningService::new(MapFetcher(HashMap::from([entry])));
+        for position in [main_line, transposed] {
+            let m = service.lookup(position).unwrap().expect("Book move not found");
+            assert_eq!("b8c6", m.to_string());
+        }
+    }
+
+    #[test]
+    fn diversity_hint_avoids_recent_line() {
+        let history = OpeningHistory::default();
+        let start = book_key(&Position::default().to_string());
+        let play_game = || {
+            let fetcher =
+                MapFetcher(HashMap::from([(start.clone(), vec![mv("e2e4:1000"), mv("d2d4:1")])]));
+            let service = OpeningService {
+                selection: SelectionStrategy::MostFrequent,
+                diversity: Some(history.hint()),
+                ..OpeningService::new(fetcher)
+            };
+            let mut position = Position::default();
+            let m = service.lookup(position.clone()).unwrap().expect("Book move not found");
+            position.make(m.clone()).unwrap();
+            position.play("e7e5").unwrap();
+            assert_eq!(None, service.lookup(position).unwrap());
+            m.to_string()
+        };
+        assert_eq!("e2e4", play_game());
+        assert_eq!("d2d4", play_game());
+        // Once every option has been played recently the book choice applies again
+        assert_eq!("e2e4", play_game());
+    }
+
+    #[test]
+    fn diversity_hint_follows_recent_line_until_last_move() {
+        let history = OpeningHistory::default();
+        let start = book_key(&Position::default().to_string());
+        let after_e5 = book_key(&"1. e4 e5".parse::<Position>().unwrap().to_string());
+        let play_game = || {
+            let fetcher = MapFetcher(HashMap::from([
+                (start.clone(), vec![mv("e2e4:1000"), mv("d2d4:1")]),
+                (after_e5.clone(), vec![mv("g1f3:1000"), mv("f1c4:1")]),
+            ]));
+            let service = OpeningService {
+                selection: SelectionStrategy::MostFrequent,
+                diversity: Some(history.hint()),
+                ..OpeningService::new(fetcher)
+            };
+            let mut position = Position::default();
+            let mut moves = vec![];
+            for reply in ["e7e5", "b8c6"] {
+                let m = service.lookup(position.clone()).unwrap().expect("Book move not found");
+                position.make(m.clone()).unwrap();
+                position.play(reply).unwrap();
+                moves.push(m.to_string());
+            }
+            assert_eq!(None, service.lookup(position).unwrap());
+            moves
+        };
+        assert_eq!(vec!["e2e4", "g1f3"], play_game());
+        // Only the move completing the recent line is avoided
+        assert_eq!(vec!["e2e4", "f1c4"], play_game());
+    }
+
+    #[test]
+    fn cached_fetcher_queries_backend_once_per_position() {
+        let cached = CachedFetcher::new(CountingFetcher { calls: Cell::new(0) }, 1);
+        let start = Position::default().to_string();
+        for _ in 0..3 {
+            assert!(cached.lookup(&start).unwrap().is_empty());
+        }
+        assert_eq!(1, cached.fetcher.calls.get());
+
+        // A new position evicts the start position once the capacity is reached
+        let mut next = Position::default();
+        next.play("e2e4").unwrap();
+        cached.lookup(&next.to_string()).unwrap();
+        cached.lookup(&start).unwrap();
+        assert_eq!(3, cached.fetcher.calls.get());
+
+        cached.clear();
+        cached.lookup(&start).unwrap();
+        assert_eq!(4, cached.fetcher.calls.get());
+    }
 }
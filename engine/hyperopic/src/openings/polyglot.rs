@@ -0,0 +1,262 @@
+use super::{OpeningMoveRecord, choose_move};
+use crate::LookupMoveService;
+use crate::Square;
+use crate::constants::{
+    class, create_piece, piece_class, piece_side, side, square_file, square_rank,
+};
+use crate::moves::{Move, Moves};
+use crate::parse::parse_uci_move;
+use crate::position::Position;
+use anyhow::{Result, anyhow};
+use std::path::Path;
+
+/// The number of random values a polyglot key is built from, 768 for the pieces on each
+/// square, 4 for the castling rights, 8 for the en passant files and 1 for the side to move.
+pub const POLYGLOT_KEY_COUNT: usize = 781;
+const CASTLING_OFFSET: usize = 768;
+const ENPASSANT_OFFSET: usize = 772;
+const TURN_OFFSET: usize = 780;
+/// Each book entry is a big endian key, move, weight and learn value
+const ENTRY_SIZE: usize = 16;
+const PROMOTIONS: [&str; 5] = ["", "n", "b", "r", "q"];
+
+/// The random values used to compute the polyglot key of a position. Books are only
+/// readable with the values they were written with, for books in the wild these are the
+/// Random64 array published with the polyglot format.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PolyglotKeys(Vec<u64>);
+
+impl PolyglotKeys {
+    pub fn new(values: Vec<u64>) -> Result<PolyglotKeys> {
+        if values.len() == POLYGLOT_KEY_COUNT {
+            Ok(PolyglotKeys(values))
+        } else {
+            Err(anyhow!("Expected {} key values, got {}", POLYGLOT_KEY_COUNT, values.len()))
+        }
+    }
+
+    /// Compute the polyglot key of the given position
+    pub fn key(&self, position: &Position) -> u64 {
+        let mut key = 0u64;
+        for (square, piece) in position.piece_locs.iter().enumerate() {
+            if let Some(p) = piece {
+                // Polyglot orders the pieces black pawn, white pawn, black knight, ...
+                let kind = 2 * piece_class(*p) + if piece_side(*p) == side::W { 1 } else { 0 };
+                let (file, row) = polyglot_coordinates(square);
+                key ^= self.0[64 * kind + 8 * row + file];
+            }
+        }
+        for (corner, _) in position.castling_rights.iter().enumerate().filter(|(_, r)| **r) {
+            key ^= self.0[CASTLING_OFFSET + corner];
+        }
+        if let Some(ep) = position.enpassant.filter(|&ep| enpassant_capturable(position, ep)) {
+            key ^= self.0[ENPASSANT_OFFSET + polyglot_coordinates(ep).0];
+        }
+        if position.active == side::W {
+            key ^= self.0[TURN_OFFSET];
+        }
+        key
+    }
+}
+
+/// The polyglot file and row of a square, both counted from zero at a1
+fn polyglot_coordinates(square: Square) -> (usize, usize) {
+    (7 - square_file(square), square_rank(square))
+}
+
+/// Polyglot only hashes the en passant file if a pawn of the side to move could capture
+fn enpassant_capturable(position: &Position, ep: Square) -> bool {
+    let pawn = create_piece(position.active, class::P);
+    let rank = if position.active == side::W { square_rank(ep) - 1 } else { square_rank(ep) + 1 };
+    let file = square_file(ep);
+    [file.wrapping_sub(1), file + 1]
+        .into_iter()
+        .filter(|&f| f < 8)
+        .any(|f| position.piece_locs[8 * rank + f] == Some(pawn))
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct PolyglotEntry {
+    key: u64,
+    mv: u16,
+    weight: u16,
+}
+
+/// An opening book in the polyglot binary format, moves are chosen with probability
+/// proportional to their weight in the book.
+pub struct PolyglotBook {
+    keys: PolyglotKeys,
+    entries: Vec<PolyglotEntry>,
+}
+
+impl PolyglotBook {
+    pub fn new(bytes: &[u8], keys: PolyglotKeys) -> Result<PolyglotBook> {
+        if !bytes.len().is_multiple_of(ENTRY_SIZE) {
+            return Err(anyhow!("Book length {} is not a multiple of {}", bytes.len(), ENTRY_SIZE));
+        }
+        let mut entries = bytes
+            .chunks_exact(ENTRY_SIZE)
+            .map(|chunk| PolyglotEntry {
+                key: u64::from_be_bytes(chunk[0..8].try_into().unwrap()),
+                mv: u16::from_be_bytes(chunk[8..10].try_into().unwrap()),
+                weight: u16::from_be_bytes(chunk[10..12].try_into().unwrap()),
+            })
+            .collect::<Vec<_>>();
+        // Books should be written sorted by key but the lookup relies on it so make sure
+        entries.sort_by_key(|e| e.key);
+        Ok(PolyglotBook { keys, entries })
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P, keys: PolyglotKeys) -> Result<PolyglotBook> {
+        PolyglotBook::new(&std::fs::read(path)?, keys)
+    }
+
+    fn entries(&self, key: u64) -> &[PolyglotEntry] {
+        let start = self.entries.partition_point(|e| e.key < key);
+        let end = self.entries.partition_point(|e| e.key <= key);
+        &self.entries[start..end]
+    }
+}
+
+impl LookupMoveService for PolyglotBook {
+    fn lookup(&self, position: Position) -> Result<Option<Move>> {
+        let options = self
+            .entries(self.keys.key(&position))
+            .iter()
+            .filter(|e| e.weight > 0)
+            // Skip entries which are illegal here, they can only come from key collisions
+            .filter_map(|e| decode_move(&position, e.mv).ok().map(|m| (m, e.weight)))
+            .collect::<Vec<_>>();
+        if options.is_empty() {
+            return Ok(None);
+        }
+        let records = options
+            .iter()
+            .map(|(m, weight)| OpeningMoveRecord {
+                mv: m.to_string(),
+                freq: *weight as u64,
+                results: None,
+            })
+            .collect::<Vec<_>>();
+        let chosen = choose_move(&records, rand::random)?;
+        Ok(options.into_iter().map(|(m, _)| m).find(|m| m.to_string() == chosen.mv))
+    }
+}
+
+/// Translate a polyglot move into a legal move in the given position. Polyglot encodes
+/// castling as the king moving onto the square of its own rook.
+fn decode_move(position: &Position, encoded: u16) -> Result<Move> {
+    let field = |shift: u16| ((encoded >> shift) & 0b111) as usize;
+    let (dest_file, dest_row, from_file, from_row) = (field(0), field(3), field(6), field(9));
+    let from = 8 * from_row + 7 - from_file;
+    let dest = 8 * dest_row + 7 - dest_file;
+    let castle = position
+        .moves(&Moves::All)
+        .into_iter()
+        .find(|m| matches!(m, &Move::Castle { king, rook, .. } if (king, rook) == (from, dest)));
+    if let Some(castle) = castle {
+        return Ok(castle);
+    }
+    let promotion = PROMOTIONS.get(field(12)).ok_or(anyhow!("Bad promotion in {}", encoded))?;
+    let uci = format!(
+        "{}{}{}{}{}",
+        (b'a' + from_file as u8) as char,
+        from_row + 1,
+        (b'a' + dest_file as u8) as char,
+        dest_row + 1,
+        promotion
+    );
+    parse_uci_move(position, &uci)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{POLYGLOT_KEY_COUNT, PolyglotBook, PolyglotKeys};
+    use crate::LookupMoveService;
+    use crate::constants::corner;
+    use crate::constants::square::*;
+    use crate::moves::Move;
+    use crate::position::Position;
+
+    fn test_keys() -> PolyglotKeys {
+        let mut s = 0x9D39247E33776D41u64;
+        let values = (0..POLYGLOT_KEY_COUNT)
+            .map(|_| {
+                s ^= s << 13;
+                s ^= s >> 7;
+                s ^= s << 17;
+                s
+            })
+            .collect();
+        PolyglotKeys::new(values).unwrap()
+    }
+
+    fn encode(from: (u16, u16), dest: (u16, u16)) -> u16 {
+        (from.1 << 9) | (from.0 << 6) | (dest.1 << 3) | dest.0
+    }
+
+    fn entry(key: u64, mv: u16, weight: u16) -> Vec<u8> {
+        let mut bytes = key.to_be_bytes().to_vec();
+        bytes.extend(mv.to_be_bytes());
+        bytes.extend(weight.to_be_bytes());
+        bytes.extend(0u32.to_be_bytes());
+        bytes
+    }
+
+    #[test]
+    fn keys_require_every_value() {
+        assert!(PolyglotKeys::new(vec![0; 10]).is_err());
+    }
+
+    #[test]
+    fn enpassant_only_hashed_when_capturable() {
+        let keys = test_keys();
+        let mut position = Position::default();
+        position.play("e2e4").unwrap();
+        let without_ep: Position =
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1".parse().unwrap();
+        assert_eq!(keys.key(&without_ep), keys.key(&position));
+
+        position.play("d7d5 e4e5 f7f5").unwrap();
+        let without_ep: Position =
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq - 0 3".parse().unwrap();
+        assert_ne!(keys.key(&without_ep), keys.key(&position));
+    }
+
+    #[test]
+    fn book_move_returned_for_start_position() {
+        let keys = test_keys();
+        let start_key = keys.key(&Position::default());
+        let mut bytes = entry(start_key, encode((4, 1), (4, 3)), 1);
+        bytes.extend(entry(start_key.wrapping_add(1), encode((3, 1), (3, 3)), 1));
+        bytes.extend(entry(start_key.wrapping_sub(1), encode((2, 1), (2, 3)), 1));
+
+        let path = std::env::temp_dir().join("hyperopic-polyglot-test.bin");
+        std::fs::write(&path, &bytes).unwrap();
+        let book = PolyglotBook::load(&path, keys).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let expected = Position::default().play("e2e4").unwrap()[0].clone();
+        assert_eq!(Some(expected), book.lookup(Position::default()).unwrap());
+    }
+
+    #[test]
+    fn castling_translated_from_king_takes_rook() {
+        let keys = test_keys();
+        let position: Position = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1".parse().unwrap();
+        let bytes = entry(keys.key(&position), encode((4, 0), (7, 0)), 5);
+        let book = PolyglotBook::new(&bytes, keys).unwrap();
+        assert_eq!(
+            Some(Move::Castle { corner: corner::WK, king: E1, rook: H1 }),
+            book.lookup(position).unwrap()
+        );
+    }
+
+    #[test]
+    fn missing_position_has_no_move() {
+        let keys = test_keys();
+        let bytes = entry(keys.key(&Position::default()) ^ 1, encode((4, 1), (4, 3)), 1);
+        let book = PolyglotBook::new(&bytes, keys).unwrap();
+        assert_eq!(None, book.lookup(Position::default()).unwrap());
+    }
+}
@@ -6,11 +6,11 @@ use lazy_static::lazy_static;
 use regex::Regex;
 
 use crate::board::iter;
-use crate::constants::{class, lift, piece_class, square_file, square_rank};
+use crate::constants::{class, create_piece, lift, piece_class, side, square_file, square_rank};
 use crate::moves::{Move, Moves};
-use crate::{Board, Class, Piece, PieceMap, Square};
+use crate::{Board, Class, Corner, Piece, PieceMap, Square, SquareMap};
 
-use crate::position::{CASTLING_DETAILS, Position};
+use crate::position::{CastlingDetails, Position, STANDARD_CASTLING_SQUARES};
 
 impl FromStr for Position {
     type Err = Error;
@@ -40,6 +40,36 @@ impl Position {
         }
         Ok(result)
     }
+
+    /// Play a sequence of moves in standard algebraic notation, such as the movetext of
+    /// a PGN game. Move numbers, comments, variations, annotations, en passant markers
+    /// and the game result are ignored. Every move must be legal and unambiguous, on an
+    /// error the moves before the offending one are left played.
+    pub fn play_san<S: AsRef<str>>(&mut self, san: S) -> Result<Vec<Move>> {
+        let stripped = PGN_IGNORED.replace_all(san.as_ref(), " ");
+        let mut result = vec![];
+        for token in SPACE.split(&stripped).filter(|t| !t.is_empty() && !PGN_RESULT.is_match(t)) {
+            let move_number = format!(
+                "{}{}",
+                self.history.len() / 2 + 1,
+                if self.history.len().is_multiple_of(2) { "." } else { "..." }
+            );
+            let candidates = if PGN_MOVE_TOKEN.is_match(token) || PGN_FILE_CAPTURE.is_match(token) {
+                pgn_move_candidates(self, token)
+            } else {
+                return Err(anyhow!("Unrecognised token {}{} in {}", move_number, token, self));
+            };
+            match candidates.len() {
+                0 => return Err(anyhow!("Illegal move {}{} in {}", move_number, token, self)),
+                1 => {
+                    result.push(candidates[0].clone());
+                    self.make(candidates[0].clone())?
+                }
+                _ => return Err(anyhow!("Ambiguous move {}{} in {}", move_number, token, self)),
+            }
+        }
+        Ok(result)
+    }
 }
 
 pub struct StringIndexMap {
@@ -123,7 +153,7 @@ lazy_static! {
     static ref FEN_RANK: Regex = r"([pnbrqkPNBRQK1-8]{1,8})".parse().unwrap();
 
     static ref FEN: Regex = format!(
-        r"{}(/{}){{7}}\s+(w|b)\s+(-|[kqKQ]{{1,4}})\s+(-|{})\s+\d+\s+\d+",
+        r"{}(/{}){{7}}\s+(w|b)\s+(-|[kqKQa-hA-H]{{1,4}})\s+(-|{})\s+\d+\s+\d+",
         FEN_RANK.as_str(),
         FEN_RANK.as_str(),
         SQUARE.as_str(),
@@ -152,6 +182,18 @@ lazy_static! {
         PGN_CASTLE.as_str()
     ).as_str().parse().unwrap();
 
+    static ref PGN_MOVE_TOKEN: Regex =
+        format!(r"^{}[+#]?[!?]*$", PGN_MOVE.as_str()).as_str().parse().unwrap();
+    static ref PGN_RESULT: Regex = r"^(1-0|0-1|1/2-1/2|\*)$".parse().unwrap();
+    // A pawn capture giving only the destination file, like exd
+    static ref PGN_FILE_CAPTURE: Regex = r"^([a-h])x([a-h])(=[NBRQ])?[+#]?[!?]*$".parse().unwrap();
+    // Headers, comments, variations (one level of nesting), annotation glyphs, move numbers
+    // and en passant markers
+    static ref PGN_IGNORED: Regex =
+        r"(\[[^\]]*\])|(\{[^}]*\})|(;[^\n]*)|(\(([^()]|\([^()]*\))*\))|(\$\d+)|(\d+\.(\.\.)?)|(e\.p\.)"
+            .parse()
+            .unwrap();
+
     static ref UCI_MOVE: Regex = r"(([a-h][1-8]){2}[nbrq]?)".parse().unwrap();
 }
 
@@ -164,7 +206,11 @@ pub fn parse_uci_move(position: &Position, input: &str) -> Result<Move> {
             Null => false,
             &Normal { from, dest, .. } => from == f && dest == d,
             &Enpassant { from, dest, .. } => from == f && dest == d,
-            &Castle { corner, .. } => CASTLING_DETAILS[corner].king_line == (f, d),
+            &Castle { corner, king, rook } => {
+                // Accept both standard and Chess960 (king takes rook) encodings
+                let details = CastlingDetails::new(corner, king, rook);
+                details.king_line == (f, d) || (king, rook) == (f, d)
+            }
             &Promote { from, dest, promoted, .. } => {
                 from == f
                     && dest == d
@@ -192,20 +238,52 @@ fn extract_uci_component(m: &str) -> Result<(Square, Square, Option<char>)> {
 }
 
 pub fn parse_pgn_move(position: &Position, input: &str) -> Result<Move> {
+    pgn_move_candidates(position, input)
+        .into_iter()
+        .next()
+        .ok_or(anyhow!("No move matching {}", input))
+}
+
+/// Replay a complete PGN game from the standard start position checking that every
+/// move is legal and unambiguous, the final position is returned. Headers, comments,
+/// variations, annotations and the game result are ignored.
+pub fn verify_pgn(pgn: &str) -> Result<Position> {
+    let mut position = Position::default();
+    position.play_san(pgn)?;
+    Ok(position)
+}
+
+fn pgn_move_candidates(position: &Position, input: &str) -> Vec<Move> {
     let moves = position.moves(&Moves::All);
 
-    if PGN_CASTLE.is_match(input) {
+    if let Some(captures) = PGN_FILE_CAPTURE.captures(input) {
+        let file = |group: usize| captures.get(group).and_then(|m| m.as_str().chars().next());
+        let promote_piece_class = parse_pgn_classes(input).1;
+        let files_match = |from: Square, dest: Square| {
+            file(1) == Some(FILE_CHARS[square_file(from)])
+                && file(2) == Some(FILE_CHARS[square_file(dest)])
+        };
         return moves
-            .iter()
-            .find(|&m| {
-                if let Castle { corner } = m {
-                    *corner % 2 == if input == "O-O" { 0 } else { 1 }
-                } else {
-                    false
+            .into_iter()
+            .filter(|m| match m {
+                Enpassant { from, dest, .. } => files_match(*from, *dest),
+                Normal { moving, from, dest, capture: Some(_) } => {
+                    piece_class(*moving) == class::P && files_match(*from, *dest)
+                }
+                Promote { from, dest, promoted, capture: Some(_) } => {
+                    files_match(*from, *dest) && piece_class(*promoted) == promote_piece_class
                 }
+                _ => false,
             })
-            .cloned()
-            .ok_or(anyhow!("{} not legal", input));
+            .collect();
+    }
+
+    if PGN_CASTLE.is_match(input) {
+        let kingside = !input.contains("O-O-O");
+        return moves
+            .into_iter()
+            .filter(|m| matches!(m, Castle { corner, .. } if (*corner % 2 == 0) == kingside))
+            .collect();
     }
 
     let target =
@@ -239,8 +317,7 @@ pub fn parse_pgn_move(position: &Position, input: &str) -> Result<Move> {
                     && promote_piece_matches(piece_class(*promoted))
             }
         })
-        .next()
-        .ok_or(anyhow!("No move matching {}", input))
+        .collect()
 }
 
 fn matches_square(file: Option<char>, rank: Option<char>, square: Square) -> bool {
@@ -281,7 +358,6 @@ fn parse_class(piece: Option<char>) -> Class {
 }
 
 fn parse_fen(fen: &str) -> Result<Position> {
-    use crate::constants::side;
     let fen = fen.trim();
     if !FEN.is_match(fen) {
         return Err(anyhow!("Cannot parse {} as a fen", fen));
@@ -293,9 +369,46 @@ fn parse_fen(fen: &str) -> Result<Position> {
     let piece_boards = parse_fen_pieces(parts[0]);
     let mut piece_locs = [None; 64];
     (0..12).for_each(|p| iter(piece_boards[p]).for_each(|s| piece_locs[s] = Some(p)));
-    let rights_fn = |s: &str| parts[2].contains(s);
-    let castling_rights = [rights_fn("K"), rights_fn("Q"), rights_fn("k"), rights_fn("q")];
-    Ok(Position::new(active, enpassant, clock, castling_rights, piece_locs))
+    let mut castling_rights = [false; 4];
+    let mut castling_squares = STANDARD_CASTLING_SQUARES;
+    for right in parts[2].chars().filter(|&c| c != '-') {
+        let (corner, squares) = parse_castling_right(right, &piece_locs);
+        castling_rights[corner] = true;
+        castling_squares[corner] = squares;
+    }
+    let mut position = Position::new(active, enpassant, clock, castling_rights, piece_locs);
+    position.castling_squares = castling_squares;
+    Ok(position)
+}
+
+/// Parse a castling right in standard, X-FEN or Shredder-FEN notation returning the
+/// corner and the start squares of the king and rook. The letters K and Q refer to
+/// the outermost rook on that side of the king while a file letter names the rook
+/// explicitly, as needed in Chess960 when two rooks stand on the same side of the king.
+fn parse_castling_right(
+    right: char,
+    piece_locs: &SquareMap<Option<Piece>>,
+) -> (Corner, (Square, Square)) {
+    let side = if right.is_ascii_uppercase() { side::W } else { side::B };
+    let back_rank = if side == side::W { 0..8 } else { 56..64 };
+    let (standard_king, _) = STANDARD_CASTLING_SQUARES[2 * side];
+    let king = back_rank
+        .clone()
+        .find(|&sq| piece_locs[sq] == Some(create_piece(side, class::K)))
+        .unwrap_or(standard_king);
+    let mut rooks =
+        back_rank.clone().filter(|&sq| piece_locs[sq] == Some(create_piece(side, class::R)));
+    // Squares increase from the h-file to the a-file
+    let (kingside, rook) = match right.to_ascii_lowercase() {
+        'k' => (true, rooks.find(|&sq| sq < king)),
+        'q' => (false, rooks.rfind(|&sq| sq > king)),
+        file => {
+            let rook = back_rank.start + FILE_CHARS.iter().position(|&f| f == file).unwrap();
+            (rook < king, Some(rook))
+        }
+    };
+    let corner = 2 * side + if kingside { 0 } else { 1 };
+    (corner, (king, rook.unwrap_or(STANDARD_CASTLING_SQUARES[corner].1)))
 }
 
 fn parse_fen_pieces(fen: &str) -> PieceMap<Board> {
@@ -435,6 +548,131 @@ mod test_pgn_game {
     }
 }
 
+#[cfg(test)]
+mod test_verify_pgn {
+    use super::verify_pgn;
+
+    #[test]
+    fn complete_game() {
+        let position = verify_pgn(
+            "
+            [Event \"Casual game\"]
+            [Site \"London ENG\"]
+            [Date \"1851.06.21\"]
+            [White \"Anderssen, Adolf\"]
+            [Black \"Kieseritzky, Lionel\"]
+            [Result \"1-0\"]
+
+            1. e4 e5 2. f4 exf4 3. Bc4 Qh4+ 4. Kf1 b5 5. Bxb5 Nf6 6. Nf3 Qh6 7. d3 Nh5
+            8. Nh4 Qg5 9. Nf5 c6 10. g4 Nf6 11. Rg1! cxb5 12. h4 Qg6 13. h5 Qg5 14. Qf3
+            Ng8 15. Bxf4 Qf6 16. Nc3 Bc5 17. Nd5 Qxb2 18. Bd6 Bxg1 {18...Qxa1+ was better}
+            19. e5 Qxa1+ 20. Ke2 Na6 (20... Qxa2 21. Nxg7+ Kd8 22. Qf6+ Nxf6 23. Be7#)
+            21. Nxg7+ Kd8 22. Qf6+ Nxf6 23. Be7# 1-0
+            ",
+        )
+        .unwrap();
+        assert_eq!(
+            "r1bk3r/p2pBpNp/n4n2/1p1NP2P/6P1/3P4/P1P1K3/q5b1 b - - 1 23",
+            position.to_string()
+        );
+        assert_eq!(45, position.history.len());
+    }
+
+    #[test]
+    fn illegal_move_reported() {
+        let error = verify_pgn("1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 4. Bxc6 dxc6 5. Bxe5").unwrap_err();
+        assert_eq!(
+            "Illegal move 5.Bxe5 in r1bqkbnr/1pp2ppp/p1p5/4p3/4P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 0 5",
+            error.to_string()
+        );
+    }
+
+    #[test]
+    fn ambiguous_move_reported() {
+        // Both the knight on c3 and the knight on g5 can move to e4
+        let error = verify_pgn("1. Nc3 Nf6 2. Nf3 d6 3. Ng5 e6 4. Ne4").unwrap_err();
+        assert!(error.to_string().starts_with("Ambiguous move 4.Ne4"), "{}", error);
+        assert!(verify_pgn("1. Nc3 Nf6 2. Nf3 d6 3. Ng5 e6 4. Nce4").is_ok());
+    }
+}
+
+#[cfg(test)]
+mod test_play_san {
+    use crate::constants::corner;
+    use crate::constants::square::*;
+    use crate::moves::Move;
+    use crate::position::Position;
+
+    #[test]
+    fn full_game() {
+        let mut position = Position::default();
+        let moves = position
+            .play_san(
+                "
+                [Event \"Paris\"]
+                [White \"Morphy, Paul\"]
+                [Black \"Duke Karl / Count Isouard\"]
+                [Result \"1-0\"]
+
+                1. e4 e5 2. Nf3 d6 3. d4 Bg4 4. dxe5 Bxf3 5. Qxf3 dxe5 6. Bc4 Nf6 7. Qb3 Qe7
+                8. Nc3 c6 9. Bg5 b5 10. Nxb5 cxb5 11. Bxb5+ Nbd7 12. O-O-O Rd8 13. Rxd7 Rxd7
+                14. Rd1 Qe6 15. Bxd7+ Nxd7 16. Qb8+ Nxb8 17. Rd8# 1-0
+                ",
+            )
+            .unwrap();
+        assert_eq!(33, moves.len());
+        assert_eq!(Move::Castle { corner: corner::WQ, king: E1, rook: A1 }, moves[22]);
+        assert_eq!("1n1Rkb1r/p4ppp/4q3/4p1B1/4P3/8/PPP2PPP/2K5 b k - 1 17", position.to_string());
+    }
+
+    #[test]
+    fn en_passant_marker_ignored() {
+        let mut position = Position::default();
+        position.play_san("1. e4 Nf6 2. e5 d5 3. exd6 e.p.").unwrap();
+        assert_eq!(
+            "rnbqkb1r/ppp1pppp/3P1n2/8/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 3",
+            position.to_string()
+        );
+    }
+
+    #[test]
+    fn pawn_capture_without_destination_rank() {
+        let mut expected = Position::default();
+        expected.play_san("1. e4 d5 2. exd5").unwrap();
+        let mut position = Position::default();
+        position.play_san("1. e4 d5 2. exd").unwrap();
+        assert_eq!(expected, position);
+        // Ambiguous when two pawns on the same file can capture onto the same file
+        let position: Position = "4k3/3p4/4P3/3p4/4P3/8/8/4K3 w - - 0 1".parse().unwrap();
+        assert!(position.clone().play_san("exd").is_err());
+        assert!(position.clone().play_san("exd5").is_ok());
+    }
+
+    #[test]
+    fn knights_reaching_the_same_square() {
+        let mut position = Position::default();
+        position.play_san("1. Nf3 d5 2. d4 Nf6").unwrap();
+        let error = position.clone().play_san("3. Nd2").unwrap_err();
+        assert!(error.to_string().starts_with("Ambiguous move 3.Nd2"), "{}", error);
+        let moves = position.play_san("3. Nbd2").unwrap();
+        assert_eq!("b1d2", moves[0].to_string());
+    }
+
+    #[test]
+    fn castling_with_check_suffix() {
+        let mut position: Position = "3k4/8/8/8/8/8/8/R3K3 w Q - 0 1".parse().unwrap();
+        let moves = position.play_san("O-O-O+").unwrap();
+        assert_eq!(vec![Move::Castle { corner: corner::WQ, king: E1, rook: A1 }], moves);
+    }
+
+    #[test]
+    fn promotion() {
+        let mut position: Position = "3r3k/4P3/8/8/8/8/8/4K3 w - - 0 1".parse().unwrap();
+        assert_eq!("e7d8n", position.clone().play_san("exd8=N").unwrap()[0].to_uci(false));
+        assert_eq!("e7e8q", position.play_san("e8=Q+").unwrap()[0].to_uci(false));
+    }
+}
+
 #[cfg(test)]
 mod test_single_pgn_move {
 
@@ -686,4 +924,22 @@ mod test_single_uci_move {
             "e8c8",
         )
     }
+
+    #[test]
+    fn case_twelve() {
+        execute_success_test(
+            "cwk",
+            "r3k2r/pp1q1ppp/n1p2n2/4p3/3pP2P/3P1QP1/PPPN1PB1/R3K2R w KQkq - 1 13",
+            "e1h1",
+        )
+    }
+
+    #[test]
+    fn case_thirteen() {
+        execute_success_test(
+            "cbq",
+            "r3k2r/pp1q1ppp/n1p2n2/4p3/3pP2P/3P1QP1/PPPN1PB1/R4RK1 b kq - 2 13",
+            "e8a8",
+        )
+    }
 }
@@ -4,7 +4,18 @@ use crate::moves::Move;
 use crate::node::Evaluation;
 use crate::position::Position;
 
-const MAX_PHASE: i32 = 256;
+/// The phase once only kings and pawns remain, the start of the game has phase zero
+pub const MAX_PHASE: i32 = 256;
+
+/// Compute the phase of the given position from its remaining non-pawn material
+pub fn game_phase(position: &Position) -> i32 {
+    Phase::from(position).phase
+}
+
+/// Blend a middlegame and endgame value according to the given phase
+pub fn interpolate(phase: i32, mid: i32, end: i32) -> i32 {
+    ((mid * (MAX_PHASE - phase)) + end * phase) / MAX_PHASE
+}
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct Phase {
@@ -54,11 +65,14 @@ impl Phase {
     }
 
     pub fn interpolate(&self, mid: i32, end: i32) -> i32 {
-        ((mid * (MAX_PHASE - self.phase)) + end * self.phase) / MAX_PHASE
+        interpolate(self.phase, mid, end)
     }
 
     fn update_phase(&mut self) {
-        self.phase = (self.phase_counter * MAX_PHASE + self.total_phase / 2i32) / self.total_phase;
+        // Promotions or odds positions can leave more or less material than the standard
+        // start so keep the phase within bounds to avoid extrapolating evaluations
+        let phase = (self.phase_counter * MAX_PHASE + self.total_phase / 2i32) / self.total_phase;
+        self.phase = phase.clamp(0, MAX_PHASE);
     }
 
     pub fn make(&mut self, mv: &Move) {
@@ -101,3 +115,57 @@ impl Phase {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{MAX_PHASE, Phase, game_phase};
+    use crate::node::Evaluation;
+    use crate::position::Position;
+
+    fn phase_of(fen: &str) -> i32 {
+        game_phase(&fen.parse::<Position>().unwrap())
+    }
+
+    #[test]
+    fn start_position_is_midgame() {
+        assert_eq!(0, phase_of("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"));
+    }
+
+    #[test]
+    fn queen_odds_is_mostly_midgame() {
+        let phase = phase_of("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNB1KBNR w KQkq - 0 1");
+        assert!(0 < phase && phase < MAX_PHASE / 4);
+    }
+
+    #[test]
+    fn extra_promoted_material_is_clamped() {
+        assert_eq!(0, phase_of("rnbqkbnr/pppppppp/8/8/8/8/1PPPPPPP/QQBQKBNR w Kkq - 0 1"));
+    }
+
+    #[test]
+    fn bare_kings_are_endgame() {
+        assert_eq!(MAX_PHASE, phase_of("4k3/8/8/8/8/8/8/4K3 w - - 0 1"));
+    }
+
+    fn phase(fen: &str) -> Phase {
+        Phase::from(&fen.parse::<Position>().unwrap())
+    }
+
+    #[test]
+    fn unwrap_at_phase_extremes() {
+        let start = phase("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        let bare_kings = phase("4k3/8/8/8/8/8/8/4K3 w - - 0 1");
+        assert_eq!(100, start.unwrap(Evaluation::Phased { mid: 100, end: -40 }));
+        assert_eq!(-40, bare_kings.unwrap(Evaluation::Phased { mid: 100, end: -40 }));
+        // A single evaluation is the same in every phase
+        assert_eq!(25, start.unwrap(Evaluation::Single(25)));
+        assert_eq!(25, bare_kings.unwrap(Evaluation::Single(25)));
+    }
+
+    #[test]
+    fn unwrap_blends_between_extremes() {
+        let queen_odds = phase("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNB1KBNR w KQkq - 0 1");
+        let blended = queen_odds.unwrap(Evaluation::Phased { mid: 100, end: -40 });
+        assert!(-40 < blended && blended < 100, "{}", blended);
+    }
+}
@@ -1,18 +1,23 @@
 use crate::moves::{Move, Move::*, MoveFacet, Moves};
 use crate::{
-    Board, Corner, CornerMap, Piece, PieceMap, Side, SideMap, Square, SquareMap, board, hash,
+    Board, Corner, CornerMap, Piece, PieceMap, Side, SideMap, Square, SquareMap, Symmetric, board,
+    hash,
 };
+use std::array;
 use std::cmp::{max, min};
 
 use crate::board::{board_moves, control, cord, iter, union_boards};
-use crate::constants::boards::{ADJACENT_FILES, RANKS};
+use crate::constants::boards::{ADJACENT_FILES, LIGHT_SQUARES, RANKS};
 use crate::constants::piece::*;
 use crate::constants::side::*;
 use crate::constants::square::*;
 use crate::constants::{
-    class, corner, create_piece, first_square, in_board, intersects, is_superset, lift,
-    piece_class, piece_side, reflect_piece, reflect_side, side, square_file, square_rank,
+    class, create_piece, first_square, in_board, intersects, is_superset, lift, piece_class,
+    piece_side, reflect_corner, reflect_piece, reflect_side, reflect_square, side, square_file,
+    square_rank,
 };
+use crate::eval::material::MaterialFacet;
+use crate::see;
 use anyhow::{Result, anyhow};
 use rustc_hash::FxHashMap;
 
@@ -36,6 +41,8 @@ pub struct Position {
     pub piece_locs: SquareMap<Option<Piece>>,
     pub side_boards: SideMap<Board>,
     pub castling_rights: CornerMap<bool>,
+    /// The start squares of the king and rook for each castling corner
+    pub castling_squares: CornerMap<(Square, Square)>,
     pub active: Side,
     pub enpassant: Option<Square>,
     pub clock: usize,
@@ -53,6 +60,30 @@ pub struct Discards {
     pub passive_control: u64,
 }
 
+/// A compact key for the material on the board, holding the number of each piece
+/// type for each side in four bits apiece. Positions with the same piece counts
+/// share a signature regardless of where the pieces stand.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct MaterialSignature(u64);
+
+impl MaterialSignature {
+    const BITS_PER_PIECE: usize = 4;
+    const COUNT_MASK: u64 = (1 << Self::BITS_PER_PIECE) - 1;
+
+    /// Counts saturate at fifteen, which no legal position exceeds, so they cannot
+    /// spill into the count of the next piece
+    pub fn new(counts: PieceMap<u32>) -> MaterialSignature {
+        MaterialSignature((0..12).fold(0u64, |a, p| {
+            a | ((counts[p].min(Self::COUNT_MASK as u32) as u64) << (p * Self::BITS_PER_PIECE))
+        }))
+    }
+
+    /// The number of the given piece on the board
+    pub fn count(&self, piece: Piece) -> u32 {
+        ((self.0 >> (piece * Self::BITS_PER_PIECE)) & Self::COUNT_MASK) as u32
+    }
+}
+
 impl Default for Position {
     fn default() -> Self {
         "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1".parse().unwrap()
@@ -61,8 +92,9 @@ impl Default for Position {
 
 #[cfg(debug_assertions)]
 pub fn check_consistent(position: &Position) -> Result<()> {
-    if position.key != position.compute_key() {
-        return Err(anyhow!("Keys do not match!"));
+    let recomputed = position.recompute_key();
+    if position.key != recomputed {
+        return Err(anyhow!("Incremental key {} does not match {}", position.key, recomputed));
     }
     for sq in 0..64 {
         let pieces_piece = (0..12).find(|p| in_board(position.piece_boards[*p], sq));
@@ -87,6 +119,29 @@ pub fn check_consistent(position: &Position) -> Result<()> {
     Ok(())
 }
 
+impl Symmetric for Position {
+    fn reflect(&self) -> Self {
+        let mut cloned = self.clone();
+        let mut moves = vec![];
+        while let Ok(m) = cloned.unmake() {
+            moves.insert(0, m);
+        }
+        let mut reflected = Position::new(
+            reflect_side(cloned.active),
+            cloned.enpassant.map(reflect_square),
+            cloned.clock,
+            array::from_fn(|c| cloned.castling_rights[reflect_corner(c)]),
+            array::from_fn(|sq| cloned.piece_locs[reflect_square(sq)].map(reflect_piece)),
+        );
+        reflected.castling_squares = array::from_fn(|c| {
+            let (king, rook) = cloned.castling_squares[reflect_corner(c)];
+            (reflect_square(king), reflect_square(rook))
+        });
+        moves.into_iter().for_each(|m| reflected.make(m.reflect()).unwrap());
+        reflected
+    }
+}
+
 impl Position {
     pub fn new(
         active: Side,
@@ -101,6 +156,7 @@ impl Position {
             clock,
             piece_locs,
             castling_rights,
+            castling_squares: STANDARD_CASTLING_SQUARES,
             key: 0,
             history: vec![],
             passive_control: 0,
@@ -114,11 +170,13 @@ impl Position {
             }),
         };
         result.passive_control = result.compute_control(reflect_side(active));
-        result.key = result.compute_key();
+        result.key = result.recompute_key();
         result
     }
 
-    fn compute_key(&self) -> u64 {
+    /// Compute the key from scratch rather than incrementally as moves are made, in debug
+    /// builds the two are compared after every make and unmake.
+    pub fn recompute_key(&self) -> u64 {
         let mut key = if self.active == W { 0u64 } else { hash::black_move() };
         self.enpassant.map(|sq| key ^= hash::enpassant(sq));
         (0..64).for_each(|sq| self.piece_locs[sq].iter().for_each(|&p| key ^= hash::piece(p, sq)));
@@ -129,7 +187,19 @@ impl Position {
 
 // Implementation block for making/unmaking moves
 impl Position {
+    /// Apply the given move which is first checked to be pseudo-legal, i.e. it moves a
+    /// piece of the side to move the way that piece moves and agrees with the board about
+    /// what is captured. The position is left untouched if the check fails.
     pub fn make(&mut self, m: Move) -> Result<()> {
+        if !self.is_pseudo_legal(&m) {
+            return Err(anyhow!("{} is not pseudo-legal in {}", m, self));
+        }
+        self.make_unchecked(m)
+    }
+
+    /// Apply the given move without checking it, the search only makes moves generated
+    /// from the position so does not pay for the check
+    pub(crate) fn make_unchecked(&mut self, m: Move) -> Result<()> {
         #[cfg(debug_assertions)]
         let start_fen = self.to_string();
         self.history.push((self.create_discards(), m.clone()));
@@ -141,8 +211,8 @@ impl Position {
                 capture.map(|p| self.unset_piece(p, dest));
                 self.unset_piece(moving, from);
                 self.set_piece(moving, dest);
-                self.remove_rights(rights_removed(from));
-                self.remove_rights(rights_removed(dest));
+                self.remove_rights(from);
+                self.remove_rights(dest);
                 let is_pawn = piece_class(moving) == class::P;
                 self.clock = if capture.is_some() || is_pawn { 0 } else { self.clock + 1 };
                 if is_pawn && max(from, dest) - min(from, dest) == 16 {
@@ -154,7 +224,7 @@ impl Position {
             Promote { from, dest, promoted, capture } => {
                 capture.map(|p| self.unset_piece(p, dest));
                 let moved = create_piece(piece_side(promoted), class::P);
-                self.remove_rights(rights_removed(dest));
+                self.remove_rights(dest);
                 self.unset_piece(moved, from);
                 self.set_piece(promoted, dest);
                 self.clock = 0;
@@ -167,11 +237,11 @@ impl Position {
                 self.set_piece(moving, dest);
                 self.clock = 0;
             }
-            Castle { corner } => {
-                let details = &CASTLING_DETAILS[corner];
+            Castle { corner, king, rook } => {
+                let details = CastlingDetails::new(corner, king, rook);
                 let (r_source, r_target) = details.rook_line;
                 let (k_source, k_target) = details.king_line;
-                self.remove_rights(rights_removed(k_source));
+                self.remove_rights(k_source);
                 let side = corner / 2;
                 let rook = create_piece(side, class::R);
                 let king = create_piece(side, class::K);
@@ -194,6 +264,63 @@ impl Position {
         Ok(())
     }
 
+    fn is_pseudo_legal(&self, m: &Move) -> bool {
+        let active_pawn = create_piece(self.active, class::P);
+        match *m {
+            Null => true,
+            Normal { moving, from, dest, capture } => {
+                piece_side(moving) == self.active
+                    && self.piece_locs[from] == Some(moving)
+                    && self.piece_locs[dest] == capture
+                    && capture.is_none_or(|p| piece_side(p) != self.active)
+                    && (moving != active_pawn || !in_board(RANKS[0] | RANKS[7], dest))
+                    && self.can_move(moving, from, dest, capture.is_some())
+            }
+            Promote { from, dest, promoted, capture } => {
+                piece_side(promoted) == self.active
+                    && (square_rank(from), square_rank(dest))
+                        == if self.active == W { (6, 7) } else { (1, 0) }
+                    && self.piece_locs[from] == Some(active_pawn)
+                    && self.piece_locs[dest] == capture
+                    && capture.is_none_or(|p| piece_side(p) != self.active)
+                    && self.can_move(active_pawn, from, dest, capture.is_some())
+            }
+            Enpassant { side, from, dest, capture } => {
+                side == self.active
+                    && self.enpassant == Some(dest)
+                    && self.piece_locs[from] == Some(active_pawn)
+                    && self.piece_locs[capture] == Some(reflect_piece(active_pawn))
+                    && square_rank(capture) == square_rank(from)
+                    && self.can_move(active_pawn, from, dest, true)
+            }
+            Castle { corner, king, rook } => {
+                corner / 2 == self.active
+                    && self.castling_rights[corner]
+                    && self.castling_squares[corner] == (king, rook)
+                    && self.piece_locs[king] == Some(create_piece(self.active, class::K))
+                    && self.piece_locs[rook] == Some(create_piece(self.active, class::R))
+            }
+        }
+    }
+
+    /// Whether the piece on the source square can reach the target on this board, pawns
+    /// only capture diagonally and otherwise push forward onto empty squares
+    fn can_move(&self, moving: Piece, from: Square, dest: Square, is_capture: bool) -> bool {
+        let occupied = union_boards(&self.piece_boards);
+        if piece_class(moving) != class::P || is_capture {
+            return in_board(control(moving, from, occupied), dest);
+        }
+        let (forward, start_rank) = if piece_side(moving) == W { (8, 1) } else { (-8, 6) };
+        let single = from as i32 + forward;
+        let double = single + forward;
+        let target = dest as i32;
+        !in_board(occupied, dest)
+            && (target == single
+                || (target == double
+                    && square_rank(from) == start_rank
+                    && !in_board(occupied, single as Square)))
+    }
+
     pub fn unmake(&mut self) -> Result<Move> {
         if self.history.len() == 0 {
             return Err(anyhow!("No moves left to unmake!"));
@@ -221,17 +348,18 @@ impl Position {
                 self.set_piece(taken, capture);
                 self.set_piece(moving, from);
             }
-            &Castle { corner } => {
-                let details = &CASTLING_DETAILS[corner];
+            &Castle { corner, king, rook } => {
+                let details = CastlingDetails::new(corner, king, rook);
                 let (r_source, r_target) = details.rook_line;
                 let (k_source, k_target) = details.king_line;
                 let side = corner / 2;
                 let rook = create_piece(side, class::R);
                 let king = create_piece(side, class::K);
-                self.set_piece(rook, r_source);
-                self.set_piece(king, k_source);
+                // In Chess960 the source and target squares can overlap so clear both first
                 self.unset_piece(rook, r_target);
                 self.unset_piece(king, k_target);
+                self.set_piece(rook, r_source);
+                self.set_piece(king, k_source);
             }
         };
         self.castling_rights = state.castling_rights;
@@ -266,9 +394,11 @@ impl Position {
         self.piece_locs[square] = None;
     }
 
-    fn remove_rights(&mut self, corners: &[Corner]) {
-        corners.iter().for_each(|&c| {
-            if self.castling_rights[c] {
+    /// Remove the rights of any corner whose king or rook starts on the given square
+    fn remove_rights(&mut self, square: Square) {
+        (0..4).for_each(|c| {
+            let (king, rook) = self.castling_squares[c];
+            if self.castling_rights[c] && (king == square || rook == square) {
                 self.castling_rights[c] = false;
                 self.key ^= hash::corner(c);
             }
@@ -295,6 +425,77 @@ impl Position {
         intersects(self.passive_control, self.piece_boards[create_piece(self.active, class::K)])
     }
 
+    /// Whether the king of the given side is attacked, regardless of whose move it is.
+    /// If the side not to move is in check then the position is illegal.
+    pub fn in_check_for(&self, side: Side) -> bool {
+        let king = self.piece_boards[create_piece(side, class::K)];
+        king != 0 && self.attackers_to(first_square(king), reflect_side(side)) != 0
+    }
+
+    /// Whether the given move takes an enemy piece, including en passant
+    pub fn is_capture(&self, m: &Move) -> bool {
+        match m {
+            Normal { capture, .. } | Promote { capture, .. } => capture.is_some(),
+            Enpassant { .. } => true,
+            Null | Castle { .. } => false,
+        }
+    }
+
+    /// Whether the given move attacks the enemy king, directly or by discovery. Only the
+    /// piece boards are updated rather than making the move in full.
+    pub fn gives_check(&self, m: &Move) -> bool {
+        let enemy_king = self.piece_boards[create_piece(reflect_side(self.active), class::K)];
+        if enemy_king == 0 {
+            return false;
+        }
+        let mut boards = self.piece_boards;
+        let active_pawn = create_piece(self.active, class::P);
+        // Each entry lifts a piece from its square and places the next piece on its target,
+        // sources are all cleared first as a castling king may land where the rook started
+        let (lifted, placed) = match *m {
+            Null => (vec![], vec![]),
+            Normal { moving, from, dest, capture } => (
+                capture.map(|p| (p, dest)).into_iter().chain([(moving, from)]).collect(),
+                vec![(moving, dest)],
+            ),
+            Promote { from, dest, promoted, capture } => (
+                capture.map(|p| (p, dest)).into_iter().chain([(active_pawn, from)]).collect(),
+                vec![(promoted, dest)],
+            ),
+            Enpassant { from, dest, capture, .. } => (
+                vec![(reflect_piece(active_pawn), capture), (active_pawn, from)],
+                vec![(active_pawn, dest)],
+            ),
+            Castle { corner, king, rook } => {
+                let details = CastlingDetails::new(corner, king, rook);
+                let king = create_piece(self.active, class::K);
+                let rook = create_piece(self.active, class::R);
+                (
+                    vec![(king, details.king_line.0), (rook, details.rook_line.0)],
+                    vec![(king, details.king_line.1), (rook, details.rook_line.1)],
+                )
+            }
+        };
+        lifted.into_iter().for_each(|(piece, sq)| boards[piece] &= !lift(sq));
+        placed.into_iter().for_each(|(piece, sq)| boards[piece] |= lift(sq));
+        let occupied = union_boards(&boards);
+        let target = first_square(enemy_king);
+        [class::P, class::N, class::B, class::R, class::Q, class::K].into_iter().any(|class| {
+            let piece = create_piece(self.active, class);
+            iter(boards[piece]).any(|sq| in_board(control(piece, sq, occupied), target))
+        })
+    }
+
+    /// The piece standing on the given square, if any
+    pub fn piece_on(&self, square: Square) -> Option<Piece> {
+        self.piece_locs[square]
+    }
+
+    /// The side of the piece standing on the given square, if any
+    pub fn side_on(&self, square: Square) -> Option<Side> {
+        self.piece_on(square).map(piece_side)
+    }
+
     pub fn friendly_enemy_boards(&self) -> (Board, Board) {
         (self.side_boards[self.active], self.side_boards[reflect_side(self.active)])
     }
@@ -334,6 +535,22 @@ impl Position {
         }
         .or(self.check_clock_limit())
         .or(self.check_repetitions())
+        .or(self.check_insufficient_material())
+    }
+
+    /// Positions where no sequence of legal moves leads to mate, i.e. only kings and
+    /// bishops on a single colour remain or a lone minor piece. Two knights cannot force
+    /// mate against a bare king but a mate is still possible so play continues.
+    fn check_insufficient_material(&self) -> Option<TerminalState> {
+        let boards = &self.piece_boards;
+        if boards[WP] | boards[BP] | boards[WR] | boards[BR] | boards[WQ] | boards[BQ] != 0 {
+            return None;
+        }
+        let knights = boards[WN] | boards[BN];
+        let bishops = boards[WB] | boards[BB];
+        let single_colour = is_superset(LIGHT_SQUARES, bishops) || bishops & LIGHT_SQUARES == 0;
+        let dead = (knights | bishops).count_ones() <= 1 || (knights == 0 && single_colour);
+        Some(TerminalState::Draw).filter(|_| dead)
     }
 
     fn check_repetitions(&self) -> Option<TerminalState> {
@@ -416,6 +633,47 @@ impl Position {
             .fold(0u64, |a, n| a | n)
             | board::pawn_control(side, self.piece_boards[if side == W { WP } else { BP }])
     }
+
+    /// The locations of all pieces of the given side which directly attack the given square
+    pub fn attackers_to(&self, square: Square, side: Side) -> Board {
+        let occupied = self.side_boards[W] | self.side_boards[B];
+        (0..6)
+            .map(|class| create_piece(side, class))
+            // Reflect the piece when taking control or won't work for pawns
+            .map(|p| self.piece_boards[p] & control(reflect_piece(p), square, occupied))
+            .fold(0u64, |a, n| a | n)
+    }
+
+    /// The cheapest piece of the given side which directly attacks the given square
+    pub fn least_valuable_attacker(&self, square: Square, side: Side) -> Option<(Piece, Square)> {
+        let attackers = self.attackers_to(square, side);
+        (0..6)
+            .map(|class| create_piece(side, class))
+            .find(|&p| intersects(self.piece_boards[p], attackers))
+            .map(|p| (p, (self.piece_boards[p] & attackers).trailing_zeros() as Square))
+    }
+
+    /// The pieces of the given side, other than the king, which are attacked by the enemy
+    /// and insufficiently defended. That is capturing with the cheapest attacker wins
+    /// material once both sides have exchanged on the square for as long as profitable.
+    pub fn hanging_pieces(&self, side: Side) -> Vec<(Piece, Square)> {
+        let material = MaterialFacet::default();
+        let enemy = reflect_side(side);
+        (0..5)
+            .map(|class| create_piece(side, class))
+            .flat_map(|p| iter(self.piece_boards[p]).map(move |sq| (p, sq)))
+            .filter(|&(_, sq)| {
+                self.least_valuable_attacker(sq, enemy).is_some_and(|(_, from)| {
+                    see::exchange_value(self, from, sq, material.mid_values()) > 0
+                })
+            })
+            .collect()
+    }
+
+    /// The piece counts for each side, used to identify specific material configurations
+    pub fn material_signature(&self) -> MaterialSignature {
+        MaterialSignature::new(std::array::from_fn(|p| self.piece_boards[p].count_ones()))
+    }
 }
 
 fn intersect_into(left: &mut ConstrainedPieces, right: &Constraints) {
@@ -435,6 +693,39 @@ enum CastlingMoveMode {
 }
 
 impl Position {
+    /// Every legal move in this position, the same list the search works from
+    pub fn legal_moves(&self) -> Vec<Move> {
+        self.moves(&Moves::All)
+    }
+
+    /// The legal moves which are not captures, promotions or checks
+    pub fn quiet_moves(&self) -> Vec<Move> {
+        let tactical = self.moves(&Moves::AreAny(&[
+            MoveFacet::Attacking,
+            MoveFacet::Checking,
+            MoveFacet::Promoting,
+        ]));
+        self.moves(&Moves::All).into_iter().filter(|m| !tactical.contains(m)).collect()
+    }
+
+    /// The legal moves of the active king only, including castling. This is much cheaper
+    /// than filtering the full move list as no pin or check constraints are needed, the
+    /// passive control is computed with our king removed so squares further along the
+    /// line of a checking slider are correctly excluded.
+    pub fn king_moves(&self) -> Vec<Move> {
+        let king = create_piece(self.active, class::K);
+        let friendly = self.side_boards[self.active];
+        let enemy = self.side_boards[reflect_side(self.active)];
+        let passive_control = self.passive_control;
+        iter(self.piece_boards[king])
+            .flat_map(|sq| {
+                let moves = board_moves(king, sq, friendly, enemy) & !passive_control;
+                self.create_normal_moves(king, sq, moves)
+            })
+            .chain(self.compute_castle_moves(passive_control, CastlingMoveMode::All))
+            .collect()
+    }
+
     // TODO We currently miss promotions which cause check if we specify checking facet only,
     //  we probably need a rethink to handle this although not an issue atm because we never
     //  specify the checking facet without also the promoting facet. For each move type we need
@@ -453,15 +744,7 @@ impl Position {
 
         // The set of constraints for each piece on the board to avoid illegal moves
         let mut constraints = if in_check {
-            let attacker_side = reflect_side(active);
-            let occupied = self.side_boards[side::W] | self.side_boards[side::B];
-            let king_attackers = (0..5)
-                .map(|class| create_piece(attacker_side, class))
-                // Reflect the piece when taking control or won't work for pawns
-                .map(|p| (p, self.piece_boards[p] & control(reflect_piece(p), active_king_loc, 0)))
-                .flat_map(|(p, b)| iter(b).map(move |sq| (p, sq)))
-                .filter(|(p, sq)| in_board(control(*p, *sq, occupied), active_king_loc))
-                .fold(0u64, |a, (_, n)| a | lift(n));
+            let king_attackers = self.attackers_to(active_king_loc, reflect_side(active));
 
             if king_attackers.count_ones() == 1 {
                 // We can move out of check or block the check, we still need to take pins into account
@@ -581,14 +864,16 @@ impl Position {
     ) -> impl Iterator<Item = Move> + 'a {
         self.castling_rights.iter().enumerate().filter(|&(_, &allowed)| allowed).filter_map(
             move |(corner, _)| {
-                let details = &CASTLING_DETAILS[corner];
+                let (king_square, rook_square) = self.castling_squares[corner];
+                let details = CastlingDetails::new(corner, king_square, rook_square);
                 let king = create_piece(self.active, class::K);
                 let rook = create_piece(self.active, class::R);
                 let occupied = union_boards(&self.side_boards);
                 if !intersects(passive_control, details.no_control)
                     && !intersects(occupied, details.no_piece)
-                    && self.piece_locs[details.king_line.0] == Some(king)
-                    && self.piece_locs[details.rook_line.0] == Some(rook)
+                    && self.piece_locs[king_square] == Some(king)
+                    && self.piece_locs[rook_square] == Some(rook)
+                    && !self.castling_rook_shields_king(&details)
                     && match mode {
                         CastlingMoveMode::All => true,
                         CastlingMoveMode::None => false,
@@ -598,7 +883,7 @@ impl Position {
                         ),
                     }
                 {
-                    Some(Castle { corner })
+                    Some(Castle { corner, king: king_square, rook: rook_square })
                 } else {
                     None
                 }
@@ -606,6 +891,20 @@ impl Position {
         )
     }
 
+    /// In Chess960 the castling rook can stand between the target square of the king
+    /// and an enemy rook or queen on the back rank, the passive control was computed
+    /// with the rook in place so it misses this attack.
+    fn castling_rook_shields_king(&self, details: &CastlingDetails) -> bool {
+        let passive = reflect_side(self.active);
+        let (king_source, king_target) = details.king_line;
+        let (rook_source, rook_target) = details.rook_line;
+        let attackers = self.piece_boards[create_piece(passive, class::R)]
+            | self.piece_boards[create_piece(passive, class::Q)];
+        let occupied = (union_boards(&self.side_boards) & !lift(king_source) & !lift(rook_source))
+            | lift(rook_target);
+        intersects(control(create_piece(passive, class::R), king_target, occupied), attackers)
+    }
+
     fn compute_pawn_moves<'a>(
         &'a self,
         constraints: &'a Constraints,
@@ -697,48 +996,13 @@ impl Position {
     }
 }
 
-fn rights_removed<'a>(square: Square) -> &'a [Corner] {
-    match square {
-        H1 => &[corner::WK],
-        E1 => &[corner::WK, corner::WQ],
-        A1 => &[corner::WQ],
-        H8 => &[corner::BK],
-        E8 => &[corner::BK, corner::BQ],
-        A8 => &[corner::BQ],
-        _ => &[],
-    }
-}
+/// The king and rook start squares for each corner in standard chess
+pub const STANDARD_CASTLING_SQUARES: CornerMap<(Square, Square)> =
+    [(E1, H1), (E1, A1), (E8, H8), (E8, A8)];
 
-#[rustfmt::skip]
-pub const CASTLING_DETAILS: CornerMap<CastlingDetails> = {
-    use crate::constants::square::*;
-    [
-        CastlingDetails {
-            king_line: (E1, G1),
-            rook_line: (H1, F1),
-            no_piece: board!(F1 => G1),
-            no_control: board!(E1 => G1)
-        },
-        CastlingDetails {
-            king_line: (E1, C1),
-            rook_line: (A1, D1),
-            no_piece: board!(D1 => B1),
-            no_control: board!(E1 => C1)
-        },
-        CastlingDetails {
-            king_line: (E8, G8),
-            rook_line: (H8, F8),
-            no_piece: board!(F8 => G8),
-            no_control: board!(E8 => G8)
-        },
-        CastlingDetails {
-            king_line: (E8, C8),
-            rook_line: (A8, D8),
-            no_piece: board!(D8 => B8),
-            no_control: board!(E8 => C8)
-        },
-    ]
-};
+/// The king and rook target squares for each corner, these are the same in standard
+/// chess and Chess960.
+const CASTLING_TARGETS: CornerMap<(Square, Square)> = [(G1, F1), (C1, D1), (G8, F8), (C8, D8)];
 
 pub struct CastlingDetails {
     pub king_line: (Square, Square),
@@ -746,3 +1010,16 @@ pub struct CastlingDetails {
     pub no_piece: Board,
     pub no_control: Board,
 }
+
+impl CastlingDetails {
+    pub fn new(corner: Corner, king: Square, rook: Square) -> CastlingDetails {
+        let (king_target, rook_target) = CASTLING_TARGETS[corner];
+        let travelled = cord(king, king_target) | cord(rook, rook_target);
+        CastlingDetails {
+            king_line: (king, king_target),
+            rook_line: (rook, rook_target),
+            no_piece: travelled & !lift(king) & !lift(rook),
+            no_control: cord(king, king_target),
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use std::sync::Arc;
+use std::sync::mpsc;
+
+use anyhow::{Result, anyhow};
+use threadpool::ThreadPool;
+
+use crate::SideMap;
+use crate::moves::{Move, Moves};
+use crate::node;
+use crate::node::TreeNode;
+use crate::position::Position;
+use crate::search::end::{SearchEndSignal, SharedEndSignal};
+use crate::search::moves::{HistoryTable, MoveGenerator};
+use crate::search::pv::PrincipleVariation;
+use crate::search::quiescent;
+use crate::search::search::{Context, KillerMoves, MAX_CHECK_EXTENSIONS, TreeSearcher};
+use crate::search::table::Transpositions;
+use crate::search::{DEPTH_UPPER_BOUND, SearchParameters, SearchStats};
+
+/// Evaluate every legal move in the given position on the calling thread. The
+/// result pairs each move with its evaluation relative to the side making it and
+/// is ordered from best to worst.
+pub fn evaluate_all_root_moves<E, T>(
+    position: Position,
+    parameters: SearchParameters<E, T>,
+) -> Result<Vec<(Move, i32)>>
+where
+    E: SearchEndSignal + Clone,
+    T: Transpositions,
+{
+    parameters.table.new_search();
+    let max_depth = parameters.max_depth.unwrap_or(DEPTH_UPPER_BOUND);
+    let verify_null_move = parameters.verify_null_move;
+    let draw_values = parameters.contempt.draw_values(&position);
+    let mut evaluations = position
+        .moves(&Moves::All)
+        .into_iter()
+        .map(|m| {
+            let eval = evaluate_root_move(
+                &position,
+                &m,
+                &parameters.end_signal,
+                parameters.table.clone(),
+                max_depth,
+                verify_null_move,
+                draw_values,
+            )?;
+            Ok((m, eval))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    sort_best_first(&mut evaluations);
+    Ok(evaluations)
+}
+
+/// Parallel version of [evaluate_all_root_moves] which searches each root move as
+/// a separate job on the given pool, all jobs share the same transposition table.
+/// As soon as any job observes the end signal every other job is stopped too.
+pub fn evaluate_all_root_moves_parallel<E, T>(
+    position: Position,
+    parameters: SearchParameters<E, T>,
+    threads: &ThreadPool,
+) -> Result<Vec<(Move, i32)>>
+where
+    E: SearchEndSignal + Clone + Send + 'static,
+    T: Transpositions + Send + Sync + 'static,
+{
+    parameters.table.new_search();
+    let max_depth = parameters.max_depth.unwrap_or(DEPTH_UPPER_BOUND);
+    let verify_null_move = parameters.verify_null_move;
+    let draw_values = parameters.contempt.draw_values(&position);
+    let end = SharedEndSignal::new(parameters.end_signal);
+    let moves = position.moves(&Moves::All);
+    let move_count = moves.len();
+    let (tx, rx) = mpsc::channel();
+    for m in moves {
+        let tx = tx.clone();
+        let position = position.clone();
+        let end = end.clone();
+        let table = parameters.table.clone();
+        threads.execute(move || {
+            let result = evaluate_root_move(
+                &position,
+                &m,
+                &end,
+                table,
+                max_depth,
+                verify_null_move,
+                draw_values,
+            );
+            // The receiver is dropped early if another job has already failed
+            tx.send(result.map(|eval| (m, eval))).ok();
+        });
+    }
+    drop(tx);
+    let mut evaluations = rx.iter().collect::<Result<Vec<_>>>().inspect_err(|_| end.stop())?;
+    if evaluations.len() != move_count {
+        return Err(anyhow!("Only {} of {} root moves evaluated", evaluations.len(), move_count));
+    }
+    sort_best_first(&mut evaluations);
+    Ok(evaluations)
+}
+
+/// Iteratively deepen the search of a single root move, returning the evaluation
+/// from the deepest search which completed before the end signal fired.
+fn evaluate_root_move<E: SearchEndSignal + Clone, T: Transpositions>(
+    position: &Position,
+    m: &Move,
+    end: &E,
+    table: Arc<T>,
+    max_depth: u8,
+    verify_null_move: Option<u8>,
+    draw_values: SideMap<i32>,
+) -> Result<i32> {
+    let root_index = position.history.len() as u16;
+    let mut node: TreeNode = position.clone().into();
+    node.make(m.clone())?;
+    let mut eval = Err(anyhow!("Terminated before {} was evaluated", m));
+    // The root move itself accounts for one ply of depth
+    for depth in 0..max_depth {
+        let mut searcher = TreeSearcher {
+            end: end.clone(),
+            table: table.clone(),
+            moves: MoveGenerator::default(),
+            pv: PrincipleVariation::default(),
+            node_counter: 0,
+            nodes: 0,
+            stats: SearchStats::default(),
+            node_limit: None,
+            pv_node_count: 0,
+            off_pv: false,
+            pv_table_hit: false,
+            killers: KillerMoves::default(),
+            history: HistoryTable::default(),
+            max_extensions: MAX_CHECK_EXTENSIONS,
+            max_evasion_plies: quiescent::MAX_EVASION_PLIES,
+            null_move_verification_depth: verify_null_move,
+            captures_only: false,
+            draw_values,
+            stall_nodes: None,
+            stall_report: None,
+        };
+        let context = Context {
+            depth,
+            alpha: -node::INFTY,
+            beta: node::INFTY,
+            known_raise_alpha: None,
+            root_index,
+            null_move_last: false,
+            ply: 0,
+            extensions: 0,
+            on_pv: false,
+        };
+        match searcher.search(&mut node, context) {
+            Err(_) => break,
+            Ok(response) => {
+                eval = Ok(-response.eval);
+                // Forced checkmate found, searching deeper won't change anything
+                if response.eval.abs() == node::WIN_VALUE {
+                    break;
+                }
+            }
+        }
+    }
+    eval
+}
+
+/// Order by descending eval, ties are broken on the move itself so the ordering is
+/// independent of the order the evaluations were computed in.
+fn sort_best_first(evaluations: &mut [(Move, i32)]) {
+    evaluations.sort_by(|(ma, ea), (mb, eb)| eb.cmp(ea).then_with(|| ma.cmp(mb)));
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use threadpool::ThreadPool;
+
+    use crate::moves::{Move, Moves};
+    use crate::position::Position;
+    use crate::search::end::EmptyEndSignal;
+    use crate::search::{ConcurrentTT, SearchParameters};
+
+    use super::{evaluate_all_root_moves, evaluate_all_root_moves_parallel};
+
+    const TABLE_SIZE: usize = 10_000;
+
+    fn parameters(depth: u8) -> SearchParameters<EmptyEndSignal, ConcurrentTT> {
+        SearchParameters {
+            max_depth: Some(depth),
+            ..SearchParameters::new(EmptyEndSignal, Arc::new(ConcurrentTT::new(TABLE_SIZE)))
+        }
+    }
+
+    fn ordering(evaluations: &[(Move, i32)]) -> Vec<Move> {
+        evaluations.iter().map(|(m, _)| m.clone()).collect()
+    }
+
+    #[test]
+    fn parallel_matches_sequential() {
+        let position: Position =
+            "r5k1/pb4pp/1pn1pq2/5B2/2Pr4/B7/PP3RPP/R4QK1 b - - 0 23".parse().unwrap();
+        let sequential = evaluate_all_root_moves(position.clone(), parameters(3)).unwrap();
+        let parallel =
+            evaluate_all_root_moves_parallel(position.clone(), parameters(3), &ThreadPool::new(4))
+                .unwrap();
+
+        assert_eq!(position.moves(&Moves::All).len(), sequential.len());
+        assert_eq!(ordering(&sequential), ordering(&parallel));
+        assert_eq!("e6f5", sequential[0].0.to_string());
+    }
+}
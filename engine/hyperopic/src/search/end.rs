@@ -1,6 +1,11 @@
 use std::cmp::max;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
 use std::time::{Duration, Instant, SystemTime};
 
+/// How often a composite or ponder signal checks whether it has ended while joining
+const JOIN_POLL: Duration = Duration::from_millis(1);
+
 /// A type which can be used to stop a search gracefully at any time.
 pub trait SearchEndSignal {
     /// The returned flag indicates to the search whether it should immediately stop
@@ -30,7 +35,7 @@ impl SearchEndSignal for SystemTime {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct EmptyEndSignal;
 
 impl SearchEndSignal for EmptyEndSignal {
@@ -40,3 +45,178 @@ impl SearchEndSignal for EmptyEndSignal {
 
     fn join(&self) -> () {}
 }
+
+/// Wraps an end signal so that once any holder observes the end every clone
+/// of this signal will also report the end.
+#[derive(Clone)]
+pub(crate) struct SharedEndSignal<E: SearchEndSignal> {
+    inner: E,
+    stopped: Arc<AtomicBool>,
+}
+
+impl<E: SearchEndSignal> SharedEndSignal<E> {
+    pub(crate) fn new(inner: E) -> Self {
+        SharedEndSignal { inner, stopped: Arc::new(AtomicBool::new(false)) }
+    }
+
+    pub(crate) fn stop(&self) {
+        self.stopped.store(true, Ordering::SeqCst)
+    }
+}
+
+impl<E: SearchEndSignal> SearchEndSignal for SharedEndSignal<E> {
+    fn should_end_now(&self) -> bool {
+        if self.stopped.load(Ordering::SeqCst) {
+            true
+        } else if self.inner.should_end_now() {
+            self.stop();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn join(&self) {
+        self.inner.join()
+    }
+}
+
+/// Combines any number of end signals, the search should end as soon as any one
+/// of the components says it should.
+#[derive(Clone, Default)]
+pub struct CompositeEndSignal {
+    signals: Vec<Arc<dyn SearchEndSignal + Send + Sync>>,
+}
+
+impl CompositeEndSignal {
+    pub fn new(signals: Vec<Arc<dyn SearchEndSignal + Send + Sync>>) -> Self {
+        CompositeEndSignal { signals }
+    }
+}
+
+impl SearchEndSignal for CompositeEndSignal {
+    fn should_end_now(&self) -> bool {
+        self.signals.iter().any(|s| s.should_end_now())
+    }
+
+    /// The components can only be waited on one at a time so instead we poll them
+    /// until the earliest one fires. With no components this returns immediately.
+    fn join(&self) {
+        while !self.signals.is_empty() && !self.should_end_now() {
+            std::thread::sleep(JOIN_POLL);
+        }
+    }
+}
+
+/// Signal for a ponder search which never ends by itself, once [PonderEndSignal::hit]
+/// is called it ends after the given search time like a normal timed search.
+#[derive(Clone, Debug, Default)]
+pub struct PonderEndSignal {
+    deadline: Arc<OnceLock<Instant>>,
+    /// Set once the search has nothing left to do and is only waiting for the hit
+    finished: Arc<AtomicBool>,
+}
+
+impl PonderEndSignal {
+    /// Convert into a time limited search, this returns false if already converted. If
+    /// the search has already finished, e.g. by finding a forced mate, it ends at once.
+    pub fn hit(&self, search_time: Duration) -> bool {
+        let search_time = if self.is_finished() { Duration::ZERO } else { search_time };
+        self.deadline.set(Instant::now() + search_time).is_ok()
+    }
+
+    /// Mark the search as finished so a later hit does not wait for no reason
+    pub(crate) fn finish(&self) {
+        self.finished.store(true, Ordering::SeqCst)
+    }
+
+    pub(crate) fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::SeqCst)
+    }
+}
+
+impl SearchEndSignal for PonderEndSignal {
+    fn should_end_now(&self) -> bool {
+        self.deadline.get().is_some_and(|deadline| deadline.should_end_now())
+    }
+
+    fn join(&self) {
+        while !self.should_end_now() {
+            std::thread::sleep(JOIN_POLL);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{Duration, Instant};
+
+    use super::{CompositeEndSignal, PonderEndSignal, SearchEndSignal};
+
+    /// Ends once it has been polled a fixed number of times, the search polls the
+    /// end signal at a fixed node frequency so this acts as a node limit.
+    struct NodeCountSignal {
+        polls: AtomicUsize,
+        limit: usize,
+    }
+
+    impl SearchEndSignal for NodeCountSignal {
+        fn should_end_now(&self) -> bool {
+            self.polls.fetch_add(1, Ordering::SeqCst) + 1 >= self.limit
+        }
+
+        fn join(&self) {}
+    }
+
+    fn composite(deadline: Instant, limit: usize) -> CompositeEndSignal {
+        CompositeEndSignal::new(vec![
+            Arc::new(deadline),
+            Arc::new(NodeCountSignal { polls: AtomicUsize::new(0), limit }),
+        ])
+    }
+
+    #[test]
+    fn node_count_fires_first() {
+        let signal = composite(Instant::now() + Duration::from_secs(60), 3);
+        assert!(!signal.should_end_now());
+        assert!(!signal.should_end_now());
+        assert!(signal.should_end_now());
+    }
+
+    #[test]
+    fn deadline_fires_first() {
+        let signal = composite(Instant::now() + Duration::from_millis(20), usize::MAX);
+        assert!(!signal.should_end_now());
+        signal.join();
+        assert!(signal.should_end_now());
+    }
+
+    #[test]
+    fn empty_never_ends() {
+        let signal = CompositeEndSignal::default();
+        signal.join();
+        assert!(!signal.should_end_now());
+    }
+
+    #[test]
+    fn ponder_ends_after_hit() {
+        let signal = PonderEndSignal::default();
+        assert!(!signal.should_end_now());
+        assert!(signal.hit(Duration::from_millis(20)));
+        assert!(!signal.hit(Duration::ZERO));
+        assert!(!signal.should_end_now());
+        signal.join();
+        assert!(signal.should_end_now());
+    }
+
+    #[test]
+    fn finished_ponder_ends_at_hit() {
+        let signal = PonderEndSignal::default();
+        signal.finish();
+        assert!(!signal.should_end_now());
+        assert!(signal.hit(Duration::from_secs(60)));
+        assert!(signal.should_end_now());
+    }
+}
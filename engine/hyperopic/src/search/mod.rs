@@ -1,3 +1,5 @@
+use std::cmp::Reverse;
+use std::fmt::{Debug, Formatter};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
@@ -5,52 +7,341 @@ use serde::Serializer;
 use serde::ser::SerializeStruct;
 
 use anyhow::{Result, anyhow};
-use end::SearchEndSignal;
+use end::{SearchEndSignal, SharedEndSignal};
 
-use crate::moves::Move;
+use crate::SideMap;
+use crate::Square;
+use crate::constants::{square_file, square_rank};
+use crate::moves::{Move, Moves};
 use crate::node;
 use crate::node::TreeNode;
-use crate::search::moves::MoveGenerator;
+use crate::phase::{game_phase, interpolate};
+use crate::position::{CastlingDetails, Position, TerminalState};
+use crate::search::moves::{HistoryTable, MoveGenerator};
 use crate::search::pv::PrincipleVariation;
-use crate::search::search::{Context, SearchResponse, TreeSearcher};
-pub use crate::search::table::{NodeType, TableEntry, Transpositions, ConcurrentTT};
+use crate::search::search::{
+    Context, KillerMoves, MAX_CHECK_EXTENSIONS, SearchResponse, TreeSearcher,
+};
+pub use crate::search::analysis::{evaluate_all_root_moves, evaluate_all_root_moves_parallel};
+pub use crate::search::perft::{divide, perft};
+pub use crate::search::table::{CollisionStats, NodeType, TableEntry, Transpositions, ConcurrentTT};
 
+mod analysis;
 pub mod end;
 mod moves;
+mod perft;
 mod pv;
 pub mod quiescent;
 pub mod search;
 mod table;
 
 const DEPTH_UPPER_BOUND: u8 = 20;
+/// Number of consecutive iterations the best move must be unchanged to be an easy move
+const EASY_MOVE_STABLE_ITERATIONS: u8 = 3;
+/// How much better than every alternative the best move must be to be an easy move
+const EASY_MOVE_MARGIN: i32 = 1000;
+/// Initial half width of the aspiration window, roughly half a pawn
+const ASPIRATION_DELTA: i32 = 120;
+/// Factor the aspiration window half width grows by after each failed search
+const ASPIRATION_WIDENING: i32 = 4;
+/// Once the half width grows past this the failing side of the window is fully opened
+const ASPIRATION_MAX_DELTA: i32 = 2000;
+/// An iteration is stalled once it visits this many times the nodes predicted by the
+/// branching factor of the previous iterations
+const STALL_FACTOR: f64 = 4.0;
+/// Iterations are never reported as stalled below this many nodes, the branching factor
+/// of the first shallow iterations is too erratic to go on
+const MIN_STALL_NODES: u64 = 5000;
 
 /// API function for executing search on the calling thread, we pass a root
 /// state and a terminator and compute the best move we can make from this
 /// state within the duration constraints implied by the terminator.
-pub fn search<E: SearchEndSignal + Clone, T: Transpositions>(
+pub fn search<E: SearchEndSignal + Clone + Send, T: Transpositions + Send + Sync>(
     node: TreeNode,
     parameters: SearchParameters<E, T>,
 ) -> Result<SearchOutcome> {
-    let max_depth = parameters.max_depth.unwrap_or(DEPTH_UPPER_BOUND);
-    let transpositions = parameters.table;
-    Search { node, end: parameters.end_signal, transpositions, max_depth }.search()
+    Search::new(node, parameters).search()
+}
+
+/// API function for executing a search which returns up to [SearchParameters::multi_pv]
+/// principal variations, one for each of the best root moves. The first outcome is the
+/// one [search] would return and the rest are ordered from best to worst, there are
+/// fewer outcomes than requested if there are not enough legal moves.
+pub fn search_multi<E: SearchEndSignal + Clone + Send, T: Transpositions + Send + Sync>(
+    node: TreeNode,
+    parameters: SearchParameters<E, T>,
+) -> Result<Vec<SearchOutcome>> {
+    Search::new(node, parameters).search_multi()
 }
 
 pub struct SearchParameters<E: SearchEndSignal + Clone, T: Transpositions> {
     pub end_signal: E,
     pub table: Arc<T>,
     pub max_depth: Option<u8>,
+    /// The search ends once this many nodes have been visited across all iterations
+    pub max_nodes: Option<u64>,
+    /// Flag which when set means we keep deepening after finding a forced mate
+    /// until the length of the mating line stops decreasing, so the optimal path
+    /// reported is the shortest forced mate rather than the first one found.
+    pub find_shortest_mate: bool,
+    /// Checked between iterations, once it signals no further iteration is started.
+    /// Unlike the end signal it never interrupts an iteration, a clock limited search
+    /// sets it before the end signal as a late iteration is unlikely to complete.
+    pub soft_end: Option<Arc<dyn SearchEndSignal + Send + Sync>>,
+    /// Flag which when set allows one iteration beyond the soft end if the best move
+    /// changed in the final iteration, to confirm the new choice. The max depth and
+    /// end signal still bound the search.
+    pub confirm_unstable_best_move: bool,
+    /// Flag which when set allows the search to return before the max depth or end
+    /// signal if the best move has been stable and is far better than any other.
+    pub return_easy_moves: bool,
+    /// Flag which when set searches each iteration with a narrow window around the
+    /// eval of the previous iteration, widening it if the eval falls outside.
+    pub aspiration_windows: bool,
+    /// Flag which when set searches for the best alternative to the best move once
+    /// the main search has finished, see [SearchOutcome::alternative].
+    pub find_alternative: bool,
+    /// If set then null move cutoffs at nodes with at least this much remaining depth
+    /// are only accepted once confirmed by a reduced search of the real moves. This
+    /// is slower but avoids misjudging zugzwang positions where passing is best.
+    pub verify_null_move: Option<u8>,
+    /// The number of principal variations returned by [search_multi], it is ignored
+    /// by [search] which only ever returns the best one.
+    pub multi_pv: usize,
+    /// Called with the details of each iteration of the search as it completes
+    pub on_info: Option<InfoCallback>,
+    /// The number of threads searching the root position, any beyond the first are
+    /// helpers which only serve to fill the shared table for the main thread.
+    pub threads: usize,
+    /// How strongly the side to move at the root avoids draws
+    pub contempt: Contempt,
+    /// Flag which when set restricts every node to captures and promotions, unless in
+    /// check, with the side to move free to stand pat. This runs the quiescent search
+    /// from the root to a fixed depth and so measures the purely tactical value.
+    pub captures_only: bool,
+    /// Bound on the plies in check along a single quiescent line, past which the static
+    /// eval is returned rather than searching every evasion.
+    pub max_evasion_plies: u8,
+}
+
+impl<E: SearchEndSignal + Clone, T: Transpositions> SearchParameters<E, T> {
+    /// Parameters for a plain single threaded search with no depth or node limit and
+    /// every optional feature disabled, override the fields which should differ.
+    pub fn new(end_signal: E, table: Arc<T>) -> SearchParameters<E, T> {
+        SearchParameters {
+            end_signal,
+            table,
+            max_depth: None,
+            max_nodes: None,
+            soft_end: None,
+            find_shortest_mate: false,
+            confirm_unstable_best_move: false,
+            return_easy_moves: false,
+            aspiration_windows: false,
+            find_alternative: false,
+            verify_null_move: None,
+            multi_pv: 1,
+            on_info: None,
+            threads: 1,
+            contempt: Contempt::default(),
+            captures_only: false,
+            max_evasion_plies: quiescent::MAX_EVASION_PLIES,
+        }
+    }
+}
+
+/// Penalty applied to draws from the point of view of the side to move at the root,
+/// a positive contempt makes the search prefer playing on in roughly level positions.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct Contempt {
+    /// The penalty applied in the opening
+    pub value: i32,
+    /// Flag which when set reduces the penalty with the game phase so it reaches zero
+    /// once only pawns remain, late in the game a safe draw is worth taking.
+    pub decay: bool,
+}
+
+impl Contempt {
+    /// The penalty applied to draws in a search of the given root position
+    pub fn effective(&self, root: &Position) -> i32 {
+        if self.decay { interpolate(game_phase(root), self.value, 0) } else { self.value }
+    }
+
+    /// The eval of a draw relative to each side to move in a search of the given root
+    pub(crate) fn draw_values(&self, root: &Position) -> SideMap<i32> {
+        let contempt = self.effective(root);
+        let mut values = [node::DRAW_VALUE + contempt; 2];
+        values[root.active] = node::DRAW_VALUE - contempt;
+        values
+    }
+}
+
+/// Details of a single completed iteration of the iterative deepening search
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SearchInfo {
+    pub depth: u8,
+    /// Larger +ve score better for side to move
+    pub relative_eval: i32,
+    /// Time elapsed since the search began
+    pub time: Duration,
+    /// Total number of tree nodes visited since the search began
+    pub nodes: u64,
+    pub optimal_path: Vec<Move>,
+    /// Occupancy of the transposition table per mille
+    pub hashfull: u16,
+    /// Set when sent as soon as the iteration at this depth stalls, the eval and path
+    /// are then those of the previous iteration
+    pub stalled: bool,
+}
+
+impl SearchInfo {
+    /// The average number of nodes visited per second since the search began
+    pub fn nps(&self) -> u64 {
+        nodes_per_second(self.nodes, self.time)
+    }
+}
+
+/// Shared callback receiving [SearchInfo] during a search, two callbacks are only
+/// equal if they are the same instance.
+#[derive(Clone)]
+pub struct InfoCallback(Arc<dyn Fn(SearchInfo) + Send + Sync>);
+
+impl InfoCallback {
+    pub fn new<F: Fn(SearchInfo) + Send + Sync + 'static>(callback: F) -> Self {
+        InfoCallback(Arc::new(callback))
+    }
+
+    pub fn call(&self, info: SearchInfo) {
+        (self.0)(info)
+    }
+}
+
+impl Debug for InfoCallback {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "InfoCallback")
+    }
+}
+
+impl PartialEq for InfoCallback {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// The info sent through the callback once a tree search reaches the stall threshold
+#[derive(Clone)]
+pub struct StallReport {
+    on_info: InfoCallback,
+    /// Everything but the time and table occupancy, which are only known at the stall
+    info: SearchInfo,
+    search_start: Instant,
+}
+
+impl StallReport {
+    pub(crate) fn send(self, hashfull: u16) {
+        self.on_info.call(SearchInfo { time: self.search_start.elapsed(), hashfull, ..self.info })
+    }
 }
 
 /// Data class composing information/result about/of a best move search.
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct SearchOutcome {
+    /// The null move if the root position is already terminal
     pub best_move: Move,
     /// Larger +ve score better for side to move
     pub relative_eval: i32,
     pub depth: u8,
     pub time: Duration,
     pub optimal_path: Vec<Move>,
+    /// Total number of tree nodes visited across all iterations of the search, by the
+    /// main thread and any helpers
+    pub nodes: u64,
+    /// The best root move other than the best move with its eval at the same depth,
+    /// only searched for on request
+    pub alternative: Option<(Move, i32)>,
+    /// Diagnostic counters for the search, absent if the outcome did not come from one
+    pub stats: Option<SearchStats>,
+}
+
+/// Counters gathered by the main search thread which show how effective the
+/// aspiration windows, transposition table and null move pruning are.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub struct SearchStats {
+    /// Nodes visited by the main thread, unlike [SearchOutcome::nodes] this excludes
+    /// helper threads so the other counters can be compared against it
+    pub nodes: u64,
+    /// Root searches repeated after failing outside the aspiration window
+    pub aspiration_researches: u64,
+    pub table_probes: u64,
+    /// Probes which found an entry for the position, whether or not it caused a cutoff
+    pub table_hits: u64,
+    pub null_move_searches: u64,
+    pub null_move_cutoffs: u64,
+    /// Iterations which visited far more nodes than the previous iterations predicted,
+    /// a warning is logged as soon as each is detected as it suggests a search explosion
+    pub stalled_iterations: u64,
+}
+
+impl SearchStats {
+    pub fn table_hit_rate(&self) -> f64 {
+        rate(self.table_hits, self.table_probes)
+    }
+
+    pub fn null_move_cutoff_rate(&self) -> f64 {
+        rate(self.null_move_cutoffs, self.null_move_searches)
+    }
+
+    fn add(&mut self, other: &SearchStats) {
+        self.nodes += other.nodes;
+        self.aspiration_researches += other.aspiration_researches;
+        self.table_probes += other.table_probes;
+        self.table_hits += other.table_hits;
+        self.null_move_searches += other.null_move_searches;
+        self.null_move_cutoffs += other.null_move_cutoffs;
+        self.stalled_iterations += other.stalled_iterations;
+    }
+}
+
+fn rate(count: u64, total: u64) -> f64 {
+    if total == 0 { 0.0 } else { count as f64 / total as f64 }
+}
+
+impl serde::Serialize for SearchStats {
+    fn serialize<S>(&self, serializer: S) -> Result<<S as Serializer>::Ok, <S as Serializer>::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("SearchStats", 4)?;
+        state.serialize_field("nodes", &self.nodes)?;
+        state.serialize_field("aspirationResearches", &self.aspiration_researches)?;
+        state.serialize_field("tableHitRate", &self.table_hit_rate())?;
+        state.serialize_field("nullMoveCutoffRate", &self.null_move_cutoff_rate())?;
+        state.end()
+    }
+}
+
+impl SearchOutcome {
+    /// The average number of nodes visited per second of search time
+    pub fn nps(&self) -> u64 {
+        nodes_per_second(self.nodes, self.time)
+    }
+}
+
+fn nodes_per_second(nodes: u64, time: Duration) -> u64 {
+    // Searches taking under a millisecond are rounded up to avoid dividing by zero
+    (nodes as u128 * 1000 / time.as_millis().max(1)) as u64
+}
+
+/// The number of nodes after which the next iteration is stalled given the nodes visited
+/// by each completed iteration, the branching factor is estimated from the last two.
+fn stall_threshold(iteration_nodes: &[u64]) -> Option<u64> {
+    match iteration_nodes {
+        [.., before, last] if *before > 0 => {
+            let branching = (*last as f64 / *before as f64).max(1.0);
+            Some(((*last as f64 * branching * STALL_FACTOR) as u64).max(MIN_STALL_NODES))
+        }
+        _ => None,
+    }
 }
 
 impl serde::Serialize for SearchOutcome {
@@ -67,6 +358,9 @@ impl serde::Serialize for SearchOutcome {
             "optimalPath",
             &self.optimal_path.iter().map(|m| m.to_string()).collect::<Vec<_>>(),
         )?;
+        if let Some(stats) = self.stats.as_ref() {
+            state.serialize_field("stats", stats)?;
+        }
         state.end()
     }
 }
@@ -81,17 +375,17 @@ mod searchoutcome_serialize_test {
     use crate::constants::{class, corner, side, square};
     use crate::moves::Move;
 
-    use super::SearchOutcome;
+    use super::{SearchOutcome, SearchStats};
 
     #[test]
     fn test_json_serialize() {
         let search_outcome = SearchOutcome {
-            best_move: Move::Castle { corner: corner::WK },
+            best_move: Move::Castle { corner: corner::WK, king: square::E1, rook: square::H1 },
             relative_eval: -125,
             depth: 2,
             time: Duration::from_millis(3000),
             optimal_path: vec![
-                Move::Castle { corner: corner::WK },
+                Move::Castle { corner: corner::WK, king: square::E1, rook: square::H1 },
                 Move::Normal {
                     moving: create_piece(side::B, class::P),
                     from: square::D7,
@@ -99,12 +393,42 @@ mod searchoutcome_serialize_test {
                     capture: None,
                 },
             ],
+            nodes: 1234,
+            alternative: None,
+            stats: None,
         };
         assert_eq!(
             r#"{"bestMove":"e1g1","positionEval":-125,"depthSearched":2,"searchDurationMillis":3000,"optimalPath":["e1g1","d7d5"]}"#,
             serde_json::to_string(&search_outcome).expect("Serialization failed")
         );
     }
+
+    #[test]
+    fn test_json_serialize_with_stats() {
+        let best_move = Move::Castle { corner: corner::WK, king: square::E1, rook: square::H1 };
+        let search_outcome = SearchOutcome {
+            best_move: best_move.clone(),
+            relative_eval: 30,
+            depth: 4,
+            time: Duration::from_millis(500),
+            optimal_path: vec![best_move],
+            nodes: 2000,
+            alternative: None,
+            stats: Some(SearchStats {
+                nodes: 2000,
+                aspiration_researches: 1,
+                table_probes: 400,
+                table_hits: 100,
+                null_move_searches: 50,
+                null_move_cutoffs: 40,
+                stalled_iterations: 0,
+            }),
+        };
+        assert_eq!(
+            r#"{"bestMove":"e1g1","positionEval":30,"depthSearched":4,"searchDurationMillis":500,"optimalPath":["e1g1"],"stats":{"nodes":2000,"aspirationResearches":1,"tableHitRate":0.25,"nullMoveCutoffRate":0.8}}"#,
+            serde_json::to_string(&search_outcome).expect("Serialization failed")
+        );
+    }
 }
 
 struct Search<E: SearchEndSignal, T: Transpositions> {
@@ -112,6 +436,84 @@ struct Search<E: SearchEndSignal, T: Transpositions> {
     end: E,
     transpositions: Arc<T>,
     max_depth: u8,
+    soft_end: Option<Arc<dyn SearchEndSignal + Send + Sync>>,
+    find_shortest_mate: bool,
+    confirm_unstable_best_move: bool,
+    return_easy_moves: bool,
+    aspiration_windows: bool,
+    find_alternative: bool,
+    max_nodes: Option<u64>,
+    verify_null_move: Option<u8>,
+    multi_pv: usize,
+    on_info: Option<InfoCallback>,
+    threads: usize,
+    captures_only: bool,
+    max_evasion_plies: u8,
+    draw_values: SideMap<i32>,
+    /// The depth of the first iteration, helper threads vary this to diverge
+    start_depth: u8,
+    nodes: u64,
+    stats: SearchStats,
+    /// Quiet move history carried between the iterations of the search, lent to each
+    /// tree search in turn
+    history: HistoryTable,
+    /// Flag which when set warns about iterations taking far longer than predicted
+    watch_stalls: bool,
+    /// The total node count at which the current iteration is stalled
+    stall_at: Option<u64>,
+    /// Sent by the tree search which reaches the stall threshold
+    stall_report: Option<StallReport>,
+}
+
+/// Everything a helper thread needs to build its own search, the tree node cannot be
+/// shared between threads so each helper creates one from the root position.
+struct HelperSearch<E: SearchEndSignal, T: Transpositions> {
+    position: Position,
+    end: SharedEndSignal<E>,
+    transpositions: Arc<T>,
+    max_depth: u8,
+    max_nodes: Option<u64>,
+    find_shortest_mate: bool,
+    verify_null_move: Option<u8>,
+    captures_only: bool,
+    max_evasion_plies: u8,
+    draw_values: SideMap<i32>,
+    start_depth: u8,
+}
+
+impl<E: SearchEndSignal + Clone + Send, T: Transpositions + Send + Sync> HelperSearch<E, T> {
+    /// Search until the max depth or until stopped, returning the nodes visited
+    fn run(&mut self) -> u64 {
+        let mut search = Search {
+            node: self.position.clone().into(),
+            end: self.end.clone(),
+            transpositions: self.transpositions.clone(),
+            max_depth: self.max_depth,
+            soft_end: None,
+            find_shortest_mate: self.find_shortest_mate,
+            confirm_unstable_best_move: false,
+            return_easy_moves: false,
+            aspiration_windows: false,
+            find_alternative: false,
+            max_nodes: self.max_nodes,
+            verify_null_move: self.verify_null_move,
+            multi_pv: 1,
+            on_info: None,
+            threads: 1,
+            captures_only: self.captures_only,
+            max_evasion_plies: self.max_evasion_plies,
+            draw_values: self.draw_values,
+            start_depth: self.start_depth,
+            nodes: 0,
+            stats: SearchStats::default(),
+            history: HistoryTable::default(),
+            watch_stalls: false,
+            stall_at: None,
+            stall_report: None,
+        };
+        search.search_iteratively().ok();
+        search.nodes
+    }
 }
 
 struct BestMoveResponse {
@@ -121,77 +523,584 @@ struct BestMoveResponse {
     depth: u8,
 }
 
-impl<E: SearchEndSignal + Clone, T: Transpositions> Search<E, T> {
+impl<E: SearchEndSignal + Clone + Send, T: Transpositions + Send + Sync> Search<E, T> {
+    fn new(node: TreeNode, parameters: SearchParameters<E, T>) -> Self {
+        let draw_values = parameters.contempt.draw_values(node.position());
+        parameters.table.new_search();
+        Search {
+            node,
+            end: parameters.end_signal,
+            transpositions: parameters.table,
+            max_depth: parameters.max_depth.unwrap_or(DEPTH_UPPER_BOUND),
+            soft_end: parameters.soft_end,
+            find_shortest_mate: parameters.find_shortest_mate,
+            confirm_unstable_best_move: parameters.confirm_unstable_best_move,
+            return_easy_moves: parameters.return_easy_moves,
+            aspiration_windows: parameters.aspiration_windows,
+            find_alternative: parameters.find_alternative,
+            max_nodes: parameters.max_nodes,
+            verify_null_move: parameters.verify_null_move,
+            multi_pv: parameters.multi_pv,
+            on_info: parameters.on_info,
+            threads: parameters.threads,
+            captures_only: parameters.captures_only,
+            max_evasion_plies: parameters.max_evasion_plies,
+            draw_values,
+            start_depth: 1,
+            nodes: 0,
+            stats: SearchStats::default(),
+            history: HistoryTable::default(),
+            watch_stalls: true,
+            stall_at: None,
+            stall_report: None,
+        }
+    }
+
+    pub fn search_multi(&mut self) -> Result<Vec<SearchOutcome>> {
+        let search_start = Instant::now();
+        let best = self.search()?;
+        if best.best_move == Move::Null || self.multi_pv <= 1 {
+            return Ok(vec![best]);
+        }
+        let mut lines = self.search_other_lines(&best);
+        lines.sort_by_key(|(_, eval)| Reverse(*eval));
+        let mut outcomes = vec![best.clone()];
+        outcomes.extend(lines.into_iter().take(self.multi_pv - 1).map(|(path, eval)| {
+            SearchOutcome {
+                best_move: path[0].clone(),
+                relative_eval: eval,
+                optimal_path: path,
+                alternative: None,
+                ..best.clone()
+            }
+        }));
+        // Every outcome reports the totals for the whole search
+        let time = search_start.elapsed();
+        outcomes.iter_mut().for_each(|o| {
+            o.time = time;
+            o.nodes = self.nodes;
+        });
+        Ok(outcomes)
+    }
+
+    /// Search every root move other than the best one with a full window at the same
+    /// depth as the best move, giving the exact eval and line for each. If the search
+    /// is terminated part way through only the lines completed so far are returned.
+    fn search_other_lines(&mut self, best: &SearchOutcome) -> Vec<(Vec<Move>, i32)> {
+        let root_index = self.node.position().history.len() as u16;
+        let others = self.node.position().moves(&Moves::All);
+        let mut lines = vec![];
+        for m in others.into_iter().filter(|m| m != &best.best_move) {
+            let mut searcher = self.create_searcher(PrincipleVariation::default());
+            let context = Context {
+                depth: best.depth - 1,
+                alpha: -node::INFTY,
+                beta: node::INFTY,
+                known_raise_alpha: None,
+                root_index,
+                null_move_last: false,
+                ply: 0,
+                extensions: 0,
+                on_pv: false,
+            };
+            if self.node.make(m.clone()).is_err() {
+                break;
+            }
+            let result = searcher.search(&mut self.node, context);
+            self.absorb(&mut searcher);
+            if self.node.unmake().is_err() {
+                break;
+            }
+            match result {
+                Ok(response) => {
+                    let mut path = response.path;
+                    path.insert(0, m);
+                    lines.push((path, -response.eval));
+                }
+                Err(_) => break,
+            }
+        }
+        lines
+    }
+
+    /// Lazy SMP, helper threads search the same root independently sharing only the
+    /// table. The main thread benefits from the entries they leave and its outcome is
+    /// the one returned, helpers are stopped as soon as the main thread completes.
     pub fn search(&mut self) -> Result<SearchOutcome> {
+        if self.threads <= 1 {
+            return self.search_iteratively();
+        }
+        let helper_end = SharedEndSignal::new(self.end.clone());
+        let position = self.node.position().clone();
+        // Split any node limit so all the threads together stay within it, the main
+        // thread also takes what is left over from the even split
+        let threads = self.threads as u64;
+        let helper_max_nodes = self.max_nodes.map(|limit| limit / threads);
+        self.max_nodes = self.max_nodes.map(|limit| limit - (threads - 1) * (limit / threads));
+        std::thread::scope(|scope| {
+            let helpers = (1..self.threads)
+                .map(|index| {
+                    let mut helper = HelperSearch {
+                        position: position.clone(),
+                        end: helper_end.clone(),
+                        transpositions: self.transpositions.clone(),
+                        max_depth: self.max_depth,
+                        max_nodes: helper_max_nodes,
+                        find_shortest_mate: self.find_shortest_mate,
+                        verify_null_move: self.verify_null_move,
+                        captures_only: self.captures_only,
+                        max_evasion_plies: self.max_evasion_plies,
+                        draw_values: self.draw_values,
+                        // Half the helpers start a ply deeper so they are not in lockstep
+                        start_depth: 1 + (index % 2) as u8,
+                    };
+                    scope.spawn(move || helper.run())
+                })
+                .collect::<Vec<_>>();
+            let outcome = self.search_iteratively();
+            helper_end.stop();
+            let mut helper_nodes = 0;
+            for helper in helpers {
+                helper_nodes += helper.join().map_err(|_| anyhow!("Helper search panicked"))?;
+            }
+            outcome.map(|o| SearchOutcome { nodes: o.nodes + helper_nodes, ..o })
+        })
+    }
+
+    fn search_iteratively(&mut self) -> Result<SearchOutcome> {
         let search_start = Instant::now();
+        // There is nothing to search if the game is already over
+        if let Some(state) = self.node.position().compute_terminal_state() {
+            return Ok(SearchOutcome {
+                best_move: Move::Null,
+                relative_eval: match state {
+                    TerminalState::Loss => node::LOSS_VALUE,
+                    TerminalState::Draw => node::DRAW_VALUE,
+                },
+                depth: 0,
+                time: search_start.elapsed(),
+                optimal_path: vec![],
+                nodes: 0,
+                alternative: None,
+                stats: None,
+            });
+        }
         let mut break_err = anyhow!("Terminated before search began");
         let mut pv = PrincipleVariation::default();
-        let mut best_response = None;
-        for i in 1..=self.max_depth {
-            match self.best_move(i, &pv) {
+        let mut best_response: Option<BestMoveResponse> = None;
+        let mut stable_iterations = 0;
+        let mut confirming = false;
+        let mut iteration_nodes = vec![];
+        let mut i = self.start_depth;
+        while i <= self.max_depth {
+            let nodes_before = self.nodes;
+            self.stall_at = stall_threshold(&iteration_nodes)
+                .filter(|_| self.watch_stalls)
+                .map(|n| nodes_before + n);
+            self.stall_report =
+                self.on_info.clone().zip(self.stall_at).zip(best_response.as_ref()).map(
+                    |((on_info, stall_at), previous)| StallReport {
+                        on_info,
+                        info: SearchInfo {
+                            depth: i,
+                            relative_eval: previous.eval,
+                            time: Duration::ZERO,
+                            nodes: stall_at,
+                            optimal_path: previous.path.clone(),
+                            hashfull: 0,
+                            stalled: true,
+                        },
+                        search_start,
+                    },
+                );
+            match self.best_move(i, &pv, best_response.as_ref().map(|r| r.eval)) {
                 Err(message) => {
                     break_err = anyhow!("{}", message);
                     break;
                 }
                 Ok(response) => {
+                    iteration_nodes.push(self.nodes - nodes_before);
+                    if let Some(on_info) = self.on_info.as_ref() {
+                        on_info.call(SearchInfo {
+                            depth: response.depth,
+                            relative_eval: response.eval,
+                            time: search_start.elapsed(),
+                            nodes: self.nodes,
+                            optimal_path: response.path.clone(),
+                            hashfull: self.transpositions.hashfull(),
+                            stalled: false,
+                        });
+                    }
                     pv = PrincipleVariation { path: response.path.clone() };
-                    let eval = response.eval;
-                    best_response = Some(response);
-                    // Inevitable checkmate detected, don't search any deeper
-                    if eval.abs() == node::WIN_VALUE {
+                    let best_move_changed =
+                        best_response.as_ref().is_some_and(|r| r.best_move != response.best_move);
+                    stable_iterations = if best_move_changed { 1 } else { stable_iterations + 1 };
+                    let is_mate = response.eval.abs() == node::WIN_VALUE;
+                    if is_mate && self.find_shortest_mate {
+                        let previous_mate = best_response
+                            .as_ref()
+                            .filter(|r: &&BestMoveResponse| self.ends_in_checkmate(&r.path));
+                        // Stop once the full mating line no longer gets any shorter
+                        if let Some(previous) = previous_mate
+                            && (!self.ends_in_checkmate(&response.path)
+                                || response.path.len() >= previous.path.len())
+                        {
+                            break;
+                        }
+                        best_response = Some(response);
+                    } else {
+                        best_response = Some(response);
+                        // Inevitable checkmate detected, don't search any deeper
+                        if is_mate {
+                            break;
+                        }
+                    }
+                    if self.soft_end.as_ref().is_some_and(|end| end.should_end_now()) {
+                        // A late change of mind often means an unresolved tactic so confirm
+                        // the new move with one more iteration if the end signal allows it
+                        if confirming || !best_move_changed || !self.confirm_unstable_best_move {
+                            break;
+                        }
+                        confirming = true;
+                    }
+                    // Bank the clock if one move clearly dominates and has done for a while
+                    if self.return_easy_moves
+                        && i < self.max_depth
+                        && stable_iterations >= EASY_MOVE_STABLE_ITERATIONS
+                        && best_response.as_ref().is_some_and(|r| self.is_easy_move(r))
+                    {
                         break;
                     }
                 }
             }
+            i += 1;
         }
 
+        let alternative = match best_response.as_ref() {
+            Some(best) if self.find_alternative => self.search_alternative(best).ok().flatten(),
+            _ => None,
+        };
+
         best_response.ok_or(break_err).map(|response| SearchOutcome {
             best_move: response.best_move,
             relative_eval: response.eval,
             depth: response.depth,
             time: search_start.elapsed(),
             optimal_path: response.path,
+            nodes: self.nodes,
+            alternative,
+            stats: Some(SearchStats { nodes: self.nodes, ..self.stats }),
         })
     }
 
-    fn best_move(&mut self, depth: u8, pv: &PrincipleVariation) -> Result<BestMoveResponse> {
-        if depth < 1 {
-            return Err(anyhow!("Cannot iteratively deepen with depth 0"));
+    /// Check whether every root move other than the best one fails low against a bound
+    /// well below the best eval, using a null window search two plies shallower than
+    /// the iteration which found the best move.
+    fn is_easy_move(&mut self, best: &BestMoveResponse) -> bool {
+        let threshold = best.eval - EASY_MOVE_MARGIN;
+        let root_index = self.node.position().history.len() as u16;
+        let alternatives = self.node.position().moves(&Moves::All);
+        for m in alternatives.into_iter().filter(|m| m != &best.best_move) {
+            let mut searcher = self.create_searcher(PrincipleVariation::default());
+            let context = Context {
+                depth: best.depth.saturating_sub(2),
+                alpha: -threshold,
+                beta: -threshold + 1,
+                known_raise_alpha: None,
+                root_index,
+                null_move_last: false,
+                ply: 0,
+                extensions: 0,
+                on_pv: false,
+            };
+            if self.node.make(m).is_err() {
+                return false;
+            }
+            let result = searcher.search(&mut self.node, context);
+            self.absorb(&mut searcher);
+            if self.node.unmake().is_err() {
+                return false;
+            }
+            match result {
+                Ok(response) if -response.eval < threshold => {}
+                _ => return false,
+            }
         }
+        true
+    }
 
-        let root_index = self.node.position().history.len() as u16;
-        let mut searcher = TreeSearcher {
+    /// Add the nodes and stats of a finished tree search to the totals for this search
+    /// and take back the history table lent to it
+    fn absorb(&mut self, searcher: &mut TreeSearcher<E, T>) {
+        self.nodes += searcher.nodes;
+        self.history = std::mem::take(&mut searcher.history);
+        self.stats.add(&searcher.stats);
+        // Only warn once per iteration
+        if searcher.stats.stalled_iterations > 0 {
+            self.stall_at = None;
+            self.stall_report = None;
+        }
+    }
+
+    fn create_searcher(&mut self, pv: PrincipleVariation) -> TreeSearcher<E, T> {
+        TreeSearcher {
             end: self.end.clone(),
             table: self.transpositions.clone(),
             moves: MoveGenerator::default(),
-            pv: pv.clone(),
+            pv,
             node_counter: 0,
+            nodes: 0,
+            stats: SearchStats::default(),
+            node_limit: self.remaining_nodes(),
             pv_node_count: 0,
             off_pv: false,
-        };
-        
-        let SearchResponse { eval, path } = searcher.search(
-            &mut self.node,
-            Context {
-                depth,
+            pv_table_hit: false,
+            killers: KillerMoves::default(),
+            history: std::mem::take(&mut self.history),
+            // Extended mates are not bounded by the depth, so they would stop the search
+            // for the shortest mate before the mating line is shortest
+            max_extensions: if self.find_shortest_mate { 0 } else { MAX_CHECK_EXTENSIONS },
+            max_evasion_plies: self.max_evasion_plies,
+            null_move_verification_depth: self.verify_null_move,
+            captures_only: self.captures_only,
+            draw_values: self.draw_values,
+            stall_nodes: self.stall_at.map(|at| at.saturating_sub(self.nodes)),
+            stall_report: self.stall_report.clone(),
+        }
+    }
+
+    /// Find the best root move other than the given best move along with its exact
+    /// eval at the same depth. The main search only bounds the other moves with null
+    /// windows so this needs its own pass, although it is cheap with a warm table as
+    /// each move after the first only needs to prove it is no better than the best
+    /// alternative found so far.
+    fn search_alternative(&mut self, best: &BestMoveResponse) -> Result<Option<(Move, i32)>> {
+        let root_index = self.node.position().history.len() as u16;
+        let others = self.node.position().moves(&Moves::All);
+        let mut alternative: Option<(Move, i32)> = None;
+        for m in others.into_iter().filter(|m| m != &best.best_move) {
+            let alpha = alternative.as_ref().map_or(-node::INFTY, |(_, eval)| *eval);
+            let mut searcher = self.create_searcher(PrincipleVariation::default());
+            let context = Context {
+                depth: best.depth - 1,
                 alpha: -node::INFTY,
-                beta: node::INFTY,
+                beta: -alpha,
                 known_raise_alpha: None,
                 root_index,
                 null_move_last: false,
-                on_pv: true
-            },
-        )?;
-        
-        // We should always hit the principle variation in full
-        debug_assert!(searcher.off_pv);
-        debug_assert_eq!(depth as u32, searcher.pv_node_count);
+                ply: 0,
+                extensions: 0,
+                on_pv: false,
+            };
+            self.node.make(m.clone())?;
+            let result = searcher.search(&mut self.node, context);
+            self.absorb(&mut searcher);
+            self.node.unmake()?;
+            let eval = -result?.eval;
+            if eval > alpha {
+                alternative = Some((m, eval));
+            }
+        }
+        Ok(alternative)
+    }
+
+    /// The number of nodes which can still be visited before the node limit is reached
+    fn remaining_nodes(&self) -> Option<u64> {
+        self.max_nodes.map(|limit| limit.saturating_sub(self.nodes))
+    }
+
+    /// Check whether the given path from the root ends in checkmate, the path may end
+    /// before the mate if it was found in the quiescent search beyond the horizon.
+    fn ends_in_checkmate(&self, path: &[Move]) -> bool {
+        let mut position = self.node.position().clone();
+        path.iter().all(|m| position.make(m.clone()).is_ok())
+            && position.compute_terminal_state() == Some(TerminalState::Loss)
+    }
+
+    fn best_move(
+        &mut self,
+        depth: u8,
+        pv: &PrincipleVariation,
+        previous_eval: Option<i32>,
+    ) -> Result<BestMoveResponse> {
+        if depth < 1 {
+            return Err(anyhow!("Cannot iteratively deepen with depth 0"));
+        }
+
+        // Start with a narrow window around the previous eval unless it was a mate, on
+        // failing either side widen that side exponentially until it opens up fully
+        let mut delta = ASPIRATION_DELTA;
+        let centre = previous_eval.unwrap_or(0);
+        let (mut alpha, mut beta) = match previous_eval {
+            Some(eval) if self.aspiration_windows && eval.abs() < node::WIN_VALUE => {
+                (centre - delta, centre + delta)
+            }
+            _ => (-node::INFTY, node::INFTY),
+        };
+        let (eval, path) = loop {
+            let SearchResponse { eval, path } = self.search_root(depth, pv, alpha, beta)?;
+            delta *= ASPIRATION_WIDENING;
+            if eval <= alpha && alpha > -node::INFTY {
+                alpha = if delta > ASPIRATION_MAX_DELTA { -node::INFTY } else { centre - delta };
+            } else if eval >= beta && beta < node::INFTY {
+                beta = if delta > ASPIRATION_MAX_DELTA { node::INFTY } else { centre + delta };
+            } else {
+                break (eval, path);
+            }
+            self.stats.aspiration_researches += 1;
+        };
 
         // If the path returned is empty then there must be no legal moves in this position
         if path.is_empty() {
             Err(anyhow!("No moves for position {} at depth {}", self.node.position(), depth))
         } else {
-            Ok(BestMoveResponse { best_move: path.get(0).unwrap().clone(), eval, path, depth })
+            self.break_tie(BestMoveResponse {
+                best_move: path.get(0).unwrap().clone(),
+                eval,
+                path,
+                depth,
+            })
+        }
+    }
+
+    fn search_root(
+        &mut self,
+        depth: u8,
+        pv: &PrincipleVariation,
+        alpha: i32,
+        beta: i32,
+    ) -> Result<SearchResponse> {
+        let root_index = self.node.position().history.len() as u16;
+        // Clearing killers between iterations visits fewer nodes than carrying them over
+        let mut searcher = self.create_searcher(pv.clone());
+
+        let result = searcher.search(
+            &mut self.node,
+            Context {
+                depth,
+                alpha,
+                beta,
+                known_raise_alpha: None,
+                root_index,
+                null_move_last: false,
+                ply: 0,
+                extensions: 0,
+                on_pv: true,
+            },
+        );
+        self.absorb(&mut searcher);
+        self.history.age();
+        let response = result?;
+
+        // We should always hit the principle variation in full, unless nodes along it
+        // can stand pat in a captures only search or are answered by the table
+        if !self.captures_only && !searcher.pv_table_hit {
+            debug_assert!(searcher.off_pv || response.eval <= alpha || response.eval >= beta);
+            debug_assert_eq!(1 + pv.path.len() as u32, searcher.pv_node_count);
+        }
+        Ok(response)
+    }
+
+    /// Replace the best move with the root move preferred by the tie break which has
+    /// exactly the same eval, if there is one, so the choice between equal moves does
+    /// not depend on move ordering or the state of the table.
+    fn break_tie(&mut self, best: BestMoveResponse) -> Result<BestMoveResponse> {
+        // Equal mate evals can hide mates of different lengths
+        if best.eval.abs() == node::WIN_VALUE {
+            return Ok(best);
         }
+        let position = self.node.position().clone();
+        let best_key = tie_break_key(&position, &best.best_move);
+        let mut candidates = position
+            .moves(&Moves::All)
+            .into_iter()
+            .map(|m| (tie_break_key(&position, &m), m))
+            .filter(|(key, _)| key > &best_key)
+            .collect::<Vec<_>>();
+        candidates.sort_by(|(left, _), (right, _)| right.cmp(left));
+
+        let root_index = position.history.len() as u16;
+        for (_, m) in candidates {
+            let mut searcher = self.create_searcher(PrincipleVariation::default());
+            // A window either side of the best eval so an equal move returns its exact line
+            let context = Context {
+                depth: best.depth - 1,
+                alpha: -best.eval - 1,
+                beta: -best.eval + 1,
+                known_raise_alpha: None,
+                root_index,
+                null_move_last: false,
+                ply: 0,
+                extensions: 0,
+                on_pv: false,
+            };
+            self.node.make(m.clone())?;
+            let result = searcher.search(&mut self.node, context);
+            self.absorb(&mut searcher);
+            self.node.unmake()?;
+            if let Ok(response) = result
+                && -response.eval == best.eval
+                && response.path.len() + 1 == best.path.len()
+            {
+                let mut path = response.path;
+                path.insert(0, m.clone());
+                return Ok(BestMoveResponse { best_move: m, path, ..best });
+            }
+        }
+        Ok(best)
+    }
+}
+
+type TieBreakKey = (bool, bool, bool, i32, Reverse<Square>, Reverse<Square>);
+
+/// Ordering of root moves used to choose between equal evals, larger is preferred.
+/// Checks come first, then captures, then castling, then moves closer to the centre
+/// and finally the moves with the lowest source and destination squares.
+fn tie_break_key(position: &Position, m: &Move) -> TieBreakKey {
+    let (from, dest, is_capture) = match m {
+        &Move::Normal { from, dest, capture, .. } => (from, dest, capture.is_some()),
+        &Move::Promote { from, dest, capture, .. } => (from, dest, capture.is_some()),
+        &Move::Enpassant { from, dest, .. } => (from, dest, true),
+        &Move::Castle { corner, king, rook } => {
+            let (from, dest) = CastlingDetails::new(corner, king, rook).king_line;
+            (from, dest, false)
+        }
+        Move::Null => (0, 0, false),
+    };
+    let mut next = position.clone();
+    let is_check = next.make(m.clone()).is_ok() && next.in_check();
+    let centre_distance = |sq: Square| {
+        let (file, rank) = (square_file(sq) as i32, square_rank(sq) as i32);
+        (2 * file - 7).abs().max((2 * rank - 7).abs())
+    };
+    let is_castle = matches!(m, Move::Castle { .. });
+    (is_check, is_capture, is_castle, -centre_distance(dest), Reverse(from), Reverse(dest))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{MIN_STALL_NODES, stall_threshold};
+
+    #[test]
+    fn no_stall_threshold_without_two_iterations() {
+        assert_eq!(None, stall_threshold(&[]));
+        assert_eq!(None, stall_threshold(&[20000]));
+        assert_eq!(None, stall_threshold(&[0, 20000]));
+    }
+
+    #[test]
+    fn stall_threshold_follows_branching_factor() {
+        assert_eq!(Some(64000), stall_threshold(&[50, 1000, 4000]));
+        assert_eq!(Some(1000000), stall_threshold(&[10000, 50000]));
+    }
+
+    #[test]
+    fn stall_threshold_never_predicts_fewer_nodes() {
+        assert_eq!(Some(32000), stall_threshold(&[16000, 8000]));
+    }
+
+    #[test]
+    fn stall_threshold_bounded_below() {
+        assert_eq!(Some(MIN_STALL_NODES), stall_threshold(&[10, 20]));
     }
 }
@@ -8,8 +8,8 @@ use crate::eval::tables::PositionTables;
 use crate::moves::Move::{Castle, Enpassant, Normal, Null, Promote};
 use crate::moves::{Move, Moves};
 use crate::node::TreeNode;
-use crate::position::{CASTLING_DETAILS, ConstrainedPieces, Position};
-use crate::{Board, Class, Piece, Square};
+use crate::position::{CastlingDetails, ConstrainedPieces, Position};
+use crate::{Board, Class, Piece, SideMap, Square, SquareMatrix};
 use crate::search::quiescent;
 use crate::search::search::Context;
 
@@ -39,13 +39,52 @@ impl SearchMove {
     }
 }
 
+/// Butterfly table indexed by side, source and destination square which scores quiet
+/// moves by how often and how deep they have caused a beta cutoff. The scores are only
+/// allocated at the first cutoff so an empty table is free to create.
+#[derive(Default)]
+pub struct HistoryTable {
+    scores: Option<Box<SideMap<SquareMatrix<u32>>>>,
+}
+
+impl HistoryTable {
+    pub fn record_cutoff(&mut self, m: &Move, depth: u8) {
+        if let &Normal { moving, from, dest, .. } = m {
+            let scores = self.scores.get_or_insert_with(|| Box::new([[[0; 64]; 64]; 2]));
+            let score = &mut scores[piece_side(moving)][from][dest];
+            *score = score.saturating_add(depth as u32 * depth as u32);
+        }
+    }
+
+    pub fn score(&self, m: &Move) -> u32 {
+        match (m, self.scores.as_ref()) {
+            (&Normal { moving, from, dest, .. }, Some(scores)) => {
+                scores[piece_side(moving)][from][dest]
+            }
+            _ => 0,
+        }
+    }
+
+    /// Halve every score so cutoffs from the latest iteration outweigh older ones
+    pub fn age(&mut self) {
+        if let Some(scores) = self.scores.as_mut() {
+            scores.iter_mut().flatten().flatten().for_each(|score| *score /= 2);
+        }
+    }
+}
+
 impl MoveGenerator {
-    pub fn generate(&self, node: &mut TreeNode, ctx: &Context) -> Vec<SearchMove> {
+    pub fn generate(
+        &self,
+        node: &mut TreeNode,
+        ctx: &Context,
+        history: &HistoryTable,
+    ) -> Vec<SearchMove> {
         let mut moves = node.position().moves(&Moves::All);
         if ctx.depth > QUIESCENT_ORDERING_DEPTH_THRESHOLD {
             moves.sort_by_cached_key(|m| quiescent_evaluation(node, m));
         } else {
-            moves.sort_by_cached_key(|m| self.estimator.estimate(node, m));
+            moves.sort_by_cached_key(|m| self.estimator.estimate(node, m, history));
         }
         let pos = node.position();
         let enemy_king = create_piece(reflect_side(pos.active), class::K);
@@ -114,8 +153,10 @@ mod test {
     use crate::constants::square::*;
     use crate::moves::Move;
     use crate::moves::Move::Normal;
+    use crate::node::TreeNode;
     use crate::position::Position;
-    use crate::search::moves::{is_passed_pawn, is_positional_xray};
+    use crate::search::moves::{HistoryTable, MoveGenerator, is_passed_pawn, is_positional_xray};
+    use crate::search::search::Context;
 
     fn execute_test(pos: Position, m: Move, p: fn(&Move, &Position) -> bool, expected: bool) {
         let ref_p = pos.reflect();
@@ -124,6 +165,46 @@ mod test {
         assert_eq!(p(&ref_m, &ref_p), expected);
     }
 
+    fn best_ordered_move(history: &HistoryTable) -> Move {
+        let mut node: TreeNode = Position::default().into();
+        let ctx = Context {
+            root_index: 0,
+            alpha: -1,
+            beta: 1,
+            depth: 3,
+            known_raise_alpha: None,
+            null_move_last: false,
+            on_pv: false,
+            ply: 0,
+            extensions: 0,
+        };
+        let moves = MoveGenerator::default().generate(&mut node, &ctx, history);
+        moves.last().unwrap().m.clone()
+    }
+
+    #[test]
+    fn history_ranks_cutoff_move_first() {
+        let cutoff = Normal { moving: piece::WP, from: A2, dest: A3, capture: None };
+        let mut history = HistoryTable::default();
+        assert_ne!(cutoff, best_ordered_move(&history));
+        for depth in 1..4 {
+            history.record_cutoff(&cutoff, depth);
+        }
+        assert_eq!(cutoff, best_ordered_move(&history));
+    }
+
+    #[test]
+    fn history_ageing_halves_scores() {
+        let m = Normal { moving: piece::BN, from: G8, dest: F6, capture: None };
+        let mut history = HistoryTable::default();
+        history.record_cutoff(&m, 3);
+        history.record_cutoff(&m, 1);
+        assert_eq!(10, history.score(&m));
+        history.age();
+        assert_eq!(5, history.score(&m));
+        assert_eq!(0, history.score(&m.reflect()));
+    }
+
     #[test]
     fn is_positional_xray_case_0() {
         execute_test(
@@ -229,8 +310,8 @@ fn is_checking(
             in_board(discoveries.1[*from], *dest)
                 || in_board(control(*promoted, *dest, occupied & !lift(*from)), enemy_king)
         }
-        Castle { corner } => {
-            let details = &CASTLING_DETAILS[*corner];
+        &Castle { corner, king, rook } => {
+            let details = CastlingDetails::new(corner, king, rook);
             in_board(
                 control(create_piece(side::W, class::R), details.rook_line.1, occupied),
                 enemy_king,
@@ -249,12 +330,14 @@ struct MaterialAndPositioningHeuristic {
 }
 
 impl MaterialAndPositioningHeuristic {
-    fn estimate(&self, board: &TreeNode, mv: &Move) -> i32 {
+    /// Positional moves are ordered by their history score first, only falling back to
+    /// the position tables when the scores are equal.
+    fn estimate(&self, board: &TreeNode, mv: &Move, history: &HistoryTable) -> (i32, u32, i32) {
         match self.get_category(board, mv) {
-            MoveCategory::GoodExchange(n) => 30_000 + n,
-            MoveCategory::Special => 20_000,
-            MoveCategory::Positional(n) => 10_000 + n,
-            MoveCategory::BadExchange(n) => n,
+            MoveCategory::GoodExchange(n) => (3, 0, n),
+            MoveCategory::Special => (2, 0, 0),
+            MoveCategory::Positional(n) => (1, history.score(mv), n),
+            MoveCategory::BadExchange(n) => (0, 0, n),
         }
     }
 
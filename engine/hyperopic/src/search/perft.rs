@@ -0,0 +1,92 @@
+use anyhow::Result;
+
+use crate::moves::{Move, Moves};
+use crate::node::TreeNode;
+
+/// Count the leaf nodes of the legal move tree rooted at the given node down to
+/// the given depth, used to validate move generation against known totals.
+pub fn perft(node: &mut TreeNode, depth: u8) -> Result<u64> {
+    if depth == 0 {
+        return Ok(1);
+    }
+    let moves = node.position().moves(&Moves::All);
+    if depth == 1 {
+        return Ok(moves.len() as u64);
+    }
+    let mut count = 0;
+    for m in moves {
+        node.make(m)?;
+        count += perft(node, depth - 1)?;
+        node.unmake()?;
+    }
+    Ok(count)
+}
+
+/// Perft split by the legal root moves, each paired with the leaf count of its subtree
+pub fn divide(node: &mut TreeNode, depth: u8) -> Result<Vec<(Move, u64)>> {
+    let mut counts = vec![];
+    if depth == 0 {
+        return Ok(counts);
+    }
+    for m in node.position().moves(&Moves::All) {
+        node.make(m.clone())?;
+        let count = perft(node, depth - 1)?;
+        node.unmake()?;
+        counts.push((m, count));
+    }
+    Ok(counts)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{divide, perft};
+    use crate::node::TreeNode;
+    use crate::position::Position;
+
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+    fn execute_test(fen: &str, expected: &[u64]) {
+        let mut node: TreeNode = fen.parse::<Position>().unwrap().into();
+        for (i, &count) in expected.iter().enumerate() {
+            let depth = (i + 1) as u8;
+            assert_eq!(count, perft(&mut node, depth).unwrap(), "depth {}", depth);
+        }
+    }
+
+    #[test]
+    fn start_position() {
+        execute_test(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            &[20, 400, 8902, 197281],
+        );
+    }
+
+    #[test]
+    fn kiwipete() {
+        execute_test(KIWIPETE, &[48, 2039, 97862]);
+    }
+
+    #[test]
+    fn chess960_positions() {
+        execute_test(
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+            &[21, 528, 12189],
+        );
+        execute_test(
+            "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+            &[21, 807, 18002],
+        );
+        execute_test(
+            "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9",
+            &[20, 479, 10471],
+        );
+    }
+
+    #[test]
+    fn divide_sums_to_perft() {
+        let mut node: TreeNode = KIWIPETE.parse::<Position>().unwrap().into();
+        let split = divide(&mut node, 2).unwrap();
+        assert_eq!(48, split.len());
+        assert_eq!(2039, split.iter().map(|(_, n)| n).sum::<u64>());
+    }
+}
@@ -6,17 +6,14 @@ pub struct PrincipleVariation {
 }
 
 impl PrincipleVariation {
-    pub fn get_next_move(&self, curr_depth: usize) -> Option<&Move> {
-        // If pv depth is n then we would map
-        // n+1 -> 0
-        // n   -> 1
-        // ..
-        // 1   -> n-1
-        self.path.get((1 + self.path.len()) - curr_depth)
+    /// The pv is indexed by distance from the root rather than remaining depth as
+    /// extensions mean the two no longer line up.
+    pub fn get_next_move(&self, ply: u8) -> Option<&Move> {
+        self.path.get(ply as usize)
     }
 
-    pub fn is_next_on_pv(&self, curr_depth: u8, candidate: &Move) -> bool {
-        self.get_next_move(curr_depth as usize).is_some_and(|pvm| pvm == candidate)
+    pub fn is_next_on_pv(&self, ply: u8, candidate: &Move) -> bool {
+        self.get_next_move(ply).is_some_and(|pvm| pvm == candidate)
     }
 }
 
@@ -40,16 +37,16 @@ mod test {
     #[test]
     fn is_next_on_pv() {
         let pv = create_test_pv();
-        assert!(pv.is_next_on_pv(4, &Normal { moving: piece::WP, from: E2, dest: E4, capture: None }));
+        assert!(pv.is_next_on_pv(0, &Normal { moving: piece::WP, from: E2, dest: E4, capture: None }));
     }
 
     #[test]
     fn get_next_move() {
         let pv = create_test_pv();
 
-        assert_eq!(Some(&Normal { moving: piece::WP, from: E2, dest: E4, capture: None }), pv.get_next_move(4));
-        assert_eq!(Some(&Normal { moving: piece::BP, from: E5, dest: E7, capture: None }), pv.get_next_move(3));
+        assert_eq!(Some(&Normal { moving: piece::WP, from: E2, dest: E4, capture: None }), pv.get_next_move(0));
+        assert_eq!(Some(&Normal { moving: piece::BP, from: E5, dest: E7, capture: None }), pv.get_next_move(1));
         assert_eq!(Some(&Normal { moving: piece::WN, from: F1, dest: G3, capture: None }), pv.get_next_move(2));
-        assert_eq!(None, pv.get_next_move(1));
+        assert_eq!(None, pv.get_next_move(3));
     }
 }
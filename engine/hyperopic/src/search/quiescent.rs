@@ -14,20 +14,37 @@ use crate::position::TerminalState;
 const Q_CHECK_CAP: i32 = 0;
 const DELTA_SKIP_MARGIN: i32 = 200;
 const DELTA_SKIP_MAX_PHASE: f32 = 0.9;
+// Captures are skipped when not in check if their static exchange value is below
+// minus this margin, so that roughly equal exchanges are still explored
+const SEE_SKIP_MARGIN: i32 = 50;
+// Number of plies at the start of the quiescent search in which roughly equal
+// exchanges are explored, deeper than this (or everywhere if zero) only winning
+// exchanges are searched
+const EQUAL_EXCHANGE_PLIES: i32 = 1;
+/// Default bound on the number of plies along a single quiescent line in which the
+/// side to move is in check. Past this the static eval is returned rather than
+/// generating every evasion, so long checking sequences cannot explode the search.
+pub const MAX_EVASION_PLIES: u8 = 6;
 const SHALLOW_MOVE_FACETS: [MoveFacet; 3] = [Attacking, Checking, Promoting];
 const DEEP_MOVE_FACETS: [MoveFacet; 2] = [Attacking, Promoting];
 
 pub fn full_search(node: &mut TreeNode) -> Result<i32> {
-    search(node, -node::INFTY, node::INFTY)
+    search(node, -node::INFTY, node::INFTY, MAX_EVASION_PLIES)
 }
 
-pub fn search(node: &mut TreeNode, alpha: i32, beta: i32) -> Result<i32> {
-    search_impl(node, alpha, beta, -1)
+pub fn search(node: &mut TreeNode, alpha: i32, beta: i32, max_evasion_plies: u8) -> Result<i32> {
+    search_impl(node, alpha, beta, -1, max_evasion_plies)
 }
 
 /// Performs a depth limited search looking to evaluate only quiet positions,
 /// i.e. those with no attack moves.
-fn search_impl(node: &mut TreeNode, mut alpha: i32, beta: i32, depth: i32) -> Result<i32> {
+fn search_impl(
+    node: &mut TreeNode,
+    mut alpha: i32,
+    beta: i32,
+    depth: i32,
+    evasions_left: u8,
+) -> Result<i32> {
     // We know the start node not terminal otherwise wouldn't have entered the quiescent search
     if depth != -1 {
         match node.position().compute_terminal_state() {
@@ -43,6 +60,10 @@ fn search_impl(node: &mut TreeNode, mut alpha: i32, beta: i32, depth: i32) -> Re
     // are in check because we will consider all the moves and so we
     // assume lost until proven otherwise.
     let in_check = node.position().in_check();
+    if in_check && evasions_left == 0 {
+        return Ok(node.relative_eval());
+    }
+    let evasions_left = if in_check { evasions_left - 1 } else { evasions_left };
     let mut result = if in_check { -node::INFTY } else { node.relative_eval() };
 
     // Break immediately if the stand pat is greater than beta.
@@ -63,7 +84,10 @@ fn search_impl(node: &mut TreeNode, mut alpha: i32, beta: i32, depth: i32) -> Re
                     continue;
                 }
             }
-            MoveCategory::GoodExchange { optimistic_delta, .. } => {
+            MoveCategory::GoodExchange { see, optimistic_delta } => {
+                if !in_check && see <= 0 && depth < -EQUAL_EXCHANGE_PLIES {
+                    continue;
+                }
                 if !in_check
                     && depth < Q_CHECK_CAP
                     && phase < DELTA_SKIP_MAX_PHASE
@@ -74,7 +98,7 @@ fn search_impl(node: &mut TreeNode, mut alpha: i32, beta: i32, depth: i32) -> Re
             }
         };
         node.make(m)?;
-        let next_result = -search_impl(node, -beta, -alpha, depth - 1)?;
+        let next_result = -search_impl(node, -beta, -alpha, depth - 1, evasions_left)?;
         node.unmake()?;
         result = max(result, next_result);
         alpha = max(alpha, result);
@@ -122,7 +146,7 @@ fn categorise(state: &mut TreeNode, mv: &Move) -> MoveCategory {
             None => MoveCategory::Other,
             Some(piece) => {
                 let see = state.see(*from, *dest);
-                if see <= 0 {
+                if see < -SEE_SKIP_MARGIN {
                     MoveCategory::BadExchange { see }
                 } else {
                     MoveCategory::GoodExchange {
@@ -152,3 +176,37 @@ impl MoveCategory {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::MAX_EVASION_PLIES;
+    use crate::node::{self, INFTY, TreeNode};
+    use crate::position::Position;
+
+    fn search_with_limit(fen: &str, max_evasion_plies: u8) -> (TreeNode, i32) {
+        let mut node = TreeNode::from(fen.parse::<Position>().unwrap());
+        let eval = super::search(&mut node, -INFTY, INFTY, max_evasion_plies).unwrap();
+        (node, eval)
+    }
+
+    #[test]
+    fn no_evasions_returns_static_eval() {
+        let (node, eval) = search_with_limit("4k3/8/8/8/8/8/4q3/4K3 w - - 0 1", 0);
+        assert_eq!(node.relative_eval(), eval);
+        // The king recaptures the queen once one evasion is allowed, leaving a dead draw
+        let (_, eval) = search_with_limit("4k3/8/8/8/8/8/4q3/4K3 w - - 0 1", 1);
+        assert_eq!(node::DRAW_VALUE, eval);
+    }
+
+    #[test]
+    fn checking_sequence_cut_at_limit() {
+        // White is in check and black has further captures with check against the king,
+        // the line is resolved in three evasions
+        let fen = "7k/8/8/8/8/8/5qpp/6K1 w - - 0 1";
+        let evals = (0..=3).map(|limit| search_with_limit(fen, limit).1).collect::<Vec<_>>();
+        for i in 1..evals.len() {
+            assert_ne!(evals[i - 1], evals[i], "{:?}", evals);
+        }
+        assert_eq!(evals[3], search_with_limit(fen, MAX_EVASION_PLIES).1);
+    }
+}
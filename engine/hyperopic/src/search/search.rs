@@ -3,21 +3,37 @@ use anyhow::{Result, anyhow};
 use std::cmp::{max, min};
 use std::sync::Arc;
 
+use crate::SideMap;
 use crate::board::board_moves;
 use crate::constants::{class, create_piece, in_board};
 use crate::moves::Move;
 use crate::node;
 use crate::node::{INFTY, TreeNode};
-use crate::position::{CASTLING_DETAILS, TerminalState};
+use crate::position::TerminalState;
 use crate::search::end::SearchEndSignal;
-use crate::search::moves::{MoveGenerator, SearchMove};
+use crate::search::moves::{HistoryTable, MoveGenerator, SearchMove};
 use crate::search::pv::PrincipleVariation;
 use crate::search::quiescent;
 use crate::search::table::{NodeType, Transpositions};
+use crate::search::{DEPTH_UPPER_BOUND, SearchStats, StallReport};
 
 const END_CHECK_FREQ: u32 = 1000;
 // Better results compared to reduction of 3 or 4
 const MIN_NULL_MOVE_REDUCTION: u8 = 5;
+/// Bound on the number of check extensions along a single path so long sequences
+/// of checks cannot blow up the size of the tree
+pub const MAX_CHECK_EXTENSIONS: u8 = 4;
+/// Quiet moves at frontier nodes are skipped if the static eval plus this margin
+/// cannot raise alpha
+const FUTILITY_MARGIN: i32 = 200;
+/// Nodes this close to the horizon are cut off if the static eval minus a margin
+/// per remaining ply still beats beta
+const REVERSE_FUTILITY_MAX_DEPTH: u8 = 3;
+const REVERSE_FUTILITY_MARGIN: i32 = 120;
+/// Nodes this close to the horizon drop straight into the quiescent search if the
+/// static eval plus the margin for the remaining depth is still below alpha
+const RAZOR_MAX_DEPTH: u8 = 2;
+const RAZOR_MARGINS: [i32; RAZOR_MAX_DEPTH as usize + 1] = [0, 300, 550];
 
 /// Provides relevant callstack information for the search to
 /// use during the traversal of the tree.
@@ -30,6 +46,10 @@ pub struct Context {
     pub known_raise_alpha: Option<Move>,
     pub null_move_last: bool,
     pub on_pv: bool,
+    /// Distance from the root of the search
+    pub ply: u8,
+    /// Number of times the depth has been extended on the path from the root
+    pub extensions: u8,
 }
 
 impl Context {
@@ -42,6 +62,9 @@ impl Context {
             known_raise_alpha: None,
             null_move_last: matches!(m, Move::Null),
             on_pv,
+            ply: self.ply + 1,
+            // A move searched without any depth reduction has been extended
+            extensions: self.extensions + u8::from(r == 0),
         }
     }
 }
@@ -67,8 +90,60 @@ pub struct TreeSearcher<E: SearchEndSignal, T: Transpositions> {
     pub moves: MoveGenerator,
     pub pv: PrincipleVariation,
     pub node_counter: u32,
+    /// Total number of nodes visited in the main search tree
+    pub nodes: u64,
+    pub stats: SearchStats,
+    /// The search is terminated once this many nodes have been visited
+    pub node_limit: Option<u64>,
     pub pv_node_count: u32,
     pub off_pv: bool,
+    /// Set if a node on the principal variation was answered by the table, an entry from
+    /// an earlier search can reach deeper so the rest of the variation is not visited
+    pub pv_table_hit: bool,
+    pub killers: KillerMoves,
+    pub history: HistoryTable,
+    /// The number of check extensions allowed along any one path
+    pub max_extensions: u8,
+    /// The number of plies in check the quiescent search resolves along any one line
+    pub max_evasion_plies: u8,
+    /// Null move cutoffs at nodes with at least this depth remaining are verified
+    pub null_move_verification_depth: Option<u8>,
+    /// Only captures and promotions are searched outside of check, see
+    /// [crate::search::SearchParameters::captures_only]
+    pub captures_only: bool,
+    /// The eval of a draw for each side to move, these differ from zero with contempt
+    pub draw_values: SideMap<i32>,
+    /// A warning is logged when this many nodes have been visited as the iteration is
+    /// taking far longer than predicted, see [SearchStats::stalled_iterations]
+    pub stall_nodes: Option<u64>,
+    /// Sent through the info callback as soon as the stall is detected
+    pub stall_report: Option<StallReport>,
+}
+
+/// Quiet moves which recently caused a beta cutoff, indexed by ply. A move which
+/// refutes one line is likely to refute sibling lines at the same ply too.
+pub struct KillerMoves {
+    moves: [[Option<Move>; 2]; DEPTH_UPPER_BOUND as usize],
+}
+
+impl Default for KillerMoves {
+    fn default() -> Self {
+        KillerMoves { moves: std::array::from_fn(|_| [None, None]) }
+    }
+}
+
+impl KillerMoves {
+    fn get(&self, ply: u8) -> impl Iterator<Item = &Move> {
+        self.moves.get(ply as usize).into_iter().flatten().flatten()
+    }
+
+    fn record(&mut self, ply: u8, m: &Move) {
+        if let Some(slots) = self.moves.get_mut(ply as usize)
+            && slots[0].as_ref() != Some(m)
+        {
+            slots[1] = slots[0].replace(m.clone());
+        }
+    }
 }
 
 fn reposition_move_last(dest: &mut Vec<SearchMove>, m: &Move) {
@@ -86,9 +161,24 @@ where
     }
 }
 
+/// Move the last element satisfying the matcher to sit just in front of the trailing
+/// run of elements satisfying the skip predicate
+fn reposition_before_last<T, F, S>(dest: &mut Vec<T>, matcher: F, skip: S)
+where
+    F: Fn(&T) -> bool,
+    S: Fn(&T) -> bool,
+{
+    if let Some(index) = dest.iter().rev().position(matcher) {
+        let n = dest.len();
+        let removed = dest.remove(n - 1 - index);
+        let skipped = dest.iter().rev().take_while(|&x| skip(x)).count();
+        dest.insert(n - 1 - skipped, removed);
+    }
+}
+
 #[cfg(test)]
 mod reposition_test {
-    use super::reposition_last;
+    use super::{reposition_before_last, reposition_last};
 
     #[test]
     fn case_1() {
@@ -96,6 +186,42 @@ mod reposition_test {
         reposition_last(&mut xs, |&x| x == "c");
         assert_eq!(vec!["a", "b", "d", "e", "f", "c"], xs)
     }
+
+    #[test]
+    fn before_last_case_1() {
+        let mut xs = vec!["a", "b", "c", "D", "E"];
+        reposition_before_last(&mut xs, |&x| x == "a", |x| x.chars().all(char::is_uppercase));
+        assert_eq!(vec!["b", "c", "a", "D", "E"], xs)
+    }
+
+    #[test]
+    fn before_last_case_2() {
+        let mut xs = vec!["a", "b", "c"];
+        reposition_before_last(&mut xs, |&x| x == "a", |x| x.chars().all(char::is_uppercase));
+        assert_eq!(vec!["b", "c", "a"], xs)
+    }
+}
+
+#[cfg(test)]
+mod killer_test {
+    use super::KillerMoves;
+    use crate::constants::piece::WN;
+    use crate::constants::square::*;
+    use crate::moves::Move;
+
+    #[test]
+    fn record_keeps_two_most_recent() {
+        let mv = |dest| Move::Normal { moving: WN, from: G1, dest, capture: None };
+        let mut killers = KillerMoves::default();
+        killers.record(3, &mv(F3));
+        killers.record(3, &mv(F3));
+        killers.record(3, &mv(H3));
+        assert_eq!(vec![&mv(H3), &mv(F3)], killers.get(3).collect::<Vec<_>>());
+        killers.record(3, &mv(E2));
+        assert_eq!(vec![&mv(E2), &mv(H3)], killers.get(3).collect::<Vec<_>>());
+        assert_eq!(0, killers.get(4).count());
+        assert_eq!(0, killers.get(100).count());
+    }
 }
 
 enum TableLookup {
@@ -114,6 +240,17 @@ impl<E: SearchEndSignal, T: Transpositions> TreeSearcher<E, T> {
                 self.off_pv = true;
             }
         }
+        if self.node_limit.is_some_and(|limit| self.nodes >= limit) {
+            return Err(anyhow!("Node limit reached at depth {}", ctx.depth));
+        }
+        self.nodes += 1;
+        if self.stall_nodes == Some(self.nodes) {
+            self.stats.stalled_iterations += 1;
+            log::warn!("Search iteration stalled, far more nodes visited than predicted");
+            if let Some(report) = self.stall_report.take() {
+                report.send(self.table.hashfull());
+            }
+        }
         // Periodically check if we need to end the search
         self.node_counter = (self.node_counter + 1) % END_CHECK_FREQ;
         if self.node_counter == 0 && self.end.should_end_now() {
@@ -123,16 +260,28 @@ impl<E: SearchEndSignal, T: Transpositions> TreeSearcher<E, T> {
         if ctx.depth == 0 || terminal_state.is_some() {
             return match terminal_state {
                 Some(TerminalState::Loss) => Ok(max(ctx.alpha, min(ctx.beta, node::LOSS_VALUE))),
-                Some(TerminalState::Draw) => Ok(max(ctx.alpha, min(ctx.beta, node::DRAW_VALUE))),
-                None => quiescent::search(node, ctx.alpha, ctx.beta),
+                Some(TerminalState::Draw) => {
+                    Ok(max(ctx.alpha, min(ctx.beta, self.draw_value(node))))
+                }
+                None => quiescent::search(node, ctx.alpha, ctx.beta, self.max_evasion_plies),
             }
             .map(|eval| SearchResponse { eval, path: vec![] });
         }
 
+        // A position repeated since the root is scored as a draw, either side could choose
+        // to repeat it again so there is no need to wait for the third occurrence
+        if ctx.ply > 0 && repeats_in_tree(node, ctx.root_index) {
+            let eval = max(ctx.alpha, min(ctx.beta, self.draw_value(node)));
+            return Ok(SearchResponse { eval, path: vec![] });
+        }
+
         let table_entry = match self.do_table_lookup(node, &ctx) {
             TableLookup::Miss => None,
             TableLookup::Suggestion(n) => Some(n),
-            TableLookup::Hit(response) => return Ok(response),
+            TableLookup::Hit(response) => {
+                self.pv_table_hit |= ctx.on_pv;
+                return Ok(response);
+            }
         };
 
         let is_pv_node = ctx.alpha == -INFTY
@@ -140,34 +289,95 @@ impl<E: SearchEndSignal, T: Transpositions> TreeSearcher<E, T> {
             || ctx.known_raise_alpha.is_some()
             || matches!(table_entry, Some(Pv(_)));
 
-        if !is_pv_node && !ctx.null_move_last && should_try_null_move_pruning(node) {
+        let in_check = node.position().in_check();
+
+        // Away from the root of a captures only search the side to move may decline the
+        // captures on offer, so the static eval is a lower bound as in quiescence
+        let stand_pat = if self.captures_only && ctx.ply > 0 && !in_check {
+            let eval = node.relative_eval();
+            if eval >= ctx.beta {
+                return Ok(SearchResponse { eval: ctx.beta, path: vec![] });
+            }
+            ctx.alpha = max(ctx.alpha, eval);
+            Some(eval)
+        } else {
+            None
+        };
+
+        if !self.captures_only
+            && !is_pv_node
+            && !ctx.null_move_last
+            && should_try_null_move_pruning(node)
+        {
             // The idea is if we make no move and still cause a cutoff, it is highly likely there is some
             // move we can make which will also cause a cutoff
+            self.stats.null_move_searches += 1;
             node.make(Move::Null)?;
             let r = max(MIN_NULL_MOVE_REDUCTION, ctx.depth / 3);
             let score =
                 -self.search(node, ctx.next(-ctx.beta, -ctx.beta + 1, &Move::Null, r, false))?;
             node.unmake()?;
-            if score.eval >= ctx.beta {
+            if score.eval >= ctx.beta
+                && (!self.should_verify_null_move(&ctx) || self.verify_null_move(node, &ctx, r)?)
+            {
+                self.stats.null_move_cutoffs += 1;
                 return Ok(SearchResponse { eval: ctx.beta, path: vec![] });
             }
         }
 
         let start_alpha = ctx.alpha;
-        let in_check = node.position().in_check();
+
+        // Static futility pruning, neither variant is sound so it is restricted to non pv
+        // nodes near the horizon where the static eval is a reasonable guide
+        let mut prune_quiet_moves = false;
+        let mut try_razoring = false;
+        if !is_pv_node && !in_check && ctx.depth <= REVERSE_FUTILITY_MAX_DEPTH {
+            let static_eval = node.relative_eval();
+            let margin = REVERSE_FUTILITY_MARGIN * ctx.depth as i32;
+            if ctx.beta.abs() < node::WIN_VALUE && static_eval - margin >= ctx.beta {
+                return Ok(SearchResponse { eval: ctx.beta, path: vec![] });
+            }
+            prune_quiet_moves = ctx.depth == 1 && static_eval + FUTILITY_MARGIN <= ctx.alpha;
+            try_razoring = ctx.depth <= RAZOR_MAX_DEPTH
+                && ctx.alpha.abs() < node::WIN_VALUE
+                && static_eval + RAZOR_MARGINS[ctx.depth as usize] < ctx.alpha;
+        }
 
         let mut i = 0;
         let mut research = false;
         let mut best_path = vec![];
         let mut raised_alpha = false;
-        let mut score = -INFTY;
+        let mut score = stand_pat.unwrap_or(-INFTY);
 
         // Ordered from worst to best, so we iterate from back to front
-        let mvs = self.generate_moves(node, &ctx, &table_entry);
+        let mut mvs = self.generate_moves(node, &ctx, &table_entry);
+        if self.captures_only && !in_check {
+            let tactical = mvs.iter().filter(|sm| sm.is_attack || sm.is_promoting).count();
+            // The root needs some move to return so it keeps its quiet moves if it must
+            if ctx.ply > 0 || tactical > 0 {
+                mvs.retain(|sm| sm.is_attack || sm.is_promoting);
+            }
+        }
+
+        // Razoring, the quiescent search only looks at captures so it cannot be trusted
+        // to confirm the node fails low if we have a check or promotion available
+        if try_razoring && !mvs.iter().any(|sm| sm.is_check || sm.is_promoting) {
+            let eval = quiescent::search(node, ctx.alpha, ctx.alpha + 1, self.max_evasion_plies)?;
+            if eval <= ctx.alpha {
+                return Ok(SearchResponse { eval: ctx.alpha, path: vec![] });
+            }
+        }
+
         while i < mvs.len() {
             let sm = &mvs[mvs.len() - 1 - i];
             let m = &sm.m;
 
+            // Checks are flagged as tactical so are never pruned here
+            if prune_quiet_moves && !sm.is_tactical() {
+                i += 1;
+                continue;
+            }
+
             // The depth reduction we will search the move with
             let mut r = 1;
             if !research && ctx.depth > 1 && !in_check && !sm.is_tactical() {
@@ -185,9 +395,14 @@ impl<E: SearchEndSignal, T: Transpositions> TreeSearcher<E, T> {
             }
 
             node.make(m.clone())?;
+            // Extend checking moves so forced sequences are not cut off at the horizon,
+            // an extended move is never also reduced
+            if ctx.extensions < self.max_extensions && node.position().in_check() {
+                r = 0;
+            }
             let response = if !raised_alpha {
                 // Are we continuing the principle variation?
-                let still_on_pv = ctx.on_pv && self.pv.is_next_on_pv(ctx.depth, m);
+                let still_on_pv = ctx.on_pv && self.pv.is_next_on_pv(ctx.ply, m);
                 -self.search(node, ctx.next(-ctx.beta, -ctx.alpha, &m, r, still_on_pv))?
             } else {
                 // Search with a null window under the assumption that the previous moves are better than this
@@ -219,10 +434,14 @@ impl<E: SearchEndSignal, T: Transpositions> TreeSearcher<E, T> {
             }
 
             if ctx.alpha >= ctx.beta {
+                if !sm.is_attack {
+                    self.killers.record(ctx.ply, m);
+                    self.history.record_cutoff(m, ctx.depth);
+                }
                 self.table.put(
                     node.position(),
                     ctx.root_index,
-                    ctx.depth,
+                    self.table_depth(ctx.depth),
                     ctx.beta,
                     Cut(m.clone()),
                 );
@@ -248,12 +467,18 @@ impl<E: SearchEndSignal, T: Transpositions> TreeSearcher<E, T> {
             return self.search(node, ctx);
         }
 
+        // Every move was pruned, or none beat standing pat, so all we know is the node
+        // does not raise alpha
+        if best_path.is_empty() && (prune_quiet_moves || stand_pat.is_some()) {
+            return Ok(SearchResponse { eval: ctx.alpha, path: vec![] });
+        }
+
         // Populate the table with the information from this node.
         debug_assert!(best_path.len() > 0);
         self.table.put(
             node.position(),
             ctx.root_index,
-            ctx.depth,
+            self.table_depth(ctx.depth),
             score,
             if raised_alpha {
                 Pv(best_path.clone())
@@ -265,10 +490,47 @@ impl<E: SearchEndSignal, T: Transpositions> TreeSearcher<E, T> {
         Ok(SearchResponse { eval: ctx.alpha, path: best_path })
     }
 
-    fn do_table_lookup(&self, node: &TreeNode, ctx: &Context) -> TableLookup {
+    fn draw_value(&self, node: &TreeNode) -> i32 {
+        self.draw_values[node.position().active]
+    }
+
+    fn should_verify_null_move(&self, ctx: &Context) -> bool {
+        self.null_move_verification_depth.is_some_and(|depth| ctx.depth >= depth)
+    }
+
+    /// Confirm a null move cutoff by searching the real moves from this node with the
+    /// same reduction, null moves are disabled here so the verification cannot itself
+    /// be cut off by passing.
+    fn verify_null_move(&mut self, node: &mut TreeNode, ctx: &Context, r: u8) -> Result<bool> {
+        let verification = self.search(
+            node,
+            Context {
+                root_index: ctx.root_index,
+                alpha: ctx.beta - 1,
+                beta: ctx.beta,
+                depth: ctx.depth - min(r, ctx.depth),
+                known_raise_alpha: None,
+                null_move_last: true,
+                on_pv: false,
+                ply: ctx.ply,
+                extensions: ctx.extensions,
+            },
+        )?;
+        Ok(verification.eval >= ctx.beta)
+    }
+
+    /// The depth stored with a table entry, a captures only search skips the quiet moves
+    /// so its evals are stored at depth 0 where they can never cut off a full search
+    fn table_depth(&self, depth: u8) -> u8 {
+        if self.captures_only { 0 } else { depth }
+    }
+
+    fn do_table_lookup(&mut self, node: &TreeNode, ctx: &Context) -> TableLookup {
         // If we are in a repeated position then do not break early using table lookup as we can
         // enter a repeated cycle.
+        self.stats.table_probes += 1;
         if let Some(existing) = self.table.get(node.position()) {
+            self.stats.table_hits += 1;
             let is_repeated_position = has_repetition(node);
             match &existing.node_type {
                 n @ Pv(path) => {
@@ -278,7 +540,10 @@ impl<E: SearchEndSignal, T: Transpositions> TreeSearcher<E, T> {
                         && is_pseudo_legal(node, path.first().unwrap())
                     {
                         let adjusted_eval = min(ctx.beta, max(ctx.alpha, existing.eval));
-                        TableLookup::Hit(SearchResponse { eval: adjusted_eval, path: path.clone() })
+                        // The entry may come from a deeper search, keep the path consistent
+                        // with the current depth so the principal variation lines up
+                        let path = path.iter().take(ctx.depth as usize).cloned().collect();
+                        TableLookup::Hit(SearchResponse { eval: adjusted_eval, path })
                     } else {
                         TableLookup::Suggestion(n.clone())
                     }
@@ -319,7 +584,12 @@ impl<E: SearchEndSignal, T: Transpositions> TreeSearcher<E, T> {
         ctx: &Context,
         table_entry: &Option<NodeType>,
     ) -> Vec<SearchMove> {
-        let mut mvs = self.moves.generate(node, ctx);
+        let mut mvs = self.moves.generate(node, ctx, &self.history);
+        // Killers are tried after the best captures, placing them ahead of the captures
+        // visited more nodes on the middlegame benchmark
+        for m in self.killers.get(ctx.ply).collect::<Vec<_>>().into_iter().rev() {
+            reposition_before_last(&mut mvs, |sm| &sm.m == m, |sm| sm.is_attack);
+        }
         if let Some(n) = table_entry {
             reposition_move_last(
                 &mut mvs,
@@ -333,7 +603,7 @@ impl<E: SearchEndSignal, T: Transpositions> TreeSearcher<E, T> {
             reposition_move_last(&mut mvs, m);
         }
         if ctx.on_pv {
-            self.pv.get_next_move(ctx.depth as usize).map(|m| reposition_move_last(&mut mvs, m));
+            self.pv.get_next_move(ctx.ply).map(|m| reposition_move_last(&mut mvs, m));
         }
         mvs
     }
@@ -348,19 +618,28 @@ fn has_repetition(node: &TreeNode) -> bool {
         .any(|(d, _)| d.key == node.position().key)
 }
 
+/// Check if the position at this node already occurred on the path from the root of
+/// the search, the walk stops at the first irreversible move or null move.
+fn repeats_in_tree(node: &TreeNode, root_index: u16) -> bool {
+    let history = &node.position().history;
+    history[min(root_index as usize, history.len())..]
+        .iter()
+        .rev()
+        .take_while(|(_, m)| m.is_repeatable() && m != &Move::Null)
+        .any(|(d, _)| d.key == node.position().key)
+}
+
 fn is_pseudo_legal(node: &TreeNode, m: &Move) -> bool {
     let position = node.position();
     match m {
         Move::Null => false,
         Move::Enpassant { capture, .. } => position.enpassant == Some(*capture),
-        &Move::Castle { corner } => {
-            position.castling_rights[corner] && {
-                let details = &CASTLING_DETAILS[corner];
-                let rook = create_piece(position.active, class::R);
-                let king = create_piece(position.active, class::K);
-                position.piece_locs[details.rook_line.0] == Some(rook)
-                    && position.piece_locs[details.king_line.0] == Some(king)
-            }
+        &Move::Castle { corner, king, rook } => {
+            let active = position.active;
+            position.castling_rights[corner]
+                && position.castling_squares[corner] == (king, rook)
+                && position.piece_locs[rook] == Some(create_piece(active, class::R))
+                && position.piece_locs[king] == Some(create_piece(active, class::K))
         }
         &Move::Normal { moving, from, dest, capture } => {
             let (friendly, enemy) = position.friendly_enemy_boards();
@@ -1,16 +1,45 @@
 use crate::moves::Move;
 use crate::position::Position;
+use std::cmp::max;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::mem::size_of;
+use std::sync::atomic::{AtomicU16, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 
+const BYTES_PER_MEGABYTE: usize = 1024 * 1024;
+const HASHFULL_SAMPLE: usize = 1000;
+
 pub trait Transpositions {
     fn get(&self, pos: &Position) -> Option<Arc<TableEntry>>;
     fn put(&self, pos: &Position, root_index: u16, depth: u8, eval: i32, node_type: NodeType);
     fn reset(&self);
+
+    /// Called once at the start of each search so a table can tell entries stored by
+    /// earlier searches apart from those of the searches currently running.
+    fn new_search(&self) {}
+
+    /// The occupancy of the table per mille, estimated from the first thousand rows
+    /// as in the uci hashfull info field.
+    fn hashfull(&self) -> u16;
+
+    /// The best move, eval and depth stored for a position, used to report interim
+    /// results for the root of a search which is still running. The eval is relative
+    /// to the side to move and only a bound unless the entry is a principal variation.
+    fn probe_root(&self, pos: &Position) -> Option<(Move, i32, u8)> {
+        let entry = self.get(pos)?;
+        let best_move = match &entry.node_type {
+            NodeType::Pv(path) => path.first()?.clone(),
+            NodeType::Cut(m) | NodeType::All(m) => m.clone(),
+        };
+        Some((best_move, entry.eval, entry.depth))
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct TableEntry {
     pub root_index: u16,
+    /// The search generation of the table when the entry was stored
+    pub generation: u16,
     pub key: u64,
     pub depth: u8,
     pub eval: i32,
@@ -26,18 +55,110 @@ pub enum NodeType {
 
 pub struct ConcurrentTT {
     inner: Vec<Mutex<Option<Arc<TableEntry>>>>,
+    /// Advanced at the start of every search, the table may be shared by searches of
+    /// different games whose root indices cannot be compared
+    generation: AtomicU16,
+    collisions: Option<CollisionTracker>,
+}
+
+/// Counts of the collisions seen by a table with collision tracking enabled
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct CollisionStats {
+    /// Insertions which replaced an entry for a different key in the same row
+    pub index_collisions: u64,
+    /// Lookups which matched the key of an entry stored for a different position,
+    /// only detected in debug builds
+    pub key_collisions: u64,
+}
+
+struct CollisionTracker {
+    index_collisions: AtomicU64,
+    key_collisions: AtomicU64,
+    /// A second hash of the position stored in each row, independent of the zobrist
+    /// key, which is only kept in debug builds to catch key collisions
+    verification: Vec<AtomicU64>,
+}
+
+impl CollisionTracker {
+    fn new(n_entries: usize) -> CollisionTracker {
+        let n_verification = if cfg!(debug_assertions) { n_entries } else { 0 };
+        CollisionTracker {
+            index_collisions: AtomicU64::new(0),
+            key_collisions: AtomicU64::new(0),
+            verification: (0..n_verification).map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+
+    fn on_get(&self, index: usize, pos: &Position) {
+        if let Some(stored) = self.verification.get(index)
+            && stored.load(Ordering::Relaxed) != verification_hash(pos)
+        {
+            self.key_collisions.fetch_add(1, Ordering::Relaxed);
+            log::debug!("Transposition key collision for {} at row {}", pos, index);
+        }
+    }
+
+    fn on_put(&self, index: usize, pos: &Position, existing: Option<&TableEntry>) {
+        if existing.is_some_and(|e| e.key != pos.key) {
+            self.index_collisions.fetch_add(1, Ordering::Relaxed);
+        }
+        if let Some(stored) = self.verification.get(index) {
+            stored.store(verification_hash(pos), Ordering::Relaxed);
+        }
+    }
+
+    fn stats(&self) -> CollisionStats {
+        CollisionStats {
+            index_collisions: self.index_collisions.load(Ordering::Relaxed),
+            key_collisions: self.key_collisions.load(Ordering::Relaxed),
+        }
+    }
+}
+
+fn verification_hash(pos: &Position) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    pos.piece_boards.hash(&mut hasher);
+    pos.castling_rights.hash(&mut hasher);
+    pos.active.hash(&mut hasher);
+    pos.enpassant.hash(&mut hasher);
+    hasher.finish()
 }
 
 impl Transpositions for ConcurrentTT {
     fn get(&self, pos: &Position) -> Option<Arc<TableEntry>> {
-        let index = self.index(pos.key);
-        self.inner[index].lock().unwrap().as_ref().filter(|&e| e.key == pos.key).cloned()
+        let index = self.index(pos.key)?;
+        let guard = self.inner[index].lock().unwrap();
+        let entry = guard.as_ref().filter(|&e| e.key == pos.key).cloned();
+        if let Some(collisions) = self.collisions.as_ref()
+            && entry.is_some()
+        {
+            collisions.on_get(index, pos);
+        }
+        entry
     }
 
     fn put(&self, pos: &Position, root_index: u16, depth: u8, eval: i32, node_type: NodeType) {
-        let index = self.index(pos.key);
+        let Some(index) = self.index(pos.key) else { return };
         let mut curr_guard = self.inner[index].lock().unwrap();
-        *curr_guard = Some(Arc::new(TableEntry { root_index, depth, eval, key: pos.key, node_type }));
+        if let Some(collisions) = self.collisions.as_ref() {
+            collisions.on_put(index, pos, curr_guard.as_deref());
+        }
+        // Entries are aged by the generation of the table when they were stored, a deeper
+        // entry for another position is only kept if it comes from a running search
+        let generation = self.generation.load(Ordering::Relaxed);
+        let keep_existing = curr_guard
+            .as_ref()
+            .is_some_and(|e| e.key != pos.key && e.generation == generation && e.depth > depth);
+        if !keep_existing {
+            *curr_guard = Some(Arc::new(TableEntry {
+                root_index,
+                generation,
+                depth,
+                eval,
+                key: pos.key,
+                node_type,
+            }));
+        }
     }
 
     fn reset(&self) {
@@ -46,18 +167,196 @@ impl Transpositions for ConcurrentTT {
             *p = None;
         }
     }
+
+    fn new_search(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn hashfull(&self) -> u16 {
+        let sample = self.inner.len().min(HASHFULL_SAMPLE);
+        if sample == 0 {
+            return 0;
+        }
+        let occupied =
+            self.inner[..sample].iter().filter(|row| row.lock().unwrap().is_some()).count();
+        (occupied * 1000 / sample) as u16
+    }
 }
 
 impl ConcurrentTT {
+    /// Approximate memory used by a populated row, the row itself plus the shared
+    /// entry allocation with its reference counts. Principal variation paths are
+    /// not accounted for.
+    pub const ROW_BYTES: usize = size_of::<Mutex<Option<Arc<TableEntry>>>>()
+        + size_of::<TableEntry>()
+        + 2 * size_of::<usize>();
+
+    /// The number of rows which fit in the given memory budget, always at least one
+    pub fn rows_for_megabytes(megabytes: usize) -> usize {
+        max(1, megabytes.saturating_mul(BYTES_PER_MEGABYTE) / Self::ROW_BYTES)
+    }
+
+    /// The approximate memory budget in megabytes used by the given number of rows
+    pub fn megabytes_for_rows(rows: usize) -> usize {
+        rows.saturating_mul(Self::ROW_BYTES) / BYTES_PER_MEGABYTE
+    }
+
+    /// Create a table with the given number of rows. A table with no rows is valid,
+    /// lookups always miss and insertions are discarded.
     pub fn new(n_entries: usize) -> ConcurrentTT {
         let mut inner = Vec::with_capacity(n_entries);
         for _ in 0..n_entries {
             inner.push(Mutex::new(None));
         }
-        ConcurrentTT { inner }
+        ConcurrentTT { inner, generation: AtomicU16::new(0), collisions: None }
+    }
+
+    /// Enable counting of collisions in this table for diagnostics, this adds some
+    /// overhead to every table access so should not be used when playing games.
+    pub fn with_collision_tracking(mut self) -> ConcurrentTT {
+        self.collisions = Some(CollisionTracker::new(self.inner.len()));
+        self
+    }
+
+    /// The collisions seen so far if tracking is enabled
+    pub fn collision_stats(&self) -> Option<CollisionStats> {
+        self.collisions.as_ref().map(|c| c.stats())
+    }
+
+    fn index(&self, k: u64) -> Option<usize> {
+        match self.inner.len() {
+            0 => None,
+            n => Some((k % n as u64) as usize),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BYTES_PER_MEGABYTE, CollisionStats, ConcurrentTT, NodeType, Transpositions};
+    use crate::position::Position;
+
+    #[test]
+    fn empty_table_is_noop() {
+        let table = ConcurrentTT::new(0);
+        let position = Position::default();
+        table.put(&position, 0, 3, 10, NodeType::Pv(vec![]));
+        assert_eq!(None, table.get(&position));
+        assert_eq!(0, table.hashfull());
+        table.reset();
+    }
+
+    #[test]
+    fn hashfull_rises_as_table_fills() {
+        let table = ConcurrentTT::new(100);
+        assert_eq!(0, table.hashfull());
+        let mut position = Position::default();
+        let mut last = 0;
+        for _ in 0..40 {
+            let m = position.legal_moves().into_iter().next().unwrap();
+            position.make(m).unwrap();
+            table.put(&position, 0, 3, 10, NodeType::Pv(vec![]));
+            let hashfull = table.hashfull();
+            assert!(last <= hashfull && hashfull <= 1000);
+            last = hashfull;
+        }
+        assert!(last > 0);
+        table.reset();
+        assert_eq!(0, table.hashfull());
+    }
+
+    #[test]
+    fn index_collisions_counted() {
+        let table = ConcurrentTT::new(2).with_collision_tracking();
+        let first = Position::default();
+        let second: Position =
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1".parse().unwrap();
+        let third: Position =
+            "rnbqkbnr/pppppppp/8/8/3P4/8/PPP1PPPP/RNBQKBNR b KQkq - 0 1".parse().unwrap();
+        // Two of any three keys must share one of the two rows
+        let (a, b) = [(&first, &second), (&first, &third), (&second, &third)]
+            .into_iter()
+            .find(|(a, b)| a.key % 2 == b.key % 2)
+            .unwrap();
+        table.put(a, 0, 3, 10, NodeType::Pv(vec![]));
+        table.put(a, 0, 4, 10, NodeType::Pv(vec![]));
+        assert_eq!(Some(CollisionStats::default()), table.collision_stats());
+        table.put(b, 0, 4, 10, NodeType::Pv(vec![]));
+        assert_eq!(1, table.collision_stats().unwrap().index_collisions);
+        assert_eq!(None, table.get(a));
+        assert_eq!(None, ConcurrentTT::new(2).collision_stats());
+    }
+
+    #[test]
+    fn deeper_entries_kept_until_stale() {
+        let table = ConcurrentTT::new(1);
+        let first = Position::default();
+        let second: Position =
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1".parse().unwrap();
+        table.put(&first, 10, 5, 10, NodeType::Pv(vec![]));
+        table.put(&second, 10, 3, 10, NodeType::Pv(vec![]));
+        assert!(table.get(&first).is_some());
+        assert_eq!(None, table.get(&second));
+        // The same position is always updated
+        table.put(&first, 10, 2, 20, NodeType::Pv(vec![]));
+        assert_eq!(2, table.get(&first).unwrap().depth);
+        table.put(&first, 10, 5, 10, NodeType::Pv(vec![]));
+        // Entries from an earlier search are replaced
+        table.new_search();
+        table.put(&second, 12, 3, 10, NodeType::Pv(vec![]));
+        assert_eq!(None, table.get(&first));
+        assert_eq!(3, table.get(&second).unwrap().depth);
+    }
+
+    #[test]
+    fn stale_entries_replaced_whatever_their_root() {
+        let table = ConcurrentTT::new(1);
+        let first = Position::default();
+        let second: Position =
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1".parse().unwrap();
+        // A table shared by two games, one further into its game than the other
+        table.new_search();
+        table.put(&first, 60, 5, 10, NodeType::Pv(vec![]));
+        table.put(&second, 10, 3, 10, NodeType::Pv(vec![]));
+        assert_eq!(None, table.get(&second));
+        table.new_search();
+        table.put(&second, 10, 3, 10, NodeType::Pv(vec![]));
+        assert_eq!(None, table.get(&first));
+        assert_eq!(3, table.get(&second).unwrap().depth);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn key_collisions_counted() {
+        let table = ConcurrentTT::new(10).with_collision_tracking();
+        let position = Position::default();
+        let mut imposter: Position = "4k3/8/8/8/8/8/8/4K3 w - - 0 1".parse().unwrap();
+        imposter.key = position.key;
+        table.put(&position, 0, 3, 10, NodeType::Pv(vec![]));
+        assert!(table.get(&position).is_some());
+        assert_eq!(0, table.collision_stats().unwrap().key_collisions);
+        assert!(table.get(&imposter).is_some());
+        assert_eq!(1, table.collision_stats().unwrap().key_collisions);
+    }
+
+    #[test]
+    fn rows_for_megabytes() {
+        for megabytes in [1, 16, 256, 4096] {
+            let rows = ConcurrentTT::rows_for_megabytes(megabytes);
+            let budget = megabytes * BYTES_PER_MEGABYTE;
+            assert!(rows * ConcurrentTT::ROW_BYTES <= budget);
+            assert!((rows + 1) * ConcurrentTT::ROW_BYTES > budget);
+        }
+    }
+
+    #[test]
+    fn rows_for_zero_megabytes() {
+        assert_eq!(1, ConcurrentTT::rows_for_megabytes(0));
     }
 
-    fn index(&self, k: u64) -> usize {
-        (k % self.inner.len() as u64) as usize
+    #[test]
+    fn megabytes_for_rows() {
+        assert_eq!(ConcurrentTT::ROW_BYTES, ConcurrentTT::megabytes_for_rows(BYTES_PER_MEGABYTE));
+        assert_eq!(0, ConcurrentTT::megabytes_for_rows(1));
     }
 }
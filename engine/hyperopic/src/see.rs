@@ -5,10 +5,38 @@ use crate::board::{control, iter, union_boards};
 use crate::constants::{
     class, create_piece, in_board, intersects, lift, piece_class, piece_side, reflect_side, side,
 };
-use crate::eval::material::PieceValues;
+use crate::eval::material::{MaterialFacet, PieceValues};
+use crate::moves::Move;
 use crate::position::Position;
 use crate::{Board, Class, Piece, Side, Square, SquareMap};
 
+/// Compute the material gain for the side making the given move assuming both
+/// sides then recapture on the destination square for as long as it is profitable,
+/// including recaptures by pieces x-raying through the square. The value is given
+/// using the midgame material values of the evaluation, positive is good for the
+/// side making the move. Moves which are not captures have a value of zero.
+pub fn see(position: &Position, mv: &Move) -> i32 {
+    let material = MaterialFacet::default();
+    let values = material.mid_values();
+    match *mv {
+        Move::Normal { from, dest, capture: Some(_), .. } => {
+            exchange_value(position, from, dest, values)
+        }
+        Move::Enpassant { side, from, dest, .. } => {
+            let pawn = create_piece(reflect_side(side), class::P);
+            See { board: position, source: from, target: dest, values }
+                .exchange_value_with(position.piece_locs[from].unwrap(), pawn)
+        }
+        Move::Promote { from, dest, promoted, capture: Some(captured) } => {
+            let promotion_gain = values[piece_class(promoted)] - values[class::P];
+            promotion_gain
+                + See { board: position, source: from, target: dest, values }
+                    .exchange_value_with(promoted, captured)
+        }
+        _ => 0,
+    }
+}
+
 pub fn exchange_value(
     board: &Position,
     source: Square,
@@ -38,9 +66,16 @@ impl See<'_> {
     }
 
     fn exchange_value(&self) -> i32 {
+        let first_attacker = self.board.piece_locs[self.source].unwrap();
+        let first_victim = self.board.piece_locs[self.target].unwrap();
+        self.exchange_value_with(first_attacker, first_victim)
+    }
+
+    /// Compute the exchange value where the piece standing on the target square after
+    /// the first capture and the piece it removes are given explicitly, which differ
+    /// from the board contents for promotions and enpassant.
+    fn exchange_value_with(&self, first_attacker: Piece, first_victim: Piece) -> i32 {
         let board = self.board;
-        let first_attacker = board.piece_locs[self.source].unwrap();
-        let first_victim = board.piece_locs[self.target].unwrap();
         let mut d = 0;
         let mut gain: [i32; 32] = [0; 32];
         gain[d] = self.value(first_victim);
@@ -160,12 +195,13 @@ fn compute_attack_location_constraints() -> SquareMap<Board> {
 
 #[cfg(test)]
 mod test {
-    use super::See;
+    use super::{See, see};
+    use crate::parse::parse_uci_move;
     use crate::{Square, Symmetric};
 
     use crate::constants::square::*;
     use crate::constants::{class, corner, create_piece, reflect_square, side};
-    use crate::eval::material::PieceValues;
+    use crate::eval::material::{MaterialFacet, PieceValues};
     use crate::moves::Move;
     use crate::node::TreeNode;
     use crate::position::Position;
@@ -283,7 +319,7 @@ mod test {
                 dest: H3,
                 capture: None,
             },
-            Move::Castle { corner: corner::BK },
+            Move::Castle { corner: corner::BK, king: E8, rook: H8 },
             Move::Normal {
                 moving: create_piece(side::W, class::Q),
                 from: D1,
@@ -313,4 +349,47 @@ mod test {
         moves.into_iter().for_each(|m| node.make(m).unwrap());
         assert_eq!(0, node.see(C8, H3));
     }
+
+    fn public_see(fen: &str, uci_move: &str) -> i32 {
+        let position = fen.parse::<Position>().unwrap();
+        let mv = parse_uci_move(&position, uci_move).unwrap();
+        see(&position, &mv)
+    }
+
+    #[test]
+    fn public_see_pawn_takes_defended_knight() {
+        let values = *MaterialFacet::default().mid_values();
+        let value = public_see("4k3/8/4p3/3n4/4P3/8/8/4K3 w - - 0 1", "e4d5");
+        assert_eq!(values[class::N] - values[class::P], value);
+    }
+
+    #[test]
+    fn public_see_rook_battery() {
+        let values = *MaterialFacet::default().mid_values();
+        // The doubled rooks win the pawn defended by a single rook
+        let value = public_see("3r2k1/8/8/3p4/8/8/3R4/3R2K1 w - - 0 1", "d2d5");
+        assert_eq!(values[class::P], value);
+        // Without the rook behind the exchange loses the rook for the pawn
+        let value = public_see("3r2k1/8/8/3p4/8/8/3R4/6K1 w - - 0 1", "d2d5");
+        assert_eq!(values[class::P] - values[class::R], value);
+    }
+
+    #[test]
+    fn public_see_losing_queen_capture() {
+        let values = *MaterialFacet::default().mid_values();
+        let value = public_see("4k3/2p5/3p4/8/8/8/8/3QK3 w - - 0 1", "d1d6");
+        assert_eq!(values[class::P] - values[class::Q], value);
+    }
+
+    #[test]
+    fn public_see_promotion_capture() {
+        let values = *MaterialFacet::default().mid_values();
+        let value = public_see("3r1rk1/2P5/8/8/8/8/8/4K3 w - - 0 1", "c7d8q");
+        assert_eq!(values[class::R] - values[class::P], value);
+    }
+
+    #[test]
+    fn public_see_quiet_move() {
+        assert_eq!(0, public_see("4k3/8/8/8/8/8/8/3QK3 w - - 0 1", "d1d5"));
+    }
 }
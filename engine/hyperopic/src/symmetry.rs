@@ -0,0 +1,94 @@
+use crate::Symmetric;
+use crate::eval::TempoFacet;
+use crate::hash::PRNG;
+use crate::moves::Moves;
+use crate::node::{EvalFacet, TreeNode};
+use crate::position::Position;
+use anyhow::{Result, anyhow};
+
+/// Random positions are reached by playing up to this many random moves from the start
+const MAX_RANDOM_PLIES: u64 = 60;
+
+/// Check the evaluation treats both sides alike on a battery of random positions, that
+/// is the evaluation of each position from the point of view of the side to move equals
+/// that of its reflection. The reflection swaps the side to move so facets which depend
+/// on it, like tempo, are covered. An error describing the first asymmetric position
+/// found is returned.
+pub fn check_eval_symmetry(positions: usize, seed: u64) -> Result<()> {
+    check_eval_symmetry_with(positions, seed, || vec![Box::new(TempoFacet::default())])
+}
+
+/// Check evaluation symmetry with the given facets added to the default evaluation
+pub(crate) fn check_eval_symmetry_with(
+    positions: usize,
+    seed: u64,
+    extra_facets: impl Fn() -> Vec<Box<dyn EvalFacet>>,
+) -> Result<()> {
+    let mut prng = PRNG::new(seed);
+    for _ in 0..positions {
+        let position = random_position(&mut prng);
+        let reflected = position.reflect();
+        let eval = evaluate(position.clone(), &extra_facets);
+        let reflected_eval = evaluate(reflected.clone(), &extra_facets);
+        if eval != reflected_eval {
+            return Err(anyhow!(
+                "Evaluation of {} is {} but {} for its reflection {}",
+                position,
+                eval,
+                reflected_eval,
+                reflected
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn evaluate(position: Position, extra_facets: &impl Fn() -> Vec<Box<dyn EvalFacet>>) -> i32 {
+    let mut node = TreeNode::from(position);
+    extra_facets().into_iter().for_each(|facet| node.push_facet(facet));
+    node.relative_eval()
+}
+
+fn random_position(prng: &mut PRNG) -> Position {
+    let mut position = Position::default();
+    for _ in 0..prng.rand64() % (MAX_RANDOM_PLIES + 1) {
+        let moves = position.moves(&Moves::All);
+        if moves.is_empty() || position.compute_terminal_state().is_some() {
+            break;
+        }
+        let m = moves[(prng.rand64() % moves.len() as u64) as usize].clone();
+        position.make(m).unwrap();
+    }
+    position
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check_eval_symmetry, check_eval_symmetry_with};
+    use crate::moves::Move;
+    use crate::node::{EvalFacet, Evaluation};
+    use crate::position::Position;
+
+    /// Favours white regardless of the position, so is not symmetric
+    struct WhiteBiasFacet;
+
+    impl EvalFacet for WhiteBiasFacet {
+        fn static_eval(&self, _: &Position) -> Evaluation {
+            Evaluation::Single(25)
+        }
+
+        fn make(&mut self, _: &Move, _: &Position) {}
+
+        fn unmake(&mut self, _: &Move) {}
+    }
+
+    #[test]
+    fn current_facets_are_symmetric() {
+        check_eval_symmetry(50, 1).unwrap();
+    }
+
+    #[test]
+    fn asymmetric_facet_detected() {
+        assert!(check_eval_symmetry_with(50, 1, || vec![Box::new(WhiteBiasFacet)]).is_err());
+    }
+}
@@ -1,10 +1,14 @@
-use crate::moves::Move;
+use crate::moves::{Move, Moves};
 use crate::node::TreeNode;
-use crate::position::Position;
-use crate::search::end::EmptyEndSignal;
-use crate::search::{SearchParameters, ConcurrentTT};
+use crate::position::{Position, TerminalState};
+use crate::search::end::{EmptyEndSignal, SearchEndSignal};
+use crate::search::{
+    ConcurrentTT, Contempt, InfoCallback, SearchOutcome, SearchParameters, Transpositions,
+};
 use crate::{Symmetric, node};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 const TABLE_SIZE: usize = 10_000;
 
@@ -24,9 +28,8 @@ fn test(position: &str, expected_move_pool: Vec<&str>, is_won: bool, depth: usiz
 fn test_impl(board: TreeNode, expected_move_pool: Vec<Move>, is_won: bool, depth: usize) {
     let table = ConcurrentTT::new(TABLE_SIZE);
     let params = SearchParameters {
-        end_signal: EmptyEndSignal,
-        table: Arc::new(table),
         max_depth: Some(depth as u8),
+        ..SearchParameters::new(EmptyEndSignal, Arc::new(table))
     };
     match crate::search::search(board, params) {
         Err(message) => panic!("{}", message),
@@ -60,7 +63,8 @@ fn mate_0() {
 
 #[test]
 fn mate_1() {
-    test("8/8/8/4Q3/8/6R1/2n1pkBK/8 w - - 0 1", vec!["g3d3"], true, 4)
+    // Check extensions find the longer mate starting with Qf4+ first
+    test("8/8/8/4Q3/8/6R1/2n1pkBK/8 w - - 0 1", vec!["g3d3", "e5f4"], true, 4)
 }
 
 #[test]
@@ -78,6 +82,12 @@ fn mate_4() {
     test("r1k2b1r/pp4pp/2p1n3/3NQ1B1/6q1/8/PPP2P1P/2KR4 w - - 4 20", vec!["e5c7"], true, 7)
 }
 
+#[test]
+fn mate_4_with_check_extensions() {
+    // Every white move in the mate is a check so it is found well below the full depth
+    test("r1k2b1r/pp4pp/2p1n3/3NQ1B1/6q1/8/PPP2P1P/2KR4 w - - 4 20", vec!["e5c7"], true, 3)
+}
+
 #[test]
 fn mate_5() {
     test("r1b1k1nr/p2p1ppp/n2B4/1p1NPN1P/6P1/3P1Q2/P1P1K3/q5b1 w - - 0 30", vec!["f5g7"], true, 5)
@@ -114,3 +124,535 @@ fn win_material() {
 fn enpassant_win_pawn() {
     test("8/6rk/p1p1p2p/1pPqPp2/1PNP4/1PQ5/5RPK/3b4 w - b6 0 49", vec!["c5b6"], false, 1)
 }
+
+#[test]
+fn mate_4_shortest_path() {
+    let position: Position =
+        "r1k2b1r/pp4pp/2p1n3/3NQ1B1/6q1/8/PPP2P1P/2KR4 w - - 4 20".parse().unwrap();
+    let params = SearchParameters {
+        max_depth: Some(9),
+        find_shortest_mate: true,
+        ..SearchParameters::new(EmptyEndSignal, Arc::new(ConcurrentTT::new(TABLE_SIZE)))
+    };
+    let outcome = crate::search::search(position.clone().into(), params).unwrap();
+    // Qc7+ Nxc7 Nb6+ Kb8 Rd8+ Qc8 Rxc8# is a forced mate in four
+    assert_eq!(node::WIN_VALUE, outcome.relative_eval);
+    assert_eq!(7, outcome.optimal_path.len());
+    let mut mated = position;
+    outcome.optimal_path.iter().for_each(|m| mated.make(m.clone()).unwrap());
+    assert_eq!(Some(TerminalState::Loss), mated.compute_terminal_state());
+}
+
+/// Signals once it has been checked the given number of times, as a soft end this stops
+/// the search after exactly that many iterations whatever the speed of the machine
+#[derive(Clone)]
+struct IterationCount {
+    limit: usize,
+    checks: Arc<AtomicUsize>,
+}
+
+impl SearchEndSignal for IterationCount {
+    fn should_end_now(&self) -> bool {
+        self.checks.fetch_add(1, Ordering::SeqCst) + 1 >= self.limit
+    }
+
+    fn join(&self) {}
+}
+
+fn search_with_confirmation(
+    fen: &str,
+    depth: Option<u8>,
+    soft_end: Option<usize>,
+) -> SearchOutcome {
+    let params = SearchParameters {
+        max_depth: depth,
+        soft_end: soft_end.map(|limit| {
+            Arc::new(IterationCount { limit, checks: Arc::new(AtomicUsize::new(0)) })
+                as Arc<dyn SearchEndSignal + Send + Sync>
+        }),
+        confirm_unstable_best_move: true,
+        ..SearchParameters::new(EmptyEndSignal, Arc::new(ConcurrentTT::new(TABLE_SIZE)))
+    };
+    crate::search::search(fen.parse::<Position>().unwrap().into(), params).unwrap()
+}
+
+#[test]
+fn confirm_best_move_changed_at_soft_end() {
+    // The best move changes from a5c3 to h7h6 at depth 4
+    let fen = "r1bqr1k1/ppp2ppp/2np1n2/bB4B1/3PPQ2/2N2N2/PP3PPP/R4RK1 b - - 5 11";
+    let outcome = search_with_confirmation(fen, None, Some(4));
+    assert_eq!(5, outcome.depth);
+    assert_eq!("h7h6", outcome.best_move.to_string());
+}
+
+#[test]
+fn no_confirmation_when_best_move_stable() {
+    let fen = "r1bqr1k1/ppp2ppp/2np1n2/bB4B1/3PPQ2/2N2N2/PP3PPP/R4RK1 b - - 5 11";
+    let outcome = search_with_confirmation(fen, None, Some(3));
+    assert_eq!(3, outcome.depth);
+    assert_eq!("a5c3", outcome.best_move.to_string());
+}
+
+#[test]
+fn confirmation_never_exceeds_max_depth() {
+    let fen = "r1bqr1k1/ppp2ppp/2np1n2/bB4B1/3PPQ2/2N2N2/PP3PPP/R4RK1 b - - 5 11";
+    let depths = Arc::new(Mutex::new(vec![]));
+    let recorded = depths.clone();
+    let params = SearchParameters {
+        max_depth: Some(4),
+        confirm_unstable_best_move: true,
+        on_info: Some(InfoCallback::new(move |info| recorded.lock().unwrap().push(info.depth))),
+        ..SearchParameters::new(EmptyEndSignal, Arc::new(ConcurrentTT::new(TABLE_SIZE)))
+    };
+    let outcome = crate::search::search(fen.parse::<Position>().unwrap().into(), params).unwrap();
+    assert_eq!(4, outcome.depth);
+    assert!(depths.lock().unwrap().iter().all(|&d| d <= 4), "{:?}", depths.lock().unwrap());
+    assert_eq!(4, search_with_confirmation(fen, Some(4), Some(4)).depth);
+}
+
+#[test]
+fn easy_move_returns_early() {
+    // The black queen is hanging to the knight
+    let position: Position =
+        "r1b1kb1r/pppp1ppp/2n5/4p3/3qP3/2N2N2/PPP2PPP/R1BQKB1R w KQkq - 0 6".parse().unwrap();
+    let params = SearchParameters {
+        max_depth: Some(8),
+        return_easy_moves: true,
+        ..SearchParameters::new(EmptyEndSignal, Arc::new(ConcurrentTT::new(TABLE_SIZE)))
+    };
+    let outcome = crate::search::search(position.into(), params).unwrap();
+    assert_eq!("f3d4", outcome.best_move.to_string());
+    assert!(outcome.depth < 8, "Searched to depth {}", outcome.depth);
+}
+
+#[test]
+fn node_limit_stops_search() {
+    let position: Position =
+        "r5k1/pb4pp/1pn1pq2/5B2/2Pr4/B7/PP3RPP/R4QK1 b - - 0 23".parse().unwrap();
+    let params = SearchParameters {
+        max_nodes: Some(20_000),
+        ..SearchParameters::new(EmptyEndSignal, Arc::new(ConcurrentTT::new(TABLE_SIZE)))
+    };
+    let outcome = crate::search::search(position.into(), params).unwrap();
+    assert_eq!(20_000, outcome.nodes);
+    assert!(outcome.depth < 10, "Searched to depth {}", outcome.depth);
+}
+
+fn search_to_depth(fen: &str, depth: u8) -> crate::search::SearchOutcome {
+    let params = SearchParameters {
+        max_depth: Some(depth),
+        ..SearchParameters::new(EmptyEndSignal, Arc::new(ConcurrentTT::new(TABLE_SIZE)))
+    };
+    crate::search::search(fen.parse::<Position>().unwrap().into(), params).unwrap()
+}
+
+#[test]
+fn perpetual_check_for_losing_side() {
+    // Black is a rook and queen down but can check forever on d1 and g4
+    let outcome = search_to_depth("6k1/QR3pp1/7p/8/8/8/5PKP/3q4 b - - 0 1", 8);
+    assert_eq!(node::DRAW_VALUE, outcome.relative_eval);
+    assert_eq!("d1g4", outcome.best_move.to_string());
+}
+
+#[test]
+fn perpetual_check_for_defending_side() {
+    let outcome = search_to_depth("6k1/QR3pp1/7p/8/8/8/5P1P/3q1K2 w - - 0 1", 8);
+    assert_eq!(node::DRAW_VALUE, outcome.relative_eval);
+}
+
+#[test]
+fn perpetual_check_avoided_when_winning() {
+    // With white to move the perpetual can be prevented
+    let outcome = search_to_depth("6k1/QR3pp1/7p/8/8/8/5PKP/3q4 w - - 0 1", 8);
+    assert!(outcome.relative_eval > 1000, "{}", outcome.relative_eval);
+}
+
+#[test]
+fn equal_moves_chosen_deterministically() {
+    // At depth 3 b8c6 and g8f6 have the same eval, without a tie break the first move
+    // searched is chosen and that depends on the move ordering and table state. The
+    // prior searches are shallow enough that they cannot change the evals themselves.
+    let fen = "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2";
+    let after_c6 = "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+    let after_f6 = "rnbqkb1r/pppp1ppp/5n2/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3";
+    for prior_search in [None, Some((fen, 2)), Some((after_c6, 2)), Some((after_f6, 2))] {
+        let table = Arc::new(ConcurrentTT::new(TABLE_SIZE));
+        if let Some((prior_fen, depth)) = prior_search {
+            search_with_table(prior_fen, depth, table.clone());
+        }
+        let outcome = search_with_table(fen, 3, table);
+        assert_eq!(node::DRAW_VALUE, outcome.relative_eval);
+        assert_eq!("g8f6", outcome.best_move.to_string());
+    }
+}
+
+fn search_with_table(
+    fen: &str,
+    depth: u8,
+    table: Arc<ConcurrentTT>,
+) -> crate::search::SearchOutcome {
+    let params =
+        SearchParameters { max_depth: Some(depth), ..SearchParameters::new(EmptyEndSignal, table) };
+    crate::search::search(fen.parse::<Position>().unwrap().into(), params).unwrap()
+}
+
+#[test]
+fn queen_odds_start() {
+    let position: Position =
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNB1KBNR w KQkq - 0 1".parse().unwrap();
+    for position in [position.clone(), position.reflect()] {
+        let params = SearchParameters {
+            max_depth: Some(4),
+            ..SearchParameters::new(EmptyEndSignal, Arc::new(ConcurrentTT::new(TABLE_SIZE)))
+        };
+        let outcome = crate::search::search(position.clone().into(), params).unwrap();
+        assert!(position.moves(&Moves::All).contains(&outcome.best_move));
+        // Roughly a queen down for the side giving odds
+        assert!(outcome.relative_eval < -2000, "{}", outcome.relative_eval);
+    }
+}
+
+fn search_with_aspiration(fen: &str, depth: u8, aspiration_windows: bool) -> Move {
+    let params = SearchParameters {
+        max_depth: Some(depth),
+        aspiration_windows,
+        ..SearchParameters::new(EmptyEndSignal, Arc::new(ConcurrentTT::new(TABLE_SIZE)))
+    };
+    crate::search::search(fen.parse::<Position>().unwrap().into(), params).unwrap().best_move
+}
+
+#[test]
+fn aspiration_windows_keep_best_move() {
+    let cases = [
+        ("r5k1/pb4pp/1pn1pq2/5B2/2Pr4/B7/PP3RPP/R4QK1 b - - 0 23", 4, "e6f5"),
+        ("r1k2b1r/pp4pp/2p1n3/3NQ1B1/6q1/8/PPP2P1P/2KR4 w - - 4 20", 7, "e5c7"),
+        ("r1b1k1nr/p2p1ppp/n2B4/1p1NPN1P/6P1/3P1Q2/P1P1K3/q5b1 w - - 0 30", 5, "f5g7"),
+    ];
+    for (fen, depth, expected) in cases {
+        let without = search_with_aspiration(fen, depth, false);
+        let with = search_with_aspiration(fen, depth, true);
+        assert_eq!(expected, with.to_string());
+        assert_eq!(without, with);
+    }
+}
+
+fn search_with_alternative(fen: &str, depth: u8, find_alternative: bool) -> SearchOutcome {
+    let params = SearchParameters {
+        max_depth: Some(depth),
+        find_alternative,
+        ..SearchParameters::new(EmptyEndSignal, Arc::new(ConcurrentTT::new(TABLE_SIZE)))
+    };
+    crate::search::search(fen.parse::<Position>().unwrap().into(), params).unwrap()
+}
+
+#[test]
+fn alternative_reported_with_worse_eval() {
+    // Recapturing the bishop is clearly best, anything else leaves black a piece down
+    let fen = "r5k1/pb4pp/1pn1pq2/5B2/2Pr4/B7/PP3RPP/R4QK1 b - - 0 23";
+    let outcome = search_with_alternative(fen, 4, true);
+    assert_eq!("e6f5", outcome.best_move.to_string());
+    let (alternative, eval) = outcome.alternative.expect("No alternative reported");
+    assert_ne!(outcome.best_move, alternative);
+    assert!(eval < outcome.relative_eval - 200, "{} vs {}", eval, outcome.relative_eval);
+    assert_eq!(None, search_with_alternative(fen, 4, false).alternative);
+}
+
+#[test]
+fn terminal_root_not_searched() {
+    let cases = [
+        // Stalemate
+        ("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", node::DRAW_VALUE),
+        // Checkmate
+        ("R5k1/5ppp/8/8/8/8/8/6K1 b - - 0 1", node::LOSS_VALUE),
+        // Fifty move rule
+        ("4k3/8/8/8/8/8/4R3/4K3 w - - 100 80", node::DRAW_VALUE),
+    ];
+    for (fen, expected_eval) in cases {
+        let outcome = search_to_depth(fen, 3);
+        assert_eq!(Move::Null, outcome.best_move);
+        assert_eq!(expected_eval, outcome.relative_eval);
+        assert_eq!(0, outcome.depth);
+        assert!(outcome.optimal_path.is_empty());
+    }
+}
+
+fn search_with_null_move_verification(fen: &str, verify_null_move: Option<u8>) -> SearchOutcome {
+    let params = SearchParameters {
+        max_depth: Some(8),
+        verify_null_move,
+        ..SearchParameters::new(EmptyEndSignal, Arc::new(ConcurrentTT::new(TABLE_SIZE)))
+    };
+    crate::search::search(fen.parse::<Position>().unwrap().into(), params).unwrap()
+}
+
+#[test]
+fn verified_null_move_handles_zugzwang() {
+    // The bishops are shut in and the other pawns blocked so the kings and the e-pawn
+    // are the only moving parts. Kd6 wins as the black king is forced to give way to
+    // the pawn, a zugzwang which unverified null move cutoffs fail to see.
+    let fen = "7b/6p1/1k2K1P1/p3P3/P7/6p1/6P1/7B w - - 0 1";
+    let unverified = search_with_null_move_verification(fen, None);
+    assert_eq!("e6d5", unverified.best_move.to_string());
+    assert!(unverified.relative_eval < 200, "{}", unverified.relative_eval);
+    let verified = search_with_null_move_verification(fen, Some(6));
+    assert_eq!("e6d6", verified.best_move.to_string());
+    assert!(verified.relative_eval > 2000, "{}", verified.relative_eval);
+}
+
+fn search_lines(fen: &str, multi_pv: usize) -> Vec<SearchOutcome> {
+    let params = SearchParameters {
+        max_depth: Some(3),
+        multi_pv,
+        ..SearchParameters::new(EmptyEndSignal, Arc::new(ConcurrentTT::new(TABLE_SIZE)))
+    };
+    crate::search::search_multi(fen.parse::<Position>().unwrap().into(), params).unwrap()
+}
+
+#[test]
+fn multi_pv_lines_distinct_and_ordered() {
+    let fen = "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - 4 4";
+    let position: Position = fen.parse().unwrap();
+    let lines = search_lines(fen, 3);
+    assert_eq!(3, lines.len());
+    assert_eq!(position.clone().play("h5f7").unwrap()[0], lines[0].best_move);
+    assert_eq!(node::WIN_VALUE, lines[0].relative_eval);
+    assert!(lines[1].relative_eval < lines[0].relative_eval);
+    assert!(lines[2].relative_eval <= lines[1].relative_eval);
+    assert_ne!(lines[0].best_move, lines[1].best_move);
+    assert_ne!(lines[1].best_move, lines[2].best_move);
+    assert_ne!(lines[0].best_move, lines[2].best_move);
+    lines.iter().for_each(|line| assert_eq!(line.best_move, line.optimal_path[0]));
+}
+
+#[test]
+fn multi_pv_with_fewer_legal_moves() {
+    let lines = search_lines("7k/8/8/8/8/8/7P/r6K w - - 0 1", 3);
+    assert_eq!(1, lines.len());
+    let single = search_lines("7k/8/8/8/8/8/7P/r6K w - - 0 1", 1);
+    assert_eq!(single[0].best_move, lines[0].best_move);
+}
+
+#[test]
+fn info_reported_once_per_depth() {
+    let depths = Arc::new(Mutex::new(vec![]));
+    let recorded = depths.clone();
+    let params = SearchParameters {
+        max_depth: Some(4),
+        on_info: Some(InfoCallback::new(move |info| recorded.lock().unwrap().push(info.depth))),
+        ..SearchParameters::new(EmptyEndSignal, Arc::new(ConcurrentTT::new(TABLE_SIZE)))
+    };
+    let outcome = crate::search::search(Position::default().into(), params).unwrap();
+    assert_eq!(4, outcome.depth);
+    assert_eq!(vec![1, 2, 3, 4], *depths.lock().unwrap());
+}
+
+#[test]
+fn node_count_grows_with_depth() {
+    let fen = "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4";
+    let counts = (1..=5).map(|depth| search_to_depth(fen, depth).nodes).collect::<Vec<_>>();
+    assert!(counts[0] > 0);
+    assert!(counts.windows(2).all(|w| w[0] < w[1]), "Node counts {:?}", counts);
+}
+
+#[test]
+fn search_reports_stats() {
+    let fen = "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4";
+    let outcome = search_to_depth(fen, 5);
+    let stats = outcome.stats.expect("Search outcome missing stats");
+    assert_eq!(outcome.nodes, stats.nodes);
+    assert!(stats.table_hits > 0 && stats.table_hits <= stats.table_probes);
+    assert!(stats.null_move_cutoffs > 0 && stats.null_move_cutoffs <= stats.null_move_searches);
+    assert!((0.0..=1.0).contains(&stats.table_hit_rate()));
+    assert!((0.0..=1.0).contains(&stats.null_move_cutoff_rate()));
+}
+
+#[test]
+fn nps_derived_from_time() {
+    let mut outcome = search_to_depth(Position::default().to_string().as_str(), 3);
+    outcome.nodes = 5000;
+    outcome.time = Duration::from_millis(250);
+    assert_eq!(20_000, outcome.nps());
+    outcome.time = Duration::ZERO;
+    assert_eq!(5_000_000, outcome.nps());
+}
+
+fn search_with_threads<E: SearchEndSignal + Clone + Send>(
+    fen: &str,
+    end_signal: E,
+    max_depth: Option<u8>,
+) -> SearchOutcome {
+    let params = SearchParameters {
+        max_depth,
+        threads: 2,
+        ..SearchParameters::new(end_signal, Arc::new(ConcurrentTT::new(TABLE_SIZE)))
+    };
+    crate::search::search(fen.parse::<Position>().unwrap().into(), params).unwrap()
+}
+
+#[test]
+fn two_thread_search_saves_attacked_queen() {
+    let fen = "r4rk1/5ppp/8/1Bn1p3/Q7/8/5PPP/1R3RK1 w Qq - 5 27";
+    let position: Position = fen.parse().unwrap();
+    let outcome = search_with_threads(fen, EmptyEndSignal, Some(4));
+    assert!(position.moves(&Moves::All).contains(&outcome.best_move));
+    let escapes = ["a4b4", "a4c4", "a4g4", "a4h4", "a4c2", "a4d1"];
+    assert!(escapes.contains(&outcome.best_move.to_string().as_str()), "{}", outcome.best_move);
+    assert_eq!(4, outcome.depth);
+}
+
+#[test]
+fn two_thread_search_stops_at_end_signal() {
+    let fen = "r1bq1rk1/pp2ppbp/2np1np1/8/3NP3/2N1BP2/PPPQ2PP/R3KB1R w KQ - 3 9";
+    let position: Position = fen.parse().unwrap();
+    let start = Instant::now();
+    let outcome = search_with_threads(fen, start + Duration::from_millis(300), None);
+    assert!(start.elapsed() < Duration::from_secs(3), "Took {:?}", start.elapsed());
+    assert!(position.moves(&Moves::All).contains(&outcome.best_move));
+}
+
+#[test]
+fn two_thread_search_shares_node_limit() {
+    let fen = "r1bq1rk1/pp2ppbp/2np1np1/8/3NP3/2N1BP2/PPPQ2PP/R3KB1R w KQ - 3 9";
+    let params = SearchParameters {
+        max_nodes: Some(20_000),
+        threads: 2,
+        ..SearchParameters::new(EmptyEndSignal, Arc::new(ConcurrentTT::new(TABLE_SIZE)))
+    };
+    let outcome = crate::search::search(fen.parse::<Position>().unwrap().into(), params).unwrap();
+    assert!(outcome.nodes <= 20_000, "Visited {} nodes", outcome.nodes);
+    // The stats only count the main thread
+    let stats = outcome.stats.expect("Search outcome missing stats");
+    assert!(stats.nodes < outcome.nodes);
+}
+
+#[test]
+fn perpetual_check_found_below_threefold_depth() {
+    // Black is a rook up and threatens mate so white must give perpetual check with
+    // Qh6+ Kg8 Qg6+ Kh8, the third occurrence lies well beyond this depth.
+    let position: Position = "5r1k/p7/8/8/8/8/rr1Q1PPP/6K1 w - - 0 1".parse().unwrap();
+    let params = SearchParameters {
+        max_depth: Some(3),
+        ..SearchParameters::new(EmptyEndSignal, Arc::new(ConcurrentTT::new(TABLE_SIZE)))
+    };
+    let outcome = crate::search::search(position.into(), params).unwrap();
+    assert_eq!("d2h6", outcome.best_move.to_string());
+    assert_eq!(node::DRAW_VALUE, outcome.relative_eval);
+}
+
+#[test]
+fn decaying_contempt_applied_to_draws() {
+    let contempt = Contempt { value: 40, decay: true };
+    let start = Position::default();
+    // The perpetual check position, white can do no better than the draw
+    let late: Position = "5r1k/p7/8/8/8/8/rr1Q1PPP/6K1 w - - 0 1".parse().unwrap();
+    assert_eq!(40, contempt.effective(&start));
+    assert!(0 < contempt.effective(&late) && contempt.effective(&late) < 40);
+    let params = SearchParameters {
+        max_depth: Some(3),
+        contempt,
+        ..SearchParameters::new(EmptyEndSignal, Arc::new(ConcurrentTT::new(TABLE_SIZE)))
+    };
+    let outcome = crate::search::search(late.clone().into(), params).unwrap();
+    assert_eq!("d2h6", outcome.best_move.to_string());
+    assert_eq!(-contempt.effective(&late), outcome.relative_eval);
+}
+
+#[test]
+fn captures_only_search_credits_tactical_gain() {
+    // Taking the queen wins it outright but allows Ra1 mate, a quiet move which only
+    // the full search considers
+    let position: Position = "r6k/6pp/8/3q4/4P3/8/5PPP/6K1 w - - 0 1".parse().unwrap();
+    let search = |captures_only: bool| {
+        let params = SearchParameters {
+            max_depth: Some(3),
+            captures_only,
+            ..SearchParameters::new(EmptyEndSignal, Arc::new(ConcurrentTT::new(TABLE_SIZE)))
+        };
+        crate::search::search(position.clone().into(), params).unwrap()
+    };
+    let tactical = search(true);
+    let full = search(false);
+    assert_eq!("e4d5", tactical.best_move.to_string());
+    assert_ne!("e4d5", full.best_move.to_string());
+    assert!(tactical.relative_eval > full.relative_eval + 500);
+}
+
+#[test]
+fn captures_only_search_stores_entries_at_depth_zero() {
+    let position: Position = "r6k/6pp/8/3q4/4P3/8/5PPP/6K1 w - - 0 1".parse().unwrap();
+    let table = Arc::new(ConcurrentTT::new(TABLE_SIZE));
+    let params = SearchParameters {
+        max_depth: Some(3),
+        captures_only: true,
+        ..SearchParameters::new(EmptyEndSignal, table.clone())
+    };
+    crate::search::search(position.clone().into(), params).unwrap();
+    let entry = table.get(&position).expect("Root position not stored");
+    assert_eq!(0, entry.depth);
+}
+
+#[test]
+fn root_probe_reports_completed_iteration() {
+    let position: Position =
+        "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4".parse().unwrap();
+    let table = Arc::new(ConcurrentTT::new(TABLE_SIZE));
+    assert_eq!(None, table.probe_root(&position));
+    let params = SearchParameters {
+        max_depth: Some(3),
+        ..SearchParameters::new(EmptyEndSignal, table.clone())
+    };
+    let outcome = crate::search::search(position.clone().into(), params).unwrap();
+    let (best_move, eval, depth) = table.probe_root(&position).expect("Root not in table");
+    assert!(position.moves(&Moves::All).contains(&best_move));
+    assert!(eval.abs() < 300, "Eval {}", eval);
+    assert_eq!(3, depth);
+    assert_eq!(outcome.best_move, best_move);
+}
+
+fn stalled_iterations(fen: &str, depth: u8) -> u64 {
+    let params = SearchParameters {
+        max_depth: Some(depth),
+        ..SearchParameters::new(EmptyEndSignal, Arc::new(ConcurrentTT::new(TABLE_SIZE)))
+    };
+    let outcome = crate::search::search(fen.parse::<Position>().unwrap().into(), params).unwrap();
+    outcome.stats.expect("Search outcome missing stats").stalled_iterations
+}
+
+#[test]
+fn steady_search_not_reported_as_stalled() {
+    assert_eq!(0, stalled_iterations("4k3/8/8/8/8/8/8/4K2R w K - 0 1", 7));
+}
+
+#[test]
+fn positive_contempt_avoids_available_repetition() {
+    // White is slightly worse after wasting tempi and Ng1 repeats the position a third time
+    let pgn = "1. Nf3 e5 2. Ng1 d5 3. Nf3 Nc6 4. Ng1 Nb8 5. Nf3 Nc6 6. Ng1 Nb8 7. Nf3 Nc6";
+    let search = |value: i32| {
+        let params = SearchParameters {
+            max_depth: Some(3),
+            contempt: Contempt { value, decay: false },
+            ..SearchParameters::new(EmptyEndSignal, Arc::new(ConcurrentTT::new(TABLE_SIZE)))
+        };
+        let position: Position = pgn.parse().unwrap();
+        crate::search::search(position.into(), params).unwrap()
+    };
+    assert_eq!(node::DRAW_VALUE, search(0).relative_eval);
+    assert_ne!("f3g1", search(60).best_move.to_string());
+}
+
+#[test]
+fn evasion_limit_passed_to_quiescent_search() {
+    // Black has a sequence of checks after Kxf2, the limit decides how much is searched
+    let fen = "7k/8/8/8/8/8/5qpp/6K1 w - - 0 1";
+    let search = |max_evasion_plies: u8| {
+        let params = SearchParameters {
+            max_depth: Some(1),
+            max_evasion_plies,
+            ..SearchParameters::new(EmptyEndSignal, Arc::new(ConcurrentTT::new(TABLE_SIZE)))
+        };
+        crate::search::search(fen.parse::<Position>().unwrap().into(), params).unwrap()
+    };
+    let evals = [0, 1, 6].map(|limit| search(limit).relative_eval);
+    assert_ne!(evals[0], evals[1]);
+    assert_ne!(evals[1], evals[2]);
+}
@@ -0,0 +1,84 @@
+use crate::Symmetric;
+use crate::constants::side;
+use crate::position::Position;
+
+fn execute_test(fen: &str, white_in_check: bool, black_in_check: bool) {
+    let position: Position = fen.parse().unwrap();
+    assert_eq!(white_in_check, position.in_check_for(side::W));
+    assert_eq!(black_in_check, position.in_check_for(side::B));
+    let reflected = position.reflect();
+    assert_eq!(white_in_check, reflected.in_check_for(side::B));
+    assert_eq!(black_in_check, reflected.in_check_for(side::W));
+}
+
+#[test]
+fn side_to_move_in_check() {
+    execute_test("4k3/8/8/8/8/8/8/r3K3 w - - 0 1", true, false);
+    let position: Position = "4k3/8/8/8/8/8/8/r3K3 w - - 0 1".parse().unwrap();
+    assert_eq!(position.in_check(), position.in_check_for(position.active));
+}
+
+#[test]
+fn side_not_to_move_in_check() {
+    // An illegal position as black could take the white king
+    execute_test("4k3/8/8/8/8/8/8/r3K3 b - - 0 1", true, false);
+}
+
+#[test]
+fn neither_side_in_check() {
+    execute_test("4k3/8/8/8/8/8/8/rN2K3 w - - 0 1", false, false);
+    execute_test("4k3/8/8/8/8/8/8/r2PK3 b - - 0 1", false, false);
+}
+
+#[test]
+fn check_by_knight_and_pawn() {
+    execute_test("4k3/3P4/8/8/8/5n2/8/4K3 w - - 0 1", true, true);
+}
+
+fn find_move(position: &Position, uci: &str) -> crate::moves::Move {
+    position.legal_moves().into_iter().find(|m| m.to_string() == uci).unwrap()
+}
+
+#[test]
+fn capture_detection() {
+    let position: Position =
+        "8/6rk/p1p1p2p/1pPqPp2/1PNP4/1PQ5/5RPK/3b4 w - b6 0 49".parse().unwrap();
+    assert!(position.is_capture(&find_move(&position, "c5b6")));
+    assert!(position.is_capture(&find_move(&position, "f2f5")));
+    assert!(!position.is_capture(&find_move(&position, "f2f4")));
+    let position: Position = "1n2k3/P7/8/8/8/8/8/4K2R w K - 0 1".parse().unwrap();
+    assert!(position.is_capture(&find_move(&position, "a7b8q")));
+    assert!(!position.is_capture(&find_move(&position, "a7a8n")));
+    assert!(!position.is_capture(&find_move(&position, "e1g1")));
+}
+
+#[test]
+fn discovered_check_detected() {
+    // The bishop on b2 is masked by the knight on d4
+    let position: Position = "7k/8/8/8/3N4/8/1B6/4K3 w - - 0 1".parse().unwrap();
+    assert!(position.gives_check(&find_move(&position, "d4e6")));
+    assert!(position.gives_check(&find_move(&position, "d4f5")));
+    assert!(!position.gives_check(&find_move(&position, "b2c3")));
+    // Moving the pawn along the line of the rook keeps it masked
+    let position: Position = "4k3/8/8/8/8/4P3/8/4RK2 w - - 0 1".parse().unwrap();
+    assert!(!position.gives_check(&find_move(&position, "e3e4")));
+}
+
+#[test]
+fn gives_check_agrees_with_making_the_move() {
+    let fens = [
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/6rk/p1p1p2p/1pPqPp2/1PNP4/1PQ5/5RPK/3b4 w - b6 0 49",
+        "r1bqkb1R/p6p/2np4/8/1n1N3P/P1N1B1P1/2P1pP2/R1KQ1B1R b q - 1 12",
+        "5k2/8/8/8/8/8/8/4K2R w K - 0 1",
+        "8/8/8/K1pP3r/8/8/8/7k w - c6 0 2",
+    ];
+    for fen in fens {
+        let position: Position = fen.parse().unwrap();
+        for m in position.legal_moves() {
+            let mut after = position.clone();
+            after.make(m.clone()).unwrap();
+            assert_eq!(after.in_check(), position.gives_check(&m), "{} in {}", m, fen);
+        }
+    }
+}
@@ -0,0 +1,29 @@
+use crate::Symmetric;
+use crate::constants::piece::*;
+use crate::constants::side;
+use crate::constants::square::*;
+use crate::position::Position;
+
+#[test]
+fn undefended_knight_hanging() {
+    // The knight on d4 is attacked by the rook on d8 and nothing defends it
+    let position: Position = "3rk3/8/8/8/3N4/8/8/4K3 w - - 0 1".parse().unwrap();
+    assert_eq!(vec![(WN, D4)], position.hanging_pieces(side::W));
+    assert!(position.hanging_pieces(side::B).is_empty());
+    assert_eq!(vec![(BN, D5)], position.reflect().hanging_pieces(side::B));
+}
+
+#[test]
+fn adequately_defended_knight_not_hanging() {
+    // The knight is attacked by the rook but defended by a pawn so taking loses material
+    let position: Position = "3rk3/8/8/8/3N4/4P3/8/4K3 w - - 0 1".parse().unwrap();
+    assert!(position.hanging_pieces(side::W).is_empty());
+    assert!(position.reflect().hanging_pieces(side::B).is_empty());
+}
+
+#[test]
+fn defended_piece_attacked_by_cheaper_piece_hanging() {
+    // The rook on d5 is defended but the pawn capture still wins material
+    let position: Position = "4k3/8/2n5/3r4/4P3/8/8/4K3 b - - 0 1".parse().unwrap();
+    assert_eq!(vec![(BR, D5)], position.hanging_pieces(side::B));
+}
@@ -18,7 +18,7 @@ fn execute_test(from_fen: &str, m: Move, dest_fen: &str) {
 fn white_kingside_castle() {
     execute_test(
         "r3k2r/p2qpp2/1n1b4/2p5/2B5/1N6/2Q2PP1/R3K2R w KQkq - 0 1",
-        Move::Castle { corner: corner::WK },
+        Move::Castle { corner: corner::WK, king: E1, rook: H1 },
         "r3k2r/p2qpp2/1n1b4/2p5/2B5/1N6/2Q2PP1/R4RK1 b kq - 1 1",
     );
 }
@@ -27,7 +27,7 @@ fn white_kingside_castle() {
 fn white_queenside_castle() {
     execute_test(
         "r3k2r/p2qpp2/1n1b4/2p5/2B5/1N6/2Q2PP1/R3K2R w KQkq - 0 1",
-        Move::Castle { corner: corner::WQ },
+        Move::Castle { corner: corner::WQ, king: E1, rook: A1 },
         "r3k2r/p2qpp2/1n1b4/2p5/2B5/1N6/2Q2PP1/2KR3R b kq - 1 1",
     );
 }
@@ -36,7 +36,7 @@ fn white_queenside_castle() {
 fn black_kingside_castle() {
     execute_test(
         "r3k2r/p2qpp2/1n1b4/2p5/2B5/1N6/2Q2PP1/R3K2R b KQkq - 0 1",
-        Move::Castle { corner: corner::BK },
+        Move::Castle { corner: corner::BK, king: E8, rook: H8 },
         "r4rk1/p2qpp2/1n1b4/2p5/2B5/1N6/2Q2PP1/R3K2R w KQ - 1 2",
     );
 }
@@ -45,7 +45,7 @@ fn black_kingside_castle() {
 fn black_queenside_castle() {
     execute_test(
         "r3k2r/p2qpp2/1n1b4/2p5/2B5/1N6/2Q2PP1/R3K2R b KQkq - 0 1",
-        Move::Castle { corner: corner::BQ },
+        Move::Castle { corner: corner::BQ, king: E8, rook: A8 },
         "2kr3r/p2qpp2/1n1b4/2p5/2B5/1N6/2Q2PP1/R3K2R w KQ - 1 2",
     );
 }
@@ -184,3 +184,94 @@ fn enpassant() {
         "8/6rk/pPp1p2p/3qPp2/1PNP4/1PQ5/5RPK/3b4 b - - 0 49",
     )
 }
+
+#[test]
+fn every_legal_move_round_trips() {
+    let fens = [
+        "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        "8/6rk/p1p1p2p/1pPqPp2/1PNP4/1PQ5/5RPK/3b4 w - b6 0 49",
+        "r1bqkb1R/p6p/2np4/8/1n1N3P/P1N1B1P1/2P1pP2/R1KQ1B1R b q - 1 12",
+    ];
+    for fen in fens {
+        let mut position: Position = fen.parse().unwrap();
+        let start = position.clone();
+        for m in start.legal_moves() {
+            position.make(m.clone()).unwrap();
+            assert_ne!(start.key, position.key, "{} in {}", m, fen);
+            assert_eq!(m, position.unmake().unwrap());
+            assert_eq!(start.key, position.key, "{} in {}", m, fen);
+            assert_eq!(start, position, "{} in {}", m, fen);
+        }
+    }
+}
+
+#[test]
+fn moves_which_disagree_with_the_board_rejected() {
+    let fen = "r3k2r/p2qpp2/1n1b4/2p5/2B5/1N6/2Q2PP1/R3K2R w Kkq - 0 1";
+    let start: Position = fen.parse().unwrap();
+    let rejected = [
+        // Not the side to move
+        Move::Normal { moving: piece::BN, from: B6, dest: D5, capture: None },
+        // Nothing on the source square
+        Move::Normal { moving: piece::WN, from: C3, dest: D5, capture: None },
+        // The capture is not on the target square
+        Move::Normal { moving: piece::WB, from: C4, dest: F7, capture: Some(piece::BB) },
+        // Capturing our own piece
+        Move::Normal { moving: piece::WQ, from: C2, dest: F2, capture: Some(piece::WP) },
+        Move::Promote { from: G2, dest: G8, promoted: piece::WQ, capture: None },
+        Move::Enpassant { side: side::W, from: C4, dest: D5, capture: C5 },
+        // The queenside right has been lost
+        Move::Castle { corner: corner::WQ, king: E1, rook: A1 },
+        // Pieces which cannot move that way
+        Move::Normal { moving: piece::WR, from: A1, dest: A8, capture: Some(piece::BR) },
+        Move::Normal { moving: piece::WN, from: B3, dest: B5, capture: None },
+        Move::Normal { moving: piece::WB, from: C4, dest: C6, capture: None },
+        Move::Normal { moving: piece::WP, from: F2, dest: F5, capture: None },
+        Move::Normal { moving: piece::WP, from: F2, dest: E3, capture: None },
+    ];
+    for m in rejected {
+        let mut position = start.clone();
+        assert!(position.make(m.clone()).is_err(), "{}", m);
+        assert_eq!(start, position);
+    }
+    // A rook cannot jump over a pawn
+    let mut position = Position::default();
+    let jump = Move::Normal { moving: piece::WR, from: A1, dest: A5, capture: None };
+    assert!(position.make(jump).is_err());
+    assert_eq!(Position::default(), position);
+    // Nor can a pawn push through a piece
+    let blocked: Position = "4k3/8/8/8/8/4n3/4P3/4K3 w - - 0 1".parse().unwrap();
+    let push = Move::Normal { moving: piece::WP, from: E2, dest: E4, capture: None };
+    assert!(blocked.clone().make(push).is_err());
+    let mut position = start.clone();
+    assert!(position.unmake().is_err());
+    position.make(Move::Castle { corner: corner::WK, king: E1, rook: H1 }).unwrap();
+    assert_eq!(Move::Castle { corner: corner::WK, king: E1, rook: H1 }, position.unmake().unwrap());
+    assert_eq!(start, position);
+}
+
+#[test]
+fn incremental_key_matches_recomputed_over_long_games() {
+    let mut played = vec![];
+    // Cycle through the legal moves deterministically, these two games cover castling,
+    // en passant, captures and promotions without needing a search
+    for (step, offset) in [(2, 3), (10, 7)] {
+        let mut position = Position::default();
+        let mut keys = vec![position.key];
+        while position.history.len() < 300 && position.compute_terminal_state().is_none() {
+            let moves = position.legal_moves();
+            let m = moves[(step * position.history.len() + offset) % moves.len()].clone();
+            position.make(m.clone()).unwrap();
+            assert_eq!(position.recompute_key(), position.key, "{} -> {}", m, position);
+            keys.push(position.key);
+            played.push(m);
+        }
+        while let Ok(m) = position.unmake() {
+            assert_eq!(position.recompute_key(), position.key, "{} <- {}", m, position);
+            assert_eq!(keys[position.history.len()], position.key);
+        }
+    }
+    assert!(played.iter().any(|m| matches!(m, Move::Castle { .. })));
+    assert!(played.iter().any(|m| matches!(m, Move::Enpassant { .. })));
+    assert!(played.iter().any(|m| matches!(m, Move::Promote { .. })));
+}
@@ -0,0 +1,51 @@
+use crate::Symmetric;
+use crate::constants::piece::*;
+use crate::position::{MaterialSignature, Position};
+
+fn signature(fen: &str) -> MaterialSignature {
+    fen.parse::<Position>().unwrap().material_signature()
+}
+
+#[test]
+fn counts_each_piece() {
+    let start = Position::default().material_signature();
+    assert_eq!(8, start.count(WP));
+    assert_eq!(2, start.count(BN));
+    assert_eq!(1, start.count(WQ));
+    assert_eq!(1, start.count(BK));
+    assert_eq!(MaterialSignature::new([8, 2, 2, 2, 1, 1, 8, 2, 2, 2, 1, 1]), start);
+}
+
+#[test]
+fn oversized_count_saturates() {
+    let signature = MaterialSignature::new([17, 2, 0, 0, 0, 1, 0, 0, 0, 0, 0, 1]);
+    assert_eq!(15, signature.count(WP));
+    assert_eq!(2, signature.count(WN));
+    assert_eq!(1, signature.count(BK));
+}
+
+#[test]
+fn independent_of_squares() {
+    assert_eq!(
+        signature("4k3/8/8/8/8/8/8/R3K3 w - - 0 1"),
+        signature("8/8/3k4/8/8/1R6/6K1/8 b - - 0 1"),
+    );
+}
+
+#[test]
+fn changed_by_extra_pawn() {
+    let krk = signature("4k3/8/8/8/8/8/8/R3K3 w - - 0 1");
+    let krpk = signature("4k3/8/8/8/8/8/4P3/R3K3 w - - 0 1");
+    assert_ne!(krk, krpk);
+    assert_eq!(0, krk.count(WP));
+    assert_eq!(1, krpk.count(WP));
+}
+
+#[test]
+fn reflected_swaps_sides() {
+    let position: Position = "4k3/8/8/8/8/8/4P3/R3K3 w - - 0 1".parse().unwrap();
+    let reflected = position.reflect().material_signature();
+    assert_eq!(1, reflected.count(BR));
+    assert_eq!(1, reflected.count(BP));
+    assert_eq!(0, reflected.count(WR));
+}
@@ -1,16 +1,19 @@
 use crate::board::iter;
-use crate::constants::{reflect_corner, reflect_piece, reflect_side, reflect_square};
-use crate::moves::Move;
-use crate::position::{ConstrainedPieces, Position};
+use crate::constants::reflect_square;
+use crate::position::ConstrainedPieces;
 use crate::{Board, Symmetric, constants};
 use std::array;
 
 mod best_move;
+mod check;
 mod control;
 pub(crate) mod facets;
+mod hanging;
 mod make;
+mod material;
 mod move_comparison;
 mod moves;
+mod occupancy;
 mod pinned;
 mod termination;
 
@@ -22,54 +25,6 @@ pub fn reflect_board(board: Board) -> Board {
     iter(board).map(|sq| reflect_square(sq)).fold(0u64, |a, n| a | constants::lift(n))
 }
 
-impl Symmetric for Move {
-    fn reflect(&self) -> Self {
-        use crate::moves::Move::*;
-
-        match self {
-            Null => Null,
-            Castle { corner } => Castle { corner: reflect_corner(*corner) },
-            Normal { moving, from, dest, capture } => Normal {
-                moving: reflect_piece(*moving),
-                from: reflect_square(*from),
-                dest: reflect_square(*dest),
-                capture: capture.map(|p| reflect_piece(p)),
-            },
-            Enpassant { side, from, dest, capture } => Enpassant {
-                side: reflect_side(*side),
-                from: reflect_square(*from),
-                dest: reflect_square(*dest),
-                capture: reflect_square(*capture),
-            },
-            Promote { from, dest, promoted, capture } => Promote {
-                from: reflect_square(*from),
-                dest: reflect_square(*dest),
-                promoted: reflect_piece(*promoted),
-                capture: capture.map(|p| reflect_piece(p)),
-            },
-        }
-    }
-}
-
-impl Symmetric for Position {
-    fn reflect(&self) -> Self {
-        let mut cloned = self.clone();
-        let mut moves = vec![];
-        while let Ok(m) = cloned.unmake() {
-            moves.insert(0, m);
-        }
-        let mut reflected = Position::new(
-            reflect_side(cloned.active),
-            cloned.enpassant.map(|sq| reflect_square(sq)),
-            cloned.clock,
-            array::from_fn(|c| cloned.castling_rights[reflect_corner(c)]),
-            array::from_fn(|sq| cloned.piece_locs[reflect_square(sq)].map(|p| reflect_piece(p))),
-        );
-        moves.into_iter().for_each(|m| reflected.make(m.reflect()).unwrap());
-        reflected
-    }
-}
-
 impl Symmetric for ConstrainedPieces {
     fn reflect(&self) -> Self {
         ConstrainedPieces(
@@ -86,9 +86,8 @@ fn search_after_move(pgn: &str, mv: &str, depth: usize) -> SearchOutcome {
     crate::search::search(
         board.into(),
         SearchParameters {
-            end_signal: EmptyEndSignal,
-            table: Arc::new(ConcurrentTT::new(TABLE_SIZE)),
             max_depth: Some(depth as u8),
+            ..SearchParameters::new(EmptyEndSignal, Arc::new(ConcurrentTT::new(TABLE_SIZE)))
         },
     )
     .map_err(|e| panic!("Could not search at {}: {}", pgn, e))
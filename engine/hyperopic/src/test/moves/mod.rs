@@ -9,7 +9,7 @@ use crate::Symmetric;
 use crate::moves::MoveFacet::{Attacking, Checking, Promoting};
 use crate::moves::{Move, Moves};
 use crate::parse::StringIndexMap;
-use crate::position::Position;
+use crate::position::{Position, STANDARD_CASTLING_SQUARES};
 use anyhow::{Error, Result, anyhow};
 use std::str::FromStr;
 
@@ -44,7 +44,11 @@ impl FromStr for Move {
                     promoted: pieces.index(slice(s, 5, 2)),
                     capture: pieces.index_op(slice(s, 7, 2)),
                 }),
-                'c' => Ok(Move::Castle { corner: corners.index(slice(s, 1, 2)) }),
+                'c' => {
+                    let corner = corners.index(slice(s, 1, 2));
+                    let (king, rook) = STANDARD_CASTLING_SQUARES[corner];
+                    Ok(Move::Castle { corner, king, rook })
+                }
                 _ => Err(anyhow!("Cannot parse {} as a move", s)),
             },
         }
@@ -88,6 +92,13 @@ fn execute_test(case: TestCase) -> Result<()> {
         ),
     ];
 
+    let quiet = parse_moves(case.all.iter())
+        .difference(&parse_moves(case.attacks_checks.iter().chain(case.promotes.iter())))
+        .cloned()
+        .collect::<MoveSet>();
+    execute_quiet_test(&board, &quiet);
+    execute_quiet_test(&board.reflect(), &quiet.iter().map(|m| m.reflect()).collect());
+
     let ref_board = board.reflect();
     let ref_moves = expected
         .iter()
@@ -112,6 +123,16 @@ fn execute_test_impl(board: Position, moves: ExpectedMoves<'_>) {
     }
 }
 
+fn execute_quiet_test(board: &Position, expected: &MoveSet) {
+    let under_test: MoveSet = board.quiet_moves().into_iter().collect();
+    assert_eq!(
+        expected,
+        &under_test,
+        "Differences for quiet moves are: {}",
+        format_difference(expected.clone(), under_test.clone())
+    );
+}
+
 fn format_difference(expected: MoveSet, actual: MoveSet) -> String {
     let left_sub_right: Vec<_> =
         expected.clone().difference(&actual).map(|m| format!("{}", m)).collect();
@@ -120,6 +141,43 @@ fn format_difference(expected: MoveSet, actual: MoveSet) -> String {
     format!("E - A: {:?}, A - E: {:?}", left_sub_right, right_sub_left)
 }
 
+mod legal_moves_test {
+    use crate::moves::{Move, Moves};
+    use crate::position::Position;
+
+    fn count(fen: &str) -> usize {
+        let position = fen.parse::<Position>().unwrap();
+        let legal = position.legal_moves();
+        assert_eq!(position.moves(&Moves::All), legal);
+        legal.len()
+    }
+
+    #[test]
+    fn start_position() {
+        assert_eq!(20, Position::default().legal_moves().len());
+    }
+
+    #[test]
+    fn castling_enpassant_and_promotion_included() {
+        let matching = |fen: &str, variant: fn(&Move) -> bool| {
+            fen.parse::<Position>().unwrap().legal_moves().iter().filter(|m| variant(m)).count()
+        };
+        let kiwipete = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        assert_eq!(48, count(kiwipete));
+        assert_eq!(2, matching(kiwipete, |m| matches!(m, Move::Castle { .. })));
+        let enpassant = "8/6rk/p1p1p2p/1pPqPp2/1PNP4/1PQ5/5RPK/3b4 w - b6 0 49";
+        assert_eq!(1, matching(enpassant, |m| matches!(m, Move::Enpassant { .. })));
+        let promotion = "8/2P1k3/8/8/8/8/8/4K3 w - - 0 1";
+        assert_eq!(4, matching(promotion, |m| matches!(m, Move::Promote { .. })));
+    }
+
+    #[test]
+    fn best_move_positions() {
+        assert_eq!(33, count("8/6rk/p1p1p2p/1pPqPp2/1PNP4/1PQ5/5RPK/3b4 w - b6 0 49"));
+        assert_eq!(41, count("r4rk1/5ppp/8/1Bn1p3/Q7/8/5PPP/1R3RK1 w Qq - 5 27"));
+    }
+}
+
 mod parsing_formatting_test {
     use std::str::FromStr;
 
@@ -183,6 +241,92 @@ mod parsing_formatting_test {
 
     #[test]
     fn castle() {
-        assert_eq!(Move::Castle { corner: corner::BK }, Move::from_str("cbk").unwrap());
+        assert_eq!(
+            Move::Castle { corner: corner::BK, king: E8, rook: H8 },
+            Move::from_str("cbk").unwrap()
+        );
+    }
+}
+
+mod king_moves_test {
+    use super::{MoveSet, parse_moves};
+    use crate::Symmetric;
+    use crate::moves::{Move, Moves};
+    use crate::position::Position;
+
+    fn execute_test(fen: &str, expected: Vec<&str>) {
+        let position: Position = fen.parse().unwrap();
+        let expected = parse_moves(expected.iter());
+        let actual: MoveSet = position.king_moves().into_iter().collect();
+        assert_eq!(expected, actual);
+        let king_moves_from_all: MoveSet = position
+            .moves(&Moves::All)
+            .into_iter()
+            .filter(|m| match m {
+                Move::Normal { moving, .. } => *moving % 6 == 5,
+                Move::Castle { .. } => true,
+                _ => false,
+            })
+            .collect();
+        assert_eq!(expected, king_moves_from_all);
+        let reflected: MoveSet = position.reflect().king_moves().into_iter().collect();
+        assert_eq!(expected.iter().map(|m| m.reflect()).collect::<MoveSet>(), reflected);
+    }
+
+    #[test]
+    fn cannot_step_along_line_of_checking_rook() {
+        execute_test(
+            "4r2k/8/8/8/4K3/8/8/8 w - - 0 1",
+            vec!["swke4d3-", "swke4d4-", "swke4d5-", "swke4f3-", "swke4f4-", "swke4f5-"],
+        );
+    }
+
+    #[test]
+    fn includes_castling_and_captures() {
+        execute_test("4k3/8/8/8/8/8/5r2/R3K3 w Q - 0 1", vec!["swke1d1-", "swke1f2br", "cwq"]);
+    }
+}
+
+mod chess960_castling_test {
+    use crate::constants::corner;
+    use crate::constants::square::*;
+    use crate::moves::{Move, Moves};
+    use crate::parse::parse_uci_move;
+    use crate::position::Position;
+
+    fn castle_moves(fen: &str) -> Vec<Move> {
+        let position: Position = fen.parse().unwrap();
+        position
+            .moves(&Moves::All)
+            .into_iter()
+            .filter(|m| matches!(m, Move::Castle { .. }))
+            .collect()
+    }
+
+    #[test]
+    fn castle_available_from_start_position() {
+        let fen = "bqnbrkrn/pppppppp/8/8/8/8/PPPPPPPP/BQNBRKRN w GEge - 0 1";
+        let expected = Move::Castle { corner: corner::WK, king: F1, rook: G1 };
+        assert_eq!(vec![expected.clone()], castle_moves(fen));
+        let mut position: Position = fen.parse().unwrap();
+        assert_eq!(expected, parse_uci_move(&position, "f1g1").unwrap());
+        position.make(expected).unwrap();
+        assert_eq!("bqnbrkrn/pppppppp/8/8/8/8/PPPPPPPP/BQNBRRKN b ge - 1 1", position.to_string());
+        position.unmake().unwrap();
+        assert_eq!(fen, position.to_string());
+    }
+
+    #[test]
+    fn king_takes_rook_notation_accepted() {
+        let position: Position = "4k3/8/8/8/8/8/8/1R1K4 w B - 0 1".parse().unwrap();
+        let expected = Move::Castle { corner: corner::WQ, king: D1, rook: B1 };
+        assert_eq!(vec![expected.clone()], castle_moves("4k3/8/8/8/8/8/8/1R1K4 w B - 0 1"));
+        assert_eq!(expected, parse_uci_move(&position, "d1b1").unwrap());
+        assert_eq!("d1b1", expected.to_uci(true));
+    }
+
+    #[test]
+    fn cannot_castle_into_attack_shielded_by_rook() {
+        assert!(castle_moves("4k3/8/8/8/8/8/8/rR1K4 w B - 0 1").is_empty());
     }
 }
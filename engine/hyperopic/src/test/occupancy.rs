@@ -0,0 +1,20 @@
+use crate::constants::piece::*;
+use crate::constants::side;
+use crate::constants::square::*;
+use crate::position::Position;
+
+#[test]
+fn start_position_occupancy() {
+    let start = Position::default();
+    assert_eq!(Some(WK), start.piece_on(E1));
+    assert_eq!(Some(BQ), start.piece_on(D8));
+    assert_eq!(None, start.piece_on(E4));
+}
+
+#[test]
+fn side_of_occupant() {
+    let start = Position::default();
+    assert_eq!(Some(side::W), start.side_on(A2));
+    assert_eq!(Some(side::B), start.side_on(H7));
+    assert_eq!(None, start.side_on(D5));
+}
@@ -145,3 +145,44 @@ fn repetition_11() {
         9. f3 Nd6 10. Bxc6+ bxc6 11. h4 Nf5 12. Qc3 Bd7 13. h5 Ng3 14. Rh2 Nf1 15. Rh1 Ng3 16. Rh2 Nf1 17. Rh1 Ng3",
     )
 }
+
+#[test]
+fn insufficient_material_king_vs_king() {
+    execute_test(Some(TerminalState::Draw), "8/8/4k3/8/8/3K4/8/8 w - - 0 60");
+}
+
+#[test]
+fn insufficient_material_bishop_vs_king() {
+    execute_test(Some(TerminalState::Draw), "8/8/4k3/8/8/3K4/6B1/8 b - - 0 60");
+    execute_test(Some(TerminalState::Draw), "8/8/4k3/2b5/8/3K4/8/8 w - - 0 60");
+}
+
+#[test]
+fn insufficient_material_knight_vs_king() {
+    execute_test(Some(TerminalState::Draw), "8/8/4k3/8/8/3K4/8/6N1 b - - 0 60");
+    execute_test(Some(TerminalState::Draw), "8/8/4k3/2n5/8/3K4/8/8 w - - 0 60");
+}
+
+#[test]
+fn insufficient_material_same_coloured_bishops() {
+    // Both bishops on dark squares
+    execute_test(Some(TerminalState::Draw), "8/8/4k3/2b5/8/3K4/8/6B1 w - - 0 60");
+    // Both bishops on light squares
+    execute_test(Some(TerminalState::Draw), "8/8/4k3/3b4/8/3K4/8/5B2 w - - 0 60");
+}
+
+#[test]
+fn sufficient_material_opposite_coloured_bishops() {
+    execute_test(None, "8/8/4k3/2b5/8/3K4/8/5B2 w - - 0 60");
+}
+
+#[test]
+fn sufficient_material_two_knights() {
+    execute_test(None, "8/8/4k3/8/8/3K4/8/5NN1 w - - 0 60");
+}
+
+#[test]
+fn sufficient_material_with_pawn_or_knight_vs_bishop() {
+    execute_test(None, "8/8/4k3/8/8/3K4/6P1/8 w - - 0 60");
+    execute_test(None, "8/8/4k3/2b5/8/3K4/8/6N1 w - - 0 60");
+}
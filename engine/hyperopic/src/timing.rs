@@ -1,9 +1,25 @@
+use crate::moves::{Move, Moves};
+use crate::position::Position;
+use crate::see;
 use std::cmp::{max, min};
 use std::time::Duration;
 
 const DEFAULT_MIN_COMPUTE_TIME_MS: u64 = 50;
 const DEFAULT_MIN_CLOCK_TIME_MILLIS: u64 = 250;
 const DEFAULT_LATENCY_MILLIS: u64 = 5;
+const DEFAULT_OPPONENT_LOW_TIME_MILLIS: u64 = 10_000;
+/// Lower bound on the fraction of the usual allocation used when the opponent is low on time
+const MIN_OPPONENT_LOW_TIME_SCALE: f64 = 0.25;
+/// Extra moves assumed on top of the moves to go before the next time control so the
+/// clock is not run down to the minimum just as the control is reached
+const MOVES_TO_GO_BUFFER: f64 = 1.0;
+/// Fraction of the usual allocation used when the last move can be recaptured without
+/// losing material, the recapture is nearly always best so needs less thought
+const RECAPTURE_SCALE: f64 = 0.5;
+/// Fraction of an allocation after which no new iteration of the search is started
+const SOFT_LIMIT_SCALE: f64 = 0.5;
+/// Upper bound on any allocation so the result can always be safely added to an instant
+const MAX_COMPUTE_TIME: Duration = Duration::from_secs(60 * 60 * 24);
 
 #[derive(Debug, Clone)]
 pub struct TimeAllocator {
@@ -14,6 +30,9 @@ pub struct TimeAllocator {
     latency: Duration,
     min_compute_time: Duration,
     min_clock_time: Duration,
+    /// When the opponent has less than this remaining, and less than us, we move
+    /// faster to keep the pressure on their clock
+    opponent_low_time: Duration,
 }
 
 impl Default for TimeAllocator {
@@ -23,6 +42,7 @@ impl Default for TimeAllocator {
             latency: Duration::from_millis(DEFAULT_LATENCY_MILLIS),
             min_compute_time: Duration::from_millis(DEFAULT_MIN_COMPUTE_TIME_MS),
             min_clock_time: Duration::from_millis(DEFAULT_MIN_CLOCK_TIME_MILLIS),
+            opponent_low_time: Duration::from_millis(DEFAULT_OPPONENT_LOW_TIME_MILLIS),
         }
     }
 }
@@ -32,33 +52,134 @@ impl TimeAllocator {
         TimeAllocator { latency, ..Default::default() }
     }
 
-    // TODO Pass in position so we can reduce time thinking if there is a clear capture for example
+    /// The time added to computing each move which is not spent thinking, e.g. the UCI
+    /// move overhead
+    pub fn latency(&self) -> Duration {
+        self.latency
+    }
+
+    /// How long a search allocated the given time should keep starting new iterations,
+    /// one started later is unlikely to complete before the allocation runs out.
+    pub fn soft_limit(&self, allocated: Duration) -> Duration {
+        allocated.mul_f64(SOFT_LIMIT_SCALE)
+    }
+
+    /// Allocate time as in [TimeAllocator::allocate] for a move in the given position.
+    /// Only the minimum time is used if there is a single legal move and less than
+    /// usual if the last move was a capture with an obvious recapture.
+    pub fn allocate_for_position(
+        &self,
+        position: &Position,
+        remaining_time: Duration,
+        increment: Duration,
+        moves_to_go: Option<u32>,
+    ) -> Duration {
+        let allocated = self.allocate_with_moves_to_go(
+            position.history.len(),
+            remaining_time,
+            increment,
+            moves_to_go,
+        );
+        let moves = position.moves(&Moves::All);
+        if moves.len() <= 1 {
+            self.min_compute_time
+        } else if has_obvious_recapture(position, &moves) {
+            max(self.min_compute_time, allocated.mul_f64(RECAPTURE_SCALE))
+        } else {
+            allocated
+        }
+    }
+
+    /// Allocate thinking time for the next move, the expected length of the game is
+    /// estimated from the moves played so far.
     pub fn allocate(
         &self,
         half_moves_played: usize,
         remaining_time: Duration,
         increment: Duration,
     ) -> Duration {
-        let min_remaining_after_thinking = min(remaining_time, self.min_clock_time + self.latency);
+        self.allocate_with_moves_to_go(half_moves_played, remaining_time, increment, None)
+    }
+
+    /// Allocate thinking time for the next move. If the number of moves until the next
+    /// time control is known the remaining time is shared between those moves, otherwise
+    /// the expected length of the game is estimated from the moves played so far.
+    pub fn allocate_with_moves_to_go(
+        &self,
+        half_moves_played: usize,
+        remaining_time: Duration,
+        increment: Duration,
+        moves_to_go: Option<u32>,
+    ) -> Duration {
+        // The latency can only eat into time above the min clock time, not beyond it
+        let latency = min(self.latency, remaining_time.saturating_sub(self.min_clock_time));
+        let min_remaining_after_thinking =
+            min(remaining_time, self.min_clock_time.saturating_add(latency));
         let usable_thinking_time = remaining_time - min_remaining_after_thinking;
 
-        max(
+        let allocated = max(
             self.min_compute_time,
             if usable_thinking_time <= increment {
                 usable_thinking_time
             } else {
                 // Otherwise we think for the increment and then a little more
                 let thinking_time_after_increment = usable_thinking_time - increment;
-                let exp_remaining = (self.half_moves_remaining)(half_moves_played) / 2f64;
+                // Never plan for fewer than one more move so we cannot exceed the usable time
+                let exp_remaining = match moves_to_go {
+                    Some(n) => n as f64 + MOVES_TO_GO_BUFFER,
+                    None => (self.half_moves_remaining)(half_moves_played) / 2f64,
+                }
+                .max(1f64);
                 let extra_time = ((thinking_time_after_increment.as_millis() as f64)
                     / exp_remaining)
                     .round() as u64;
-                increment + Duration::from_millis(extra_time)
+                increment + min(thinking_time_after_increment, Duration::from_millis(extra_time))
             },
-        )
+        );
+        min(allocated, MAX_COMPUTE_TIME)
+    }
+
+    /// Allocate time as in [TimeAllocator::allocate_for_position] but also take the
+    /// opponent's clock into account if it is known. If they are about to flag we play
+    /// faster moves so they have less time to think on ours.
+    pub fn allocate_against_opponent(
+        &self,
+        position: &Position,
+        remaining_time: Duration,
+        increment: Duration,
+        moves_to_go: Option<u32>,
+        opponent_remaining_time: Option<Duration>,
+    ) -> Duration {
+        let allocated =
+            self.allocate_for_position(position, remaining_time, increment, moves_to_go);
+        match opponent_remaining_time {
+            Some(opponent) if opponent < self.opponent_low_time && opponent < remaining_time => {
+                let scale = (opponent.as_secs_f64() / self.opponent_low_time.as_secs_f64())
+                    .max(MIN_OPPONENT_LOW_TIME_SCALE);
+                max(self.min_compute_time, allocated.mul_f64(scale))
+            }
+            _ => allocated,
+        }
     }
 }
 
+/// Whether the last move captured on a square where the side to move can capture back
+/// and come out ahead in the exchange
+fn has_obvious_recapture(position: &Position, moves: &[Move]) -> bool {
+    let captured_on = match position.history.last() {
+        Some((_, Move::Normal { dest, capture: Some(_), .. }))
+        | Some((_, Move::Promote { dest, capture: Some(_), .. })) => *dest,
+        _ => return false,
+    };
+    moves.iter().any(|m| match m {
+        Move::Normal { dest, capture: Some(_), .. }
+        | Move::Promote { dest, capture: Some(_), .. } => {
+            *dest == captured_on && see(position, m) > 0
+        }
+        _ => false,
+    })
+}
+
 /// https://chess.stackexchange.com/questions/2506/what-is-the-average-length-of-a-game-of-chess
 fn expected_half_moves_remaining(moves_played: usize) -> f64 {
     let k = moves_played as f64;
@@ -69,7 +190,11 @@ fn expected_half_moves_remaining(moves_played: usize) -> f64 {
 mod test {
     use std::time::Duration;
 
-    use crate::timing::TimeAllocator;
+    use crate::moves::Moves;
+    use crate::position::Position;
+    use crate::timing::{
+        DEFAULT_MIN_CLOCK_TIME_MILLIS, DEFAULT_MIN_COMPUTE_TIME_MS, MAX_COMPUTE_TIME, TimeAllocator,
+    };
 
     fn dummy_half_moves_remaining(moves_played: usize) -> f64 {
         moves_played as f64
@@ -82,6 +207,7 @@ mod test {
             min_compute_time: Duration::from_millis(500),
             latency: Duration::from_millis(200),
             min_clock_time: Duration::from_millis(250),
+            opponent_low_time: Duration::from_secs(10),
         };
         assert_eq!(
             Duration::from_millis(1355),
@@ -96,6 +222,7 @@ mod test {
             min_compute_time: Duration::from_millis(1100),
             latency: Duration::from_millis(200),
             min_clock_time: Duration::from_millis(250),
+            opponent_low_time: Duration::from_secs(10),
         };
         assert_eq!(
             Duration::from_millis(1100),
@@ -110,6 +237,7 @@ mod test {
             min_compute_time: Duration::from_millis(1100),
             latency: Duration::from_millis(200),
             min_clock_time: Duration::from_millis(250),
+            opponent_low_time: Duration::from_secs(10),
         };
 
         assert_eq!(
@@ -125,6 +253,7 @@ mod test {
             min_compute_time: Duration::from_millis(1100),
             latency: Duration::from_millis(200),
             min_clock_time: Duration::from_millis(250),
+            opponent_low_time: Duration::from_secs(10),
         };
 
         assert_eq!(
@@ -140,6 +269,7 @@ mod test {
             min_compute_time: Duration::from_millis(100),
             latency: Duration::from_millis(200),
             min_clock_time: Duration::from_millis(250),
+            opponent_low_time: Duration::from_secs(10),
         };
 
         assert_eq!(
@@ -155,10 +285,181 @@ mod test {
             min_compute_time: Duration::from_millis(50),
             latency: Duration::from_millis(5),
             min_clock_time: Duration::from_millis(250),
+            opponent_low_time: Duration::from_secs(10),
         };
         assert_eq!(
             Duration::from_millis(749),
             timing.allocate(224, Duration::from_millis(1004), Duration::from_millis(1000))
         );
     }
+
+    #[test]
+    fn max_remaining_time() {
+        let timing = TimeAllocator::default();
+        assert_eq!(MAX_COMPUTE_TIME, timing.allocate(20, Duration::MAX, Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn max_remaining_time_and_increment() {
+        let timing = TimeAllocator::default();
+        assert_eq!(MAX_COMPUTE_TIME, timing.allocate(usize::MAX, Duration::MAX, Duration::MAX));
+    }
+
+    #[test]
+    fn max_latency() {
+        let timing = TimeAllocator::with_latency(Duration::MAX);
+        assert_eq!(
+            Duration::from_millis(DEFAULT_MIN_COMPUTE_TIME_MS),
+            timing.allocate(20, Duration::from_secs(60), Duration::from_secs(1))
+        );
+    }
+
+    #[test]
+    fn larger_latency_reduces_allocation() {
+        let remaining = Duration::from_secs(30);
+        let increment = Duration::from_millis(500);
+        let allocate = |millis: u64| {
+            TimeAllocator::with_latency(Duration::from_millis(millis))
+                .allocate(20, remaining, increment)
+        };
+        assert!(allocate(100) < allocate(5));
+        assert!(allocate(2000) < allocate(100));
+    }
+
+    #[test]
+    fn latency_with_very_low_clock() {
+        let timing = TimeAllocator::with_latency(Duration::from_millis(1000));
+        for remaining in [0, 1, 100, 250, 300, 1200] {
+            let allocated = timing.allocate(20, Duration::from_millis(remaining), Duration::ZERO);
+            assert_eq!(Duration::from_millis(DEFAULT_MIN_COMPUTE_TIME_MS), allocated);
+        }
+    }
+
+    #[test]
+    fn moves_to_go_shares_remaining_time() {
+        let timing = TimeAllocator::default();
+        let remaining = Duration::from_secs(60);
+        let allocate = |moves_to_go| {
+            timing.allocate_with_moves_to_go(20, remaining, Duration::ZERO, moves_to_go)
+        };
+        // (60000 - 255) / (4 + 1) rather than the estimate of around 40 moves remaining
+        assert_eq!(Duration::from_millis(11949), allocate(Some(4)));
+        assert!(allocate(None) < allocate(Some(30)));
+        assert!(allocate(Some(30)) < allocate(Some(4)));
+        assert!(allocate(Some(4)) < allocate(Some(1)));
+        assert!(allocate(Some(0)) < remaining);
+    }
+
+    #[test]
+    fn moves_to_go_with_increment() {
+        let timing = TimeAllocator::default();
+        let remaining = Duration::from_secs(30);
+        let increment = Duration::from_secs(2);
+        // 2000 + (30000 - 255 - 2000) / (9 + 1)
+        assert_eq!(
+            Duration::from_millis(4775),
+            timing.allocate_with_moves_to_go(60, remaining, increment, Some(9))
+        );
+        assert_ne!(
+            timing.allocate(60, remaining, increment),
+            timing.allocate_with_moves_to_go(60, remaining, increment, Some(9))
+        );
+    }
+
+    #[test]
+    fn last_move_before_control_keeps_buffer() {
+        let timing = TimeAllocator::default();
+        let remaining = Duration::from_secs(10);
+        let allocated = timing.allocate_with_moves_to_go(80, remaining, Duration::ZERO, Some(1));
+        // Only half the usable time is spent even though the clock is topped up next move
+        assert_eq!(Duration::from_millis(4873), allocated);
+        assert!(remaining - allocated > Duration::from_millis(DEFAULT_MIN_CLOCK_TIME_MILLIS));
+    }
+
+    #[test]
+    fn single_legal_move_uses_min_time() {
+        let timing = TimeAllocator::default();
+        let position: Position = "k7/8/8/8/8/8/1q6/K7 w - - 0 1".parse().unwrap();
+        assert_eq!(1, position.moves(&Moves::All).len());
+        assert_eq!(
+            Duration::from_millis(DEFAULT_MIN_COMPUTE_TIME_MS),
+            timing.allocate_for_position(&position, Duration::from_secs(60), Duration::ZERO, None)
+        );
+    }
+
+    #[test]
+    fn obvious_recapture_reduces_time() {
+        let timing = TimeAllocator::default();
+        let remaining = Duration::from_secs(60);
+        let allocate = |moves: &str| {
+            let mut position = Position::default();
+            position.play(moves).unwrap();
+            timing.allocate_for_position(&position, remaining, Duration::ZERO, None)
+        };
+        let usual = timing.allocate(3, remaining, Duration::ZERO);
+        assert_eq!(usual, allocate("e2e4 d7d5 b1c3"));
+        assert_eq!(usual.mul_f64(0.5), allocate("e2e4 d7d5 e4d5"));
+
+        // Taking back the pawn would lose the queen to the rook
+        let mut position: Position = "3qk3/8/8/3p4/4P3/8/8/3RK3 w - - 0 1".parse().unwrap();
+        position.play("e4d5").unwrap();
+        assert_eq!(
+            timing.allocate(1, remaining, Duration::ZERO),
+            timing.allocate_for_position(&position, remaining, Duration::ZERO, None)
+        );
+    }
+
+    #[test]
+    fn no_half_moves_remaining() {
+        let timing = TimeAllocator {
+            half_moves_remaining: |_| 0f64,
+            min_compute_time: Duration::from_millis(50),
+            latency: Duration::from_millis(5),
+            min_clock_time: Duration::from_millis(250),
+            opponent_low_time: Duration::from_secs(10),
+        };
+        assert_eq!(
+            Duration::from_millis(745),
+            timing.allocate(usize::MAX, Duration::from_millis(1000), Duration::ZERO)
+        );
+    }
+
+    #[test]
+    fn opponent_low_on_time() {
+        let timing = TimeAllocator::default();
+        let remaining = Duration::from_secs(60);
+        let position = Position::default();
+        let symmetric = timing.allocate_against_opponent(
+            &position,
+            remaining,
+            Duration::ZERO,
+            None,
+            Some(remaining),
+        );
+        let opponent_low = timing.allocate_against_opponent(
+            &position,
+            remaining,
+            Duration::ZERO,
+            None,
+            Some(Duration::from_secs(2)),
+        );
+        assert_eq!(timing.allocate(0, remaining, Duration::ZERO), symmetric);
+        assert_eq!(symmetric.mul_f64(0.25), opponent_low);
+    }
+
+    #[test]
+    fn both_low_on_time() {
+        let timing = TimeAllocator::default();
+        let remaining = Duration::from_secs(2);
+        assert_eq!(
+            timing.allocate(0, remaining, Duration::ZERO),
+            timing.allocate_against_opponent(
+                &Position::default(),
+                remaining,
+                Duration::ZERO,
+                None,
+                Some(Duration::from_secs(3))
+            )
+        );
+    }
 }
@@ -3,7 +3,8 @@ use async_trait::async_trait;
 use chrono::{DateTime, Datelike, Timelike, Utc};
 use clap::Parser;
 use hyperopic::Engine;
-use hyperopic::openings::OpeningService;
+use hyperopic::openings::{CachedFetcher, DiversityHint, OpeningHistory, OpeningService};
+use hyperopic::search::ConcurrentTT;
 use lazy_static::lazy_static;
 use lichess_api::ratings::{ChallengeRequest, OnlineBot, TimeLimitType, TimeLimits};
 use lichess_api::{LichessClient, LichessEndgameClient};
@@ -22,6 +23,8 @@ use tokio::sync::mpsc::{Receiver, Sender};
 use tokio::time::sleep;
 
 const TABLE_SIZE: usize = 5_000_000;
+/// Book positions remembered per game, more than a game can look up within the book depth
+const OPENING_CACHE_CAPACITY: usize = 64;
 
 lazy_static! {
     // Every 10 days we do 2 blitz days, 1 rapid and 7 bullet
@@ -133,7 +136,7 @@ async fn search_for_game(args: &Args, bot_id: String, mut rx: Receiver<GameStart
 async fn backoff(index: u32) {
     let base_wait = Duration::from_secs(120);
     let max_wait = Duration::from_secs(600);
-    sleep(std::cmp::min(max_wait, index * base_wait)).await;
+    sleep(std::cmp::min(max_wait, base_wait.saturating_mul(index))).await;
 }
 
 fn get_active_time_range(args: &Args) -> Vec<Range<DateTime<Utc>>> {
@@ -282,14 +285,15 @@ async fn run_event_stream(auth_token: String, bot_id: String, tx: Sender<GameSta
             auth_token: auth_token.clone(),
             lichess: LichessClient::new(auth_token.clone()),
             games_started: Default::default(),
-            table_size: TABLE_SIZE,
+            opening_history: Default::default(),
+            table: Arc::new(ConcurrentTT::new(TABLE_SIZE)),
             tx,
         },
     )
     .await;
 }
 
-fn opening_table() -> OpeningService<DynamoOpeningClient> {
+fn opening_table(diversity: DiversityHint) -> OpeningService<CachedFetcher<DynamoOpeningClient>> {
     OpeningTable {
         name: "MyopicOpenings".to_string(),
         region: "eu-west-2".to_string(),
@@ -298,7 +302,8 @@ fn opening_table() -> OpeningService<DynamoOpeningClient> {
         max_depth: 10,
     }
     .try_into()
-    .map(|client| OpeningService::new(client))
+    .map(|client| CachedFetcher::new(client, OPENING_CACHE_CAPACITY))
+    .map(|client| OpeningService { diversity: Some(diversity), ..OpeningService::new(client) })
     .expect("Bad opening table config")
 }
 
@@ -307,7 +312,10 @@ struct EventProcessorImpl {
     auth_token: String,
     lichess: LichessClient,
     games_started: HashSet<String>,
-    table_size: usize,
+    /// Lines played in recent games so each new game varies its opening
+    opening_history: OpeningHistory,
+    /// Shared by the engines of every game so memory use does not grow with the games
+    table: Arc<ConcurrentTT>,
     tx: Sender<GameStarted>,
 }
 
@@ -330,9 +338,12 @@ impl EventProcessor for EventProcessorImpl {
                         our_bot_id: self.our_bot_id.clone(),
                         auth_token: self.auth_token.clone(),
                     };
-                    let engine = Engine::new(
-                        self.table_size,
-                        vec![Arc::new(opening_table()), Arc::new(LichessEndgameClient::default())],
+                    let engine = Engine::with_table(
+                        self.table.clone(),
+                        vec![
+                            Arc::new(opening_table(self.opening_history.hint())),
+                            Arc::new(LichessEndgameClient::default()),
+                        ],
                     );
                     self.tx
                         .send(GameStarted {
@@ -2,8 +2,7 @@ use std::collections::HashMap;
 use std::str::FromStr;
 
 use anyhow::{Error, Result, anyhow};
-use hyperopic::openings::{OpeningMoveFetcher, OpeningMoveRecord};
-use itertools::Itertools;
+use hyperopic::openings::{OpeningMoveFetcher, OpeningMoveRecord, book_key};
 use log::info;
 use rusoto_core::Region;
 use rusoto_dynamodb::{AttributeValue, DynamoDb, DynamoDbClient, GetItemInput};
@@ -38,7 +37,7 @@ impl TryFrom<OpeningTable> for DynamoOpeningClient {
 impl OpeningMoveFetcher for DynamoOpeningClient {
     fn lookup(&self, position_key: &str) -> Result<Vec<OpeningMoveRecord>> {
         tokio::runtime::Builder::new_current_thread().enable_all().build()?.block_on(async {
-            let index = position_key.to_string().split_whitespace().take(3).join(" ");
+            let index = book_key(position_key);
             info!("Querying table {} for position {}", self.params.name, index);
             self.client
                 .get_item(self.create_request(index))
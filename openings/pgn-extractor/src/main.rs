@@ -5,8 +5,8 @@ mod game_stream;
 use errors::Errors;
 use game_stream::GameStream;
 use hyperopic::moves::Move;
+use hyperopic::openings::book_key;
 use hyperopic::position::Position;
-use itertools::Itertools;
 use std::{collections::HashMap, error::Error, fs, fs::File, path::PathBuf};
 use structopt::StructOpt;
 
@@ -141,7 +141,7 @@ fn parse_entries(
                 Move::Enpassant { .. } => {}
                 _ => {
                     entries.push(CollectionEntry {
-                        position: board.to_string().split_whitespace().take(3).join(" "),
+                        position: book_key(&board.to_string()),
                         mv: mv.to_string(),
                     });
                 }